@@ -0,0 +1,297 @@
+use crate::api::{APIError, AccountInfo, DirectMessage, EraseOutcome, TwitterAPI};
+use crate::pacing::RateLimitBudget;
+use chrono::prelude::*;
+use egg_mode::tweet::Tweet;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+
+/// Line of the action log, recording a request that modified a tweet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionLogEntry {
+    pub id: u64,
+    /// One of `unliked`, `unretweeted` or `deleted`
+    pub action: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Reads the ids of the tweets recorded in the action log at `path`. A missing file is an empty log,
+/// as in the first run
+pub fn handled_ids(path: &str) -> Result<HashSet<u64>, String> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(format!("Couldn't open action log {}: {}", path, e)),
+    };
+
+    let mut ids = HashSet::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Couldn't read action log {}: {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: ActionLogEntry = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid line {} in action log {}: {}", number + 1, path, e))?;
+        ids.insert(entry.id);
+    }
+    Ok(ids)
+}
+
+/// Wrapper over a `TwitterAPI` that appends a line to the action log, as JSON, for every unlike,
+/// unretweet and erase request that succeeds. A tweet of a concurrent batch that fails isn't logged,
+/// as we don't know which of its requests went through. Failing to write the log doesn't stop the
+/// run.
+pub struct ActionLogAPI<'r> {
+    api: &'r mut dyn TwitterAPI,
+    path: String,
+    file: File,
+}
+
+impl<'r> ActionLogAPI<'r> {
+    /// Opens the action log at `path`, appending to it if it exists
+    pub fn open(api: &'r mut dyn TwitterAPI, path: &str) -> Result<ActionLogAPI<'r>, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Couldn't open action log {}: {}", path, e))?;
+        Ok(ActionLogAPI {
+            api,
+            path: String::from(path),
+            file,
+        })
+    }
+
+    fn record(&mut self, id: u64, action: &str) {
+        let entry = ActionLogEntry {
+            id,
+            action: String::from(action),
+            at: Utc::now(),
+        };
+        let written = serde_json::to_string(&entry)
+            .map_err(|e| e.to_string())
+            .and_then(|line| writeln!(self.file, "{}", line).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            warn!("Couldn't write to action log {}: {}", self.path, e);
+        }
+    }
+}
+
+impl<'r> TwitterAPI for ActionLogAPI<'r> {
+    fn validate_token(&mut self) -> Result<(), APIError> {
+        self.api.validate_token()
+    }
+
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError> {
+        self.api.get_user_id(screen_name)
+    }
+
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        self.api.get_account_info(screen_name)
+    }
+
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.api.get_statuses_count()
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        self.api.get_tweet(id)
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        self.api.rate_limit_status()
+    }
+
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        self.api.reply_count(tweet)
+    }
+
+    fn api_calls(&self) -> usize {
+        self.api.api_calls()
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.api.token_user_id()
+    }
+
+    fn tweet_count(&self) -> Option<u64> {
+        self.api.tweet_count()
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.api.pinned_tweet_id()
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        self.api.user_id()
+    }
+
+    fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.user_timeline_next_page()
+    }
+
+    fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.likes_timeline_next_page()
+    }
+
+    fn unlike_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        let result = self.api.unlike_tweet(tweet);
+        if result.is_ok() {
+            self.record(tweet.id, "unliked");
+        }
+        result
+    }
+
+    fn unretweet_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        let result = self.api.unretweet_tweet(tweet);
+        if result.is_ok() {
+            self.record(tweet.id, "unretweeted");
+        }
+        result
+    }
+
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
+        let result = self.api.erase_tweet(tweet);
+        if result == Ok(EraseOutcome::Deleted) {
+            self.record(tweet.id, "deleted");
+        }
+        result
+    }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        self.api.direct_messages_next_page()
+    }
+
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError> {
+        self.api.erase_dm(dm)
+    }
+
+    fn maintain_tweets(
+        &mut self,
+        tweets: &[&Tweet],
+        concurrency: usize,
+    ) -> Vec<Result<EraseOutcome, APIError>> {
+        let results = self.api.maintain_tweets(tweets, concurrency);
+        // a tweet of the batch that didn't fail was unliked and unretweeted as needed, even if it
+        // wasn't erased
+        for (tweet, result) in tweets.iter().zip(&results) {
+            if let Ok(outcome) = result {
+                if tweet.favorited.unwrap_or(false) {
+                    self.record(tweet.id, "unliked");
+                }
+                if tweet.retweeted.unwrap_or(false) {
+                    self.record(tweet.id, "unretweeted");
+                }
+                if *outcome == EraseOutcome::Deleted {
+                    self.record(tweet.id, "deleted");
+                }
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handled_ids, ActionLogAPI, ActionLogEntry};
+    use crate::api::{APIError, EraseOutcome, TestAPI, TwitterAPI};
+    use crate::tests::sample_tweet;
+    use std::collections::HashSet;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn records_the_tweets_modified() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_action_log_{}.jsonl",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let mut api = TestAPI {
+            ..Default::default()
+        };
+        let mut tweet = sample_tweet(1);
+        tweet.id = 42;
+
+        {
+            let mut log_api = ActionLogAPI::open(&mut api, path).unwrap();
+            log_api.unlike_tweet(&tweet).unwrap();
+            log_api.erase_tweet(&tweet).unwrap();
+        }
+        let ids = handled_ids(path);
+        let lines = fs::read_to_string(path).unwrap().lines().count();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(lines, 2);
+        assert_eq!(ids, Ok(vec![42].into_iter().collect::<HashSet<u64>>()));
+        assert_eq!(
+            api.methods_called_in_order,
+            vec!["unlike_tweet", "erase_tweet"]
+        );
+    }
+
+    #[test]
+    fn records_the_tweets_modified_in_a_batch() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_action_log_batch_{}.jsonl",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let mut api = TestAPI {
+            erase_tweet_answer: Ok(EraseOutcome::Skipped(String::from(
+                "tweet not posted by the user",
+            ))),
+            ..Default::default()
+        };
+        let mut liked = sample_tweet(1);
+        liked.id = 1;
+        liked.favorited = Some(true);
+        let mut retweeted = sample_tweet(1);
+        retweeted.id = 2;
+        retweeted.retweeted = Some(true);
+
+        {
+            let mut log_api = ActionLogAPI::open(&mut api, path).unwrap();
+            log_api.maintain_tweets(&[&liked, &retweeted], 2);
+        }
+        api.erase_tweet_answer = Ok(EraseOutcome::Deleted);
+        {
+            let mut log_api = ActionLogAPI::open(&mut api, path).unwrap();
+            log_api.maintain_tweets(&[&liked], 2);
+        }
+        api.erase_tweet_answer = Err(APIError::ErasureError(String::from("error")));
+        {
+            let mut log_api = ActionLogAPI::open(&mut api, path).unwrap();
+            log_api.maintain_tweets(&[&retweeted], 2);
+        }
+        let entries: Vec<(u64, String)> = fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let entry: ActionLogEntry = serde_json::from_str(line).unwrap();
+                (entry.id, entry.action)
+            })
+            .collect();
+        fs::remove_file(path).unwrap();
+
+        let expected: Vec<(u64, String)> = vec![
+            (1, "unliked"),
+            (2, "unretweeted"),
+            (1, "unliked"),
+            (1, "deleted"),
+        ]
+        .into_iter()
+        .map(|(id, action)| (id, String::from(action)))
+        .collect();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn missing_log_has_no_ids() {
+        assert_eq!(
+            handled_ids("/non/existent/action_log.jsonl"),
+            Ok(HashSet::new())
+        );
+    }
+}
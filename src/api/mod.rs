@@ -1,9 +1,18 @@
+use crate::cursor::Cursors;
+use crate::pacing::{AdaptiveDelay, Cooldown, Pacing, RateLimitBudget};
+use crate::retry::Backoff;
 use crate::EnvValues;
-use egg_mode;
+use chrono::prelude::*;
+use egg_mode::direct;
+use egg_mode::search;
 use egg_mode::tweet;
 use egg_mode::tweet::{Timeline, Tweet};
-use std::error::Error;
+use futures::{future, stream, Future, Stream};
+use std::cell::Cell;
+use std::convert::TryFrom;
 use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
 use tokio::runtime::current_thread::block_on_all;
 
 /// Defines errors that can happen when calling the API methods
@@ -12,7 +21,47 @@ pub enum APIError {
     InvalidToken,
     TimelineError(String),
     UserDetailsError(String),
+    /// No account has the given user handle
+    UserNotFound(String),
+    /// The account with the given user handle is suspended
+    UserSuspended(String),
     ErasureError(String),
+    /// Error retrieving a single tweet, like one that no longer exists
+    TweetLookupError(String),
+    /// Error retrieving the rate limit budget
+    RateLimitStatusError(String),
+    /// The user handle we resolved doesn't belong to the account of the token
+    AccountMismatch {
+        screen_name: String,
+        user_id: u64,
+        token_user_id: u64,
+    },
+    /// Error processing a given tweet, with the id of the tweet
+    TweetError {
+        id: u64,
+        source: Box<APIError>,
+    },
+}
+
+impl APIError {
+    /// Attaches the id of the tweet that caused this error, unless the error already has one
+    pub fn for_tweet(self, id: u64) -> APIError {
+        match self {
+            APIError::TweetError { .. } => self,
+            other => APIError::TweetError {
+                id,
+                source: Box::new(other),
+            },
+        }
+    }
+
+    /// Returns the id of the tweet that caused this error, if known
+    pub fn tweet_id(&self) -> Option<u64> {
+        match self {
+            APIError::TweetError { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for APIError {
@@ -26,16 +75,102 @@ impl fmt::Display for APIError {
                 write!(f, "Error when retrieving data from a timeline: {}", s)
             }
             APIError::UserDetailsError(s) => write!(f, "Failure obtaining user details: {}", s),
+            APIError::UserNotFound(screen_name) => {
+                write!(f, "User @{} not found, check the user handle", screen_name)
+            }
+            APIError::UserSuspended(screen_name) => {
+                write!(f, "User @{} is suspended by Twitter", screen_name)
+            }
             APIError::ErasureError(s) => {
                 write!(f, "Failure removing link between tweet and user: {}", s)
             }
+            APIError::TweetLookupError(s) => write!(f, "Failure retrieving tweet: {}", s),
+            APIError::RateLimitStatusError(s) => {
+                write!(f, "Failure retrieving the rate limit status: {}", s)
+            }
+            APIError::AccountMismatch {
+                screen_name,
+                user_id,
+                token_user_id,
+            } => write!(
+                f,
+                "@{} (id {}) is not the account of the token used (id {}), check the user handle",
+                screen_name, user_id, token_user_id
+            ),
+            APIError::TweetError { id, source } => write!(f, "Tweet #{}: {}", id, source),
         }
     }
 }
 
+/// Outcome of a request to erase a tweet
+#[derive(Debug, Clone, PartialEq)]
+pub enum EraseOutcome {
+    /// The tweet was deleted from Twitter
+    Deleted,
+    /// The tweet was left untouched, for the given reason
+    Skipped(String),
+}
+
+/// The details of a direct message we need to decide if we erase it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectMessage {
+    pub id: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&direct::DirectMessage> for DirectMessage {
+    fn from(dm: &direct::DirectMessage) -> Self {
+        DirectMessage {
+            id: dm.id,
+            created_at: dm.created_at,
+        }
+    }
+}
+
+/// The details of the account we process, as returned by Twitter
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountInfo {
+    pub user_id: u64,
+    pub screen_name: String,
+    /// Number of tweets the user posted, including retweets
+    pub statuses_count: u64,
+}
+
+impl fmt::Display for AccountInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "@{} (id {}), {} tweets",
+            self.screen_name, self.user_id, self.statuses_count
+        )
+    }
+}
+
 /// Trait that defines methods we need to interact with the Twitter API
 /// Created so we can avoid real API calls during testing, using a stub instead
 pub trait TwitterAPI {
+    /// Verifies the token used to talk to Twitter is valid
+    fn validate_token(&mut self) -> Result<(), APIError>;
+
+    /// Returns the id of the user with the given screen name
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError>;
+
+    /// Returns the details of the account with the given screen name
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError>;
+
+    /// Returns the number of tweets posted by the user whose timelines we process
+    fn get_statuses_count(&mut self) -> Result<u64, APIError>;
+
+    /// Returns the tweet with the given id
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError>;
+
+    /// Returns the requests left in the current rate limit window for retrieving tweets. When
+    /// several requests have their own limits, returns the one with the smallest share left
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError>;
+
+    /// Returns the number of replies to the given tweet we can find
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError>;
+
     /// Returns the next page available of user timeline for given user id, which contains tweets published (or retweeted) by user
     fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError>;
 
@@ -48,16 +183,140 @@ pub trait TwitterAPI {
     // Unretweets a tweets the user retweeted before
     fn unretweet_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError>;
 
-    // Erases a tweet posted by the user
-    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError>;
+    /// Erases a tweet posted by the user. Tweets posted by other users are skipped
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError>;
+
+    /// Returns the next page available of direct messages sent or received by the user
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError>;
+
+    /// Erases a direct message sent or received by the user
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError>;
+
+    /// Returns the number of requests sent to Twitter API so far
+    fn api_calls(&self) -> usize;
+
+    /// Returns the id of the user the token belongs to, if known. Set once the token is validated
+    fn token_user_id(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the number of tweets the user posted or liked, if known
+    fn tweet_count(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the id of the tweet pinned to the profile of the user, if known
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the id of the user whose timelines we process, if known
+    fn user_id(&self) -> Option<u64> {
+        None
+    }
+
+    /// Unlikes, unretweets and erases each one of the given tweets, as needed, working on up to
+    /// `concurrency` tweets at the same time. Returns the outcome for each tweet, in the same order.
+    ///
+    /// The default implementation processes the tweets one by one.
+    fn maintain_tweets(
+        &mut self,
+        tweets: &[&Tweet],
+        _concurrency: usize,
+    ) -> Vec<Result<EraseOutcome, APIError>> {
+        tweets
+            .iter()
+            .map(|tweet| {
+                let process = |api: &mut Self| {
+                    if tweet.favorited.unwrap_or(false) {
+                        api.unlike_tweet(tweet)?;
+                    }
+                    if tweet.retweeted.unwrap_or(false) {
+                        api.unretweet_tweet(tweet)?;
+                    }
+                    api.erase_tweet(tweet)
+                };
+                process(self).map_err(|e| e.for_tweet(tweet.id))
+            })
+            .collect()
+    }
 }
 
+/// Number of tweets we request in each page of a timeline
+pub const PAGE_SIZE: i32 = 25;
+
+/// Requests sent between checks of the rate limit budget, when the pacing adapts to it
+const BUDGET_CHECK_EVERY: usize = 50;
+
+/// Future of a request sent through a `Transport`
+pub type TransportFuture =
+    Box<dyn Future<Item = egg_mode::Response<Tweet>, Error = egg_mode::error::Error>>;
+
+/// Sends the requests of `RealAPI` that modify tweets. `EggModeTransport` sends them to Twitter,
+/// and tests can pass another one to `RealAPI::with_transport` to run the retries and rate limit
+/// pauses of those requests without the network. egg-mode doesn't let us change its endpoints, so
+/// the rest of requests, like validating the token or paging through timelines, always go to
+/// Twitter: the pagination of `RealAPI` still can't be tested against a mock backend
+pub trait Transport {
+    fn unlike(&self, id: u64, token: &egg_mode::Token) -> TransportFuture;
+    fn unretweet(&self, id: u64, token: &egg_mode::Token) -> TransportFuture;
+    fn delete(&self, id: u64, token: &egg_mode::Token) -> TransportFuture;
+}
+
+/// `Transport` that sends the requests to Twitter with egg-mode
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EggModeTransport;
+
+impl Transport for EggModeTransport {
+    fn unlike(&self, id: u64, token: &egg_mode::Token) -> TransportFuture {
+        Box::new(tweet::unlike(id, token))
+    }
+
+    fn unretweet(&self, id: u64, token: &egg_mode::Token) -> TransportFuture {
+        Box::new(tweet::unretweet(id, token))
+    }
+
+    fn delete(&self, id: u64, token: &egg_mode::Token) -> TransportFuture {
+        Box::new(tweet::delete(id, token))
+    }
+}
+
+// keys of the timelines in `Cursors`
+const USER_TIMELINE_CURSOR: &str = "user_timeline";
+const LIKES_TIMELINE_CURSOR: &str = "likes_timeline";
+
 /// Struct that has an implementation of TwitterAPI that calls twitter servers
 pub struct RealAPI<'a> {
     pub user_id: u64,
     pub token: egg_mode::Token,
+    /// User the token belongs to, as returned when validating it
+    pub token_user_id: Option<u64>,
+    /// Tweets posted plus tweets liked by the user, as reported by the user details
+    pub tweet_count: Option<u64>,
+    /// Tweets posted by the user, as reported by the user details
+    pub statuses_count: Option<u64>,
+    /// Tweet pinned to the profile of the user. The user details returned by egg_mode don't
+    /// include it, so it comes from the configuration
+    pub pinned_tweet_id: Option<u64>,
     pub user_timeline: Option<Timeline<'a>>,
     pub likes_timeline: Option<Timeline<'a>>,
+    pub received_dms: Option<direct::Timeline>,
+    pub sent_dms: Option<direct::Timeline>,
+    /// Requests sent to Twitter API. Shared with the requests we run concurrently, so they can count themselves
+    pub api_calls: Rc<Cell<usize>>,
+    /// Policy to retry requests that fail due to transient errors
+    pub backoff: Backoff,
+    /// Wait before each request that erases tweets or direct messages
+    pub pacing: Pacing,
+    /// Requests sent when we last checked the rate limit budget, for adaptive pacing
+    pub budget_checked_at: Option<usize>,
+    /// When the rate limit window resets, if a response told us we used it up. Shared like `api_calls`
+    pub rate_limit_pause: RateLimitPause,
+    /// Where we resume each timeline from, and the file where we keep them between runs
+    pub cursors: Cursors,
+    pub cursor_path: Option<String>,
+    /// Sends the unlike, unretweet and erase requests. Shared like `api_calls`
+    pub transport: Rc<dyn Transport>,
 }
 
 impl<'a> RealAPI<'a> {
@@ -75,78 +334,258 @@ impl<'a> RealAPI<'a> {
     /// - the `api` parameter returns some error when we use its methods
     ///
     pub fn new(env: EnvValues) -> Result<RealAPI<'a>, APIError> {
+        RealAPI::with_transport(env, Rc::new(EggModeTransport))
+    }
+
+    /// Like `new`, but sends the unlike, unretweet and erase requests through `transport`. The
+    /// token is still validated with Twitter, and timelines are still paged through Twitter, so only
+    /// the requests that modify tweets can be tested without the network
+    pub fn with_transport(
+        env: EnvValues,
+        transport: Rc<dyn Transport>,
+    ) -> Result<RealAPI<'a>, APIError> {
         info!("Creating Real API object");
 
-        let con_token = egg_mode::KeyPair::new(env.consumer_key, env.consumer_secret);
-        let access_token = egg_mode::KeyPair::new(env.access_key, env.access_secret);
-        let token = egg_mode::Token::Access {
-            consumer: con_token,
-            access: access_token,
+        // tweets are only erased once all the timelines are collected, so a checkpoint of the pages
+        // collected would skip tweets that weren't erased yet
+        let cursor_path = env.cursor_path.clone().filter(|_| !env.oldest_first);
+        if env.oldest_first && env.cursor_path.is_some() {
+            warn!("Ignoring the checkpoint file, as it isn't supported when erasing oldest first");
+        }
+        let cursors = match &cursor_path {
+            Some(path) if env.resume => Cursors::read_from(path).unwrap_or_else(|e| {
+                warn!("{}, processing the timelines from the start", e);
+                Cursors::default()
+            }),
+            Some(_) => Cursors::default(),
+            None => {
+                if env.resume {
+                    warn!("Nothing to resume without a checkpoint file, set TP_CURSOR_PATH");
+                }
+                Cursors::default()
+            }
         };
 
+        let mut pacing = Pacing::new(
+            Duration::from_millis(env.delete_delay_ms),
+            Duration::from_millis(env.delete_jitter_ms),
+        );
+        if env.adaptive_delay_ms > 0 {
+            pacing = pacing.with_adaptive(AdaptiveDelay {
+                max_delay: Duration::from_millis(env.adaptive_delay_ms),
+            });
+        }
+        if let Some(every) = env.cooldown_every {
+            pacing = pacing.with_cooldown(Cooldown {
+                every,
+                pause: Duration::from_secs(env.cooldown_seconds),
+            });
+        }
+
         let mut api = RealAPI {
             user_id: 0,
-            token,
+            token: env.token(),
+            token_user_id: None,
+            tweet_count: None,
+            statuses_count: None,
+            pinned_tweet_id: env.pinned_tweet_id,
             user_timeline: None,
             likes_timeline: None,
+            received_dms: None,
+            sent_dms: None,
+            api_calls: Rc::new(Cell::new(0)),
+            backoff: Backoff::new(
+                Duration::from_millis(env.retry_base_delay_ms),
+                2,
+                env.retry_attempts,
+            )
+            .with_jitter(Duration::from_millis(env.retry_jitter_ms)),
+            pacing,
+            budget_checked_at: None,
+            rate_limit_pause: RateLimitPause::default(),
+            cursors,
+            cursor_path,
+            transport,
         };
 
-        RealAPI::validate_token(&mut api)?;
-        RealAPI::obtain_user_id(&mut api, &env.user_handle)?;
+        api.user_id = connect(&mut api, &env.user_handle)?;
 
         info!("Welcome back, {}!", &env.user_handle);
 
         Ok(api)
     }
+}
+
+/// Validates the token of the given `api` and returns the id of the user with the given screen name
+///
+/// # Error scenarios
+///
+/// The method will return an `Err` if:
+///
+/// - the token is not valid, in which case we don't query the user details
+/// - the user details can't be retrieved
+/// - the user is not the one the token belongs to
+pub fn connect(api: &mut dyn TwitterAPI, screen_name: &str) -> Result<u64, APIError> {
+    api.validate_token()?;
+    let user_id = api.get_user_id(screen_name)?;
+    check_token_user(api, screen_name, user_id)?;
+    Ok(user_id)
+}
+
+/// Returns an error if we know the token of `api` belongs to a user other than `user_id`. Otherwise we
+/// would process the timelines of another user, where we can't erase anything
+pub fn check_token_user(
+    api: &dyn TwitterAPI,
+    screen_name: &str,
+    user_id: u64,
+) -> Result<(), APIError> {
+    match api.token_user_id() {
+        Some(token_user_id) if token_user_id != user_id => Err(APIError::AccountMismatch {
+            screen_name: String::from(screen_name),
+            user_id,
+            token_user_id,
+        }),
+        _ => Ok(()),
+    }
+}
 
-    fn validate_token(api: &mut RealAPI) -> Result<(), APIError> {
+impl<'a> TwitterAPI for RealAPI<'a> {
+    fn validate_token(&mut self) -> Result<(), APIError> {
         info!("Verifying validity of Token by querying Twitter API");
 
-        if let Err(err) = block_on_all(egg_mode::verify_tokens(&api.token)) {
-            error!("We've hit an error using your tokens: {:?}. Invalid tokens, the application can't continue.", err);
-            Err(APIError::InvalidToken)
-        } else {
-            info!("Tokens seem to be valid");
-            Ok(())
+        match self.send(egg_mode::verify_tokens) {
+            Err(err) => {
+                error!("We've hit an error using your tokens: {:?}. Invalid tokens, the application can't continue.", err);
+                Err(APIError::InvalidToken)
+            }
+            Ok(user) => {
+                info!(
+                    "Tokens seem to be valid, they belong to @{}",
+                    user.screen_name
+                );
+                self.token_user_id = Some(user.id);
+                Ok(())
+            }
         }
     }
 
-    fn obtain_user_id(api: &mut RealAPI, screen_name: &str) -> Result<(), APIError> {
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError> {
         info!("Requesting user id for user {}", screen_name);
+        self.get_account_info(screen_name).map(|info| info.user_id)
+    }
 
-        let query_for_user = block_on_all(egg_mode::user::show(screen_name, &api.token));
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        let query_for_user = self.send(|token| egg_mode::user::show(screen_name, token));
 
         let user_info = match query_for_user {
             Ok(uinfo) => uinfo,
-            Err(e) => return Err(APIError::UserDetailsError(e.description().to_string())),
+            Err(e) => return Err(user_details_error(screen_name, &e)),
         };
 
         info!(
             "Retrieved user id {} for {} (@{})",
             user_info.id, user_info.name, user_info.screen_name
         );
+        let posted = u64::try_from(user_info.statuses_count).unwrap_or(0);
+        let liked = u64::try_from(user_info.favourites_count).unwrap_or(0);
+        self.tweet_count = Some(posted + liked);
+        self.statuses_count = Some(posted);
 
-        api.user_id = user_info.id;
+        Ok(AccountInfo {
+            user_id: user_info.id,
+            screen_name: user_info.screen_name.clone(),
+            statuses_count: posted,
+        })
+    }
 
-        Ok(())
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.statuses_count.ok_or_else(|| {
+            APIError::UserDetailsError(String::from("User details not retrieved yet"))
+        })
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        info!("Requesting tweet #{}", id);
+        self.send(|token| tweet::show(id, token))
+            .map_err(|e| APIError::TweetLookupError(e.to_string()).for_tweet(id))
+    }
+
+    // the standard search only covers the last days, so replies older than that are not counted
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        let screen_name = match &tweet.user {
+            Some(author) => author.screen_name.clone(),
+            None => return Ok(0),
+        };
+        info!("Requesting replies to tweet #{}", tweet.id);
+
+        let query = format!("to:{}", screen_name);
+        let (token, api_calls) = (&self.token, &self.api_calls);
+        let (backoff, rate_limit_pause) = (&self.backoff, &self.rate_limit_pause);
+        let result = backoff.retry_with(is_transient, rate_limit_wait, || {
+            rate_limit_pause.wait(backoff);
+            count_call(api_calls);
+            let found = block_on_all(
+                search::search(query.clone())
+                    .result_type(search::ResultType::Recent)
+                    .since_tweet(tweet.id)
+                    .count(100)
+                    .call(token),
+            )?;
+            rate_limit_pause.record(&found);
+            Ok(found)
+        });
+
+        let found =
+            result.map_err(|e| APIError::TweetLookupError(e.to_string()).for_tweet(tweet.id))?;
+        let replies = found
+            .response
+            .statuses
+            .iter()
+            .filter(|reply| reply.in_reply_to_status_id == Some(tweet.id))
+            .count();
+        Ok(replies as u64)
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        use egg_mode::service::TweetMethod;
+
+        let status = self
+            .send(egg_mode::service::rate_limit_status)
+            .map_err(|e| APIError::RateLimitStatusError(e.to_string()))?;
+        let budgets = [
+            TweetMethod::UserTimeline,
+            TweetMethod::LikedBy,
+            TweetMethod::Show,
+        ];
+        budgets
+            .iter()
+            .filter_map(|method| status.tweet.get(method))
+            .map(|limits| RateLimitBudget {
+                remaining: u32::try_from(limits.rate_limit_remaining).unwrap_or(0),
+                limit: u32::try_from(limits.rate_limit).unwrap_or(0),
+            })
+            .min_by_key(|budget| {
+                // share left in thousandths, so budgets with different limits compare
+                u64::from(budget.remaining) * 1000 / u64::from(budget.limit.max(1))
+            })
+            .ok_or_else(|| {
+                APIError::RateLimitStatusError(String::from("no limits for retrieving tweets"))
+            })
     }
-}
 
-impl<'a> TwitterAPI for RealAPI<'a> {
     fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
         info!(
             "Requesting next page of User timeline for user #{}",
             self.user_id
         );
 
-        let timeline = self.user_timeline.take().unwrap_or_else(|| {
-            tweet::user_timeline(self.user_id, true, true, &self.token).with_page_size(25)
-        });
-
-        fn store_tl<'r, 'a>(api: &'r mut RealAPI<'a>, tl: Timeline<'a>) {
-            api.user_timeline = Some(tl);
+        fn new_tl<'a>(api: &RealAPI<'a>) -> Timeline<'a> {
+            tweet::user_timeline(api.user_id, true, true, &api.token).with_page_size(PAGE_SIZE)
+        }
+        fn stored_tl<'r, 'a>(api: &'r mut RealAPI<'a>) -> &'r mut Option<Timeline<'a>> {
+            &mut api.user_timeline
         }
-        progress_timeline(self, timeline, store_tl)
+        progress_timeline(self, USER_TIMELINE_CURSOR, new_tl, stored_tl)
     }
 
     fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
@@ -155,14 +594,13 @@ impl<'a> TwitterAPI for RealAPI<'a> {
             self.user_id
         );
 
-        let timeline = self.likes_timeline.take().unwrap_or_else(|| {
-            tweet::liked_by(self.user_id, &self.token).with_page_size(25)
-        });
-
-        fn store_tl<'r, 'a>(api: &'r mut RealAPI<'a>, tl: Timeline<'a>) {
-            api.likes_timeline = Some(tl);
+        fn new_tl<'a>(api: &RealAPI<'a>) -> Timeline<'a> {
+            tweet::liked_by(api.user_id, &api.token).with_page_size(PAGE_SIZE)
+        }
+        fn stored_tl<'r, 'a>(api: &'r mut RealAPI<'a>) -> &'r mut Option<Timeline<'a>> {
+            &mut api.likes_timeline
         }
-        progress_timeline(self, timeline, store_tl)
+        progress_timeline(self, LIKES_TIMELINE_CURSOR, new_tl, stored_tl)
     }
 
     fn unlike_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
@@ -172,8 +610,8 @@ impl<'a> TwitterAPI for RealAPI<'a> {
                 tweet.id, tweet.created_at
             );
 
-            block_on_all(tweet::unlike(tweet.id, &self.token))
-                .map_err(|e| APIError::ErasureError(e.description().to_string()))
+            self.send(|token| self.transport.unlike(tweet.id, token))
+                .map_err(|e| APIError::ErasureError(e.to_string()).for_tweet(tweet.id))
                 .map(|_| ())
         } else {
             warn!(
@@ -191,8 +629,8 @@ impl<'a> TwitterAPI for RealAPI<'a> {
                 tweet.id, tweet.created_at
             );
 
-            block_on_all(tweet::unretweet(tweet.id, &self.token))
-                .map_err(|e| APIError::ErasureError(e.description().to_string()))
+            self.send(|token| self.transport.unretweet(tweet.id, token))
+                .map_err(|e| APIError::ErasureError(e.to_string()).for_tweet(tweet.id))
                 .map(|_| ())
         } else {
             warn!(
@@ -203,91 +641,652 @@ impl<'a> TwitterAPI for RealAPI<'a> {
         }
     }
 
-    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
-        let is_own_tweet = match tweet.user {
-            Some(ref tu) if tu.id != self.user_id => false,
-            _ => true,
-        };
-
-        if is_own_tweet {
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
+        if self.is_own_tweet(tweet) {
             info!(
                 "Requesting removal of tweet #{} posted at {}",
                 tweet.id, tweet.created_at
             );
 
-            if let Err(e) = block_on_all(tweet::delete(tweet.id, &self.token)) {
-                warn!("Couldn't erase tweet #{}. Error received: {}", tweet.id, e);
-            }
+            self.pace();
+            self.send(|token| self.transport.delete(tweet.id, token))
+                .map_err(|e| APIError::ErasureError(e.to_string()).for_tweet(tweet.id))?;
+            self.pacing.record_deletions(1);
+            Ok(EraseOutcome::Deleted)
         } else {
             warn!(
                 "Tried to delete tweet #{} which it not posted by the user",
                 tweet.id
             );
+            Ok(EraseOutcome::Skipped(String::from(
+                "tweet not posted by the user",
+            )))
         }
+    }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        let token = &self.token;
+        let received = self
+            .received_dms
+            .get_or_insert_with(|| direct::received(token).with_page_size(PAGE_SIZE));
+        let (backoff, api_calls, pause) = (&self.backoff, &self.api_calls, &self.rate_limit_pause);
+        let mut page = progress_dms(received, backoff, api_calls, pause)?;
 
+        let sent = self
+            .sent_dms
+            .get_or_insert_with(|| direct::sent(token).with_page_size(PAGE_SIZE));
+        page.extend(progress_dms(sent, backoff, api_calls, pause)?);
+        Ok(page)
+    }
+
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError> {
+        info!(
+            "Requesting removal of direct message #{} sent at {}",
+            dm.id, dm.created_at
+        );
+
+        self.pace();
+        self.send(|token| direct::delete(dm.id, token))
+            .map_err(|e| {
+                APIError::ErasureError(format!("Couldn't erase direct message #{}: {}", dm.id, e))
+            })?;
+        self.pacing.record_deletions(1);
         Ok(())
     }
+
+    fn api_calls(&self) -> usize {
+        self.api_calls.get()
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.token_user_id
+    }
+
+    fn tweet_count(&self) -> Option<u64> {
+        self.tweet_count
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.pinned_tweet_id
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        Some(self.user_id)
+    }
+
+    fn maintain_tweets(
+        &mut self,
+        tweets: &[&Tweet],
+        concurrency: usize,
+    ) -> Vec<Result<EraseOutcome, APIError>> {
+        info!(
+            "Requesting removal of {} tweets, up to {} at the same time",
+            tweets.len(),
+            concurrency
+        );
+
+        // the requests of a batch are sent together, so we wait once before the whole batch
+        self.pace();
+        let progress: Vec<_> = tweets
+            .iter()
+            .map(|_| Rc::new(Cell::new(MaintenanceStep::Unlike)))
+            .collect();
+        // tweets that fail with a transient error are sent again in a smaller batch, from the step
+        // that failed, so a retry doesn't repeat the requests that succeeded
+        let results =
+            self.backoff
+                .retry_each_with(tweets.len(), is_transient, rate_limit_wait, |pending| {
+                    let requests: Vec<_> = pending
+                        .iter()
+                        .map(|&index| {
+                            self.maintenance_request(tweets[index], progress[index].clone())
+                        })
+                        .collect();
+                    // `buffered` keeps at most `concurrency` requests in flight, and returns results in order
+                    let all_requests = stream::iter_ok::<_, ()>(requests)
+                        .buffered(concurrency.max(1))
+                        .collect();
+
+                    // requests never fail at the stream level, errors are part of each item
+                    block_on_all(all_requests).unwrap_or_default()
+                });
+        let results: Vec<_> = results
+            .into_iter()
+            .zip(tweets)
+            .map(|(result, tweet)| match result {
+                Some(result) => {
+                    result.map_err(|e| APIError::ErasureError(e.to_string()).for_tweet(tweet.id))
+                }
+                None => {
+                    Err(APIError::ErasureError(String::from("Missing result")).for_tweet(tweet.id))
+                }
+            })
+            .collect();
+        let deleted = results
+            .iter()
+            .filter(|result| **result == Ok(EraseOutcome::Deleted))
+            .count();
+        self.pacing.record_deletions(deleted);
+        results
+    }
+}
+
+// a request that unlikes, unretweets and erases a tweet, in that order, stopping on the first error
+type MaintenanceRequest =
+    Box<dyn Future<Item = Result<EraseOutcome, egg_mode::error::Error>, Error = ()>>;
+
+// next request to send to maintain a tweet, so we can retry a failed request without repeating the
+// ones before it
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum MaintenanceStep {
+    Unlike,
+    Unretweet,
+    Erase,
+}
+
+impl<'a> RealAPI<'a> {
+    fn count_call(&self) {
+        count_call(&self.api_calls);
+    }
+
+    // sends the request built by `request`, retrying it as per `backoff` if it fails due to a transient error
+    fn send<T, F, R>(&self, request: F) -> Result<T, egg_mode::error::Error>
+    where
+        F: Fn(&egg_mode::Token) -> R,
+        R: Future<Item = egg_mode::Response<T>, Error = egg_mode::error::Error>,
+    {
+        self.backoff.retry_with(is_transient, rate_limit_wait, || {
+            self.rate_limit_pause.wait(&self.backoff);
+            self.count_call();
+            let response = block_on_all(request(&self.token))?;
+            self.rate_limit_pause.record(&response);
+            Ok(response.response)
+        })
+    }
+
+    // waits before a request that erases something. If the pacing adapts to the rate limit budget,
+    // we check the budget first, every `BUDGET_CHECK_EVERY` requests
+    fn pace(&mut self) {
+        if self.pacing.adaptive.is_some() {
+            let calls = self.api_calls.get();
+            let due = match self.budget_checked_at {
+                Some(checked_at) => calls >= checked_at + BUDGET_CHECK_EVERY,
+                None => true,
+            };
+            if due {
+                self.budget_checked_at = Some(calls);
+                match self.rate_limit_status() {
+                    Ok(budget) => {
+                        debug!("Rate limit budget: {:?}", budget);
+                        self.pacing.record_budget(budget)
+                    }
+                    Err(e) => warn!("Keeping the current pace: {}", e),
+                }
+            }
+        }
+        self.pacing.wait();
+    }
+
+    // records the oldest tweet of the pages of a timeline already processed, in the cursor file if set
+    fn save_cursor(&mut self, cursor: &str, oldest_id: Option<u64>) {
+        self.cursors.set(cursor, oldest_id);
+        if let Some(path) = &self.cursor_path {
+            if let Err(e) = self.cursors.write_to(path) {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    fn is_own_tweet(&self, tweet: &Tweet) -> bool {
+        !matches!(tweet.user, Some(ref tu) if tu.id != self.user_id)
+    }
+
+    // builds the requests still to send to maintain `tweet`, as per `progress`, which they update as
+    // they succeed
+    fn maintenance_request(
+        &self,
+        tweet: &Tweet,
+        progress: Rc<Cell<MaintenanceStep>>,
+    ) -> MaintenanceRequest {
+        let id = tweet.id;
+        let favorited = tweet.favorited.unwrap_or(false);
+        let retweeted = tweet.retweeted.unwrap_or(false);
+        let is_own_tweet = self.is_own_tweet(tweet);
+        let (unlike_token, unretweet_token, delete_token) =
+            (self.token.clone(), self.token.clone(), self.token.clone());
+        let (unretweet_calls, delete_calls) = (self.api_calls.clone(), self.api_calls.clone());
+        let (unlike_progress, unretweet_progress) = (progress.clone(), progress.clone());
+        let (pause, backoff) = (self.rate_limit_pause.clone(), self.backoff.clone());
+        let (unretweet_pause, unretweet_backoff) = (pause.clone(), backoff.clone());
+        let (unlike_transport, unretweet_transport, delete_transport) = (
+            self.transport.clone(),
+            self.transport.clone(),
+            self.transport.clone(),
+        );
+
+        type Step<T> = Box<dyn Future<Item = T, Error = egg_mode::error::Error>>;
+        let unlike: Step<()> = if favorited && progress.get() <= MaintenanceStep::Unlike {
+            self.count_call();
+            Box::new(
+                paced_request(&pause, &backoff, move || {
+                    unlike_transport.unlike(id, &unlike_token)
+                })
+                .map(move |_| unlike_progress.set(MaintenanceStep::Unretweet)),
+            )
+        } else {
+            Box::new(future::ok(()))
+        };
+
+        let unretweet = move |_| -> Step<()> {
+            if retweeted && progress.get() <= MaintenanceStep::Unretweet {
+                count_call(&unretweet_calls);
+                Box::new(
+                    paced_request(&unretweet_pause, &unretweet_backoff, move || {
+                        unretweet_transport.unretweet(id, &unretweet_token)
+                    })
+                    .map(move |_| unretweet_progress.set(MaintenanceStep::Erase)),
+                )
+            } else {
+                Box::new(future::ok(()))
+            }
+        };
+
+        let erase = move |_| -> Step<EraseOutcome> {
+            if is_own_tweet {
+                count_call(&delete_calls);
+                Box::new(
+                    paced_request(&pause, &backoff, move || {
+                        delete_transport.delete(id, &delete_token)
+                    })
+                    .map(|_| EraseOutcome::Deleted),
+                )
+            } else {
+                Box::new(future::ok(EraseOutcome::Skipped(String::from(
+                    "tweet not posted by the user",
+                ))))
+            }
+        };
+
+        Box::new(unlike.and_then(unretweet).and_then(erase).then(Ok))
+    }
+}
+
+fn count_call(api_calls: &Rc<Cell<usize>>) {
+    api_calls.set(api_calls.get() + 1);
+}
+
+// sends a request of a concurrent batch once the rate limit pause shared by the whole batch is over,
+// and records in the pause the rate limit of its response, so the requests still to start wait for
+// the window to reset instead of failing
+fn paced_request<T, F, R>(
+    pause: &RateLimitPause,
+    backoff: &Backoff,
+    request: F,
+) -> impl Future<Item = egg_mode::Response<T>, Error = egg_mode::error::Error>
+where
+    F: FnOnce() -> R,
+    R: Future<Item = egg_mode::Response<T>, Error = egg_mode::error::Error>,
+{
+    let (pause, backoff) = (pause.clone(), backoff.clone());
+    let (waited, recorded) = (pause.clone(), pause);
+    future::lazy(move || {
+        waited.wait(&backoff);
+        request()
+    })
+    .then(move |result| {
+        match &result {
+            Ok(response) => recorded.record(response),
+            Err(e) => recorded.record_error(e),
+        }
+        result
+    })
+}
+
+// requests the next page of a timeline of direct messages. Failed requests don't move the timeline,
+// so we can retry them as they are
+fn progress_dms(
+    timeline: &mut direct::Timeline,
+    backoff: &Backoff,
+    api_calls: &Rc<Cell<usize>>,
+    rate_limit_pause: &RateLimitPause,
+) -> Result<Vec<DirectMessage>, APIError> {
+    let result = backoff.retry_with(is_transient, rate_limit_wait, || {
+        rate_limit_pause.wait(backoff);
+        count_call(api_calls);
+        let page = block_on_all(timeline.older(None))?;
+        rate_limit_pause.record(&page);
+        Ok(page)
+    });
+
+    result
+        .map(|page| page.response.iter().map(DirectMessage::from).collect())
+        .map_err(|e| APIError::TimelineError(e.to_string()))
+}
+
+/// Returns true if the error may go away by sending the same request again, like network errors,
+/// errors on Twitter servers or reaching the rate limit
+pub fn is_transient(error: &egg_mode::error::Error) -> bool {
+    use egg_mode::error::Error;
+    match error {
+        Error::NetError(_) | Error::RateLimit(_) => true,
+        Error::BadStatus(status) => status.is_server_error() || status.as_u16() == 429,
+        Error::TwitterError(errors) => errors
+            .errors
+            .iter()
+            .any(|e| e.code == RATE_LIMIT_ERROR_CODE),
+        _ => false,
+    }
 }
 
-fn progress_timeline<'r, 'a, F>(
-    api: &'r mut RealAPI<'a>,
-    timeline: Timeline<'a>,
-    store_tl: F,
+// Error code Twitter uses when a request exceeds the rate limit
+const RATE_LIMIT_ERROR_CODE: i32 = 88;
+
+// Error codes Twitter uses when the requested user doesn't exist or is suspended
+const USER_NOT_FOUND_ERROR_CODE: i32 = 50;
+const USER_SUSPENDED_ERROR_CODE: i32 = 63;
+
+/// Maps the error returned when requesting the details of `screen_name`, telling apart the accounts
+/// that don't exist or are suspended, as retrying won't help with those
+pub fn user_details_error(screen_name: &str, error: &egg_mode::error::Error) -> APIError {
+    use egg_mode::error::Error;
+    let has_code = |code| match error {
+        Error::TwitterError(errors) => errors.errors.iter().any(|e| e.code == code),
+        _ => false,
+    };
+    let not_found_status = matches!(error, Error::BadStatus(status) if status.as_u16() == 404);
+
+    if has_code(USER_SUSPENDED_ERROR_CODE) {
+        APIError::UserSuspended(String::from(screen_name))
+    } else if has_code(USER_NOT_FOUND_ERROR_CODE) || not_found_status {
+        APIError::UserNotFound(String::from(screen_name))
+    } else {
+        APIError::UserDetailsError(error.to_string())
+    }
+}
+
+// Length of the rate limit windows of Twitter API
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+// if we hit the rate limit and Twitter told us when it resets (`x-rate-limit-reset` header), we wait
+// until then, or a whole window if it didn't. Otherwise the backoff falls back to its exponential delay
+fn rate_limit_wait(error: &egg_mode::error::Error) -> Option<Duration> {
+    use egg_mode::error::Error;
+    match error {
+        Error::RateLimit(reset) => {
+            Some(time_until_reset(i64::from(*reset), Utc::now().timestamp()))
+        }
+        Error::BadStatus(status) if status.as_u16() == 429 => Some(RATE_LIMIT_WINDOW),
+        Error::TwitterError(errors)
+            if errors
+                .errors
+                .iter()
+                .any(|e| e.code == RATE_LIMIT_ERROR_CODE) =>
+        {
+            Some(RATE_LIMIT_WINDOW)
+        }
+        _ => None,
+    }
+}
+
+/// Pauses requests once the rate limit headers of a response say no requests are left in the
+/// current window, until the window resets, so the next request doesn't fail. Clones share the state
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitPause(Rc<Cell<Option<i64>>>);
+
+impl RateLimitPause {
+    /// Records when the rate limit window resets if `response` used up its requests
+    pub fn record<T>(&self, response: &egg_mode::Response<T>) {
+        if response.rate_limit > 0 && response.rate_limit_remaining <= 0 {
+            self.0.set(Some(i64::from(response.rate_limit_reset)));
+        }
+    }
+
+    /// Records when the rate limit window resets if `error` says we ran out of requests
+    pub fn record_error(&self, error: &egg_mode::error::Error) {
+        if let egg_mode::error::Error::RateLimit(reset) = error {
+            self.0.set(Some(i64::from(*reset)));
+        }
+    }
+
+    /// Waits with the clock of `backoff` until the window recorded as used up resets, if any
+    pub fn wait(&self, backoff: &Backoff) {
+        self.wait_at(backoff, Utc::now().timestamp())
+    }
+
+    // like `wait`, at the given Unix timestamp
+    fn wait_at(&self, backoff: &Backoff, now: i64) {
+        if let Some(reset) = self.0.take() {
+            let pause = time_until_reset(reset, now);
+            if pause > Duration::from_secs(0) {
+                warn!("Rate limit used up, pausing {:?} until it resets", pause);
+                backoff.sleep(pause);
+            }
+        }
+    }
+}
+
+/// Returns how long to wait from `now` until the rate limit window resets at `reset`, both as Unix
+/// timestamps in seconds. No time if the reset is already in the past
+pub fn time_until_reset(reset: i64, now: i64) -> Duration {
+    Duration::from_secs(u64::try_from(reset - now).unwrap_or(0))
+}
+
+// requests the next page of the timeline stored in the `stored_tl` slot of the api, or of a new one
+// built with `new_tl` if there is none. Failed requests consume the timeline, so we rebuild it at the
+// same position before retrying or returning the error.
+fn progress_timeline<'a, N, S>(
+    api: &mut RealAPI<'a>,
+    cursor: &str,
+    new_tl: N,
+    stored_tl: S,
 ) -> Result<Vec<Tweet>, APIError>
 where
-    F: Fn(&'r mut RealAPI<'a>, Timeline<'a>) -> (),
+    N: Fn(&RealAPI<'a>) -> Timeline<'a>,
+    S: for<'r> Fn(&'r mut RealAPI<'a>) -> &'r mut Option<Timeline<'a>>,
 {
-    let future_timeline = timeline.older(None);
-    match block_on_all(future_timeline) {
-        Ok((new_tl, feed)) => {
-            store_tl(api, new_tl);
-            Ok(feed.response)
+    let backoff = api.backoff.clone();
+    let result = backoff.retry_with(is_transient, rate_limit_wait, || {
+        let stored = stored_tl(api).take();
+        let timeline = stored.unwrap_or_else(|| {
+            // a new timeline starts from the checkpoint of an interrupted run, if any
+            let mut timeline = new_tl(api);
+            timeline.min_id = api.cursors.get(cursor);
+            timeline
+        });
+        let (min_id, max_id) = (timeline.min_id, timeline.max_id);
+
+        api.rate_limit_pause.wait(&backoff);
+        api.count_call();
+        match block_on_all(timeline.older(None)) {
+            Ok((new_timeline, feed)) => {
+                api.rate_limit_pause.record(&feed);
+                let page = unseen_tweets(cursor, feed.response, min_id);
+                // pages are requested once the previous one is processed, so everything older than
+                // `min_id` is handled. The tweets of this page are retrieved again if the run stops
+                // before processing them all
+                let handled_id = if page.is_empty() { None } else { min_id };
+                api.save_cursor(cursor, handled_id);
+                *stored_tl(api) = Some(new_timeline);
+                Ok(page)
+            }
+            Err(e) => {
+                let mut timeline = new_tl(api);
+                timeline.min_id = min_id;
+                timeline.max_id = max_id;
+                *stored_tl(api) = Some(timeline);
+                Err(e)
+            }
         }
-        Err(e) => Err(APIError::TimelineError(e.description().to_string())),
+    });
+
+    result.map_err(|e| APIError::TimelineError(e.to_string()))
+}
+
+/// Removes from a page of a timeline the tweets that aren't older than `seen_min_id`, the oldest
+/// tweet of the previous page. Each page should be older than the previous one, so those tweets mean
+/// Twitter went back in the pagination, and a page left empty means we would go in circles, so we
+/// return it empty to stop paging through the timeline `name`
+fn unseen_tweets(name: &str, page: Vec<Tweet>, seen_min_id: Option<u64>) -> Vec<Tweet> {
+    let retrieved = page.len();
+    let page: Vec<Tweet> = match seen_min_id {
+        Some(seen_min_id) => page.into_iter().filter(|t| t.id < seen_min_id).collect(),
+        None => page,
+    };
+    if retrieved > 0 && page.is_empty() {
+        warn!(
+            "Stopping {} as it returned tweets we already retrieved",
+            name
+        );
+    } else if page.len() < retrieved {
+        warn!(
+            "Ignoring {} tweets of {} we already retrieved",
+            retrieved - page.len(),
+            name
+        );
     }
+    page
 }
 
+#[cfg(test)]
+use std::collections::{HashMap, VecDeque};
 #[cfg(test)]
 use std::default::Default;
 
 /// Struct that has a stub implementation of TwitterAPI that doesn't trigger network calls
+///
+/// Each call to a `*_next_page` method pops the next of the corresponding pages, and returns an empty
+/// page once there are none left, as a real timeline does when we reach its end.
 #[cfg(test)]
 #[derive(Debug)]
 pub struct TestAPI {
-    pub user_timeline_next_page_answer: Result<Vec<Tweet>, APIError>,
-    pub likes_timeline_next_page_answer: Result<Vec<Tweet>, APIError>,
+    pub validate_token_answer: Result<(), APIError>,
+    pub get_user_id_answer: Result<u64, APIError>,
+    pub get_statuses_count_answer: Result<u64, APIError>,
+    pub rate_limit_status_answer: Result<RateLimitBudget, APIError>,
+    /// Tweets returned by `get_tweet`, any other id is not found
+    pub tweets_by_id: HashMap<u64, Tweet>,
+    /// Replies returned by `reply_count`, other tweets have none
+    pub reply_counts: HashMap<u64, u64>,
+    pub user_timeline_pages: VecDeque<Result<Vec<Tweet>, APIError>>,
+    pub likes_timeline_pages: VecDeque<Result<Vec<Tweet>, APIError>>,
     pub unlike_tweet_answer: Result<(), APIError>,
     pub unretweet_tweet_answer: Result<(), APIError>,
-    pub erase_tweet_answer: Result<(), APIError>,
+    pub erase_tweet_answer: Result<EraseOutcome, APIError>,
+    pub direct_messages_pages: VecDeque<Result<Vec<DirectMessage>, APIError>>,
+    pub erase_dm_answer: Result<(), APIError>,
+    pub pinned_tweet_id_answer: Option<u64>,
+    pub user_id_answer: Option<u64>,
+    pub token_user_id_answer: Option<u64>,
     pub methods_called_in_order: Vec<String>,
+    /// Ids of the tweets passed to `erase_tweet`, in order
+    pub erased_tweet_ids: Vec<u64>,
 }
 
 #[cfg(test)]
 impl Default for TestAPI {
     fn default() -> Self {
         TestAPI {
-            user_timeline_next_page_answer: Ok(vec![]),
-            likes_timeline_next_page_answer: Ok(vec![]),
+            validate_token_answer: Ok(()),
+            get_user_id_answer: Ok(1),
+            get_statuses_count_answer: Ok(0),
+            rate_limit_status_answer: Ok(RateLimitBudget {
+                remaining: 900,
+                limit: 900,
+            }),
+            tweets_by_id: HashMap::new(),
+            reply_counts: HashMap::new(),
+            user_timeline_pages: VecDeque::new(),
+            likes_timeline_pages: VecDeque::new(),
             unlike_tweet_answer: Ok(()),
             unretweet_tweet_answer: Ok(()),
-            erase_tweet_answer: Ok(()),
+            erase_tweet_answer: Ok(EraseOutcome::Deleted),
+            direct_messages_pages: VecDeque::new(),
+            erase_dm_answer: Ok(()),
+            pinned_tweet_id_answer: None,
+            user_id_answer: None,
+            token_user_id_answer: None,
             methods_called_in_order: Vec::new(),
+            erased_tweet_ids: Vec::new(),
         }
     }
 }
 
 #[cfg(test)]
 impl TwitterAPI for TestAPI {
+    fn api_calls(&self) -> usize {
+        self.methods_called_in_order.len()
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.pinned_tweet_id_answer
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        self.user_id_answer
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.token_user_id_answer
+    }
+
+    fn validate_token(&mut self) -> Result<(), APIError> {
+        self.methods_called_in_order
+            .push(String::from("validate_token"));
+        self.validate_token_answer.clone()
+    }
+
+    fn get_user_id(&mut self, _screen_name: &str) -> Result<u64, APIError> {
+        self.methods_called_in_order
+            .push(String::from("get_user_id"));
+        self.get_user_id_answer.clone()
+    }
+
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        self.methods_called_in_order
+            .push(String::from("get_account_info"));
+        self.get_user_id_answer.clone().map(|user_id| AccountInfo {
+            user_id,
+            screen_name: String::from(screen_name),
+            statuses_count: 0,
+        })
+    }
+
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.methods_called_in_order
+            .push(String::from("get_statuses_count"));
+        self.get_statuses_count_answer.clone()
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        self.methods_called_in_order
+            .push(String::from("rate_limit_status"));
+        self.rate_limit_status_answer.clone()
+    }
+
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        self.methods_called_in_order
+            .push(String::from("reply_count"));
+        Ok(self.reply_counts.get(&tweet.id).copied().unwrap_or(0))
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        self.methods_called_in_order.push(String::from("get_tweet"));
+        self.tweets_by_id
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| APIError::TweetLookupError(String::from("not found")).for_tweet(id))
+    }
+
     fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
         self.methods_called_in_order
             .push(String::from("user_timeline_next_page"));
-        self.user_timeline_next_page_answer.clone()
+        next_page(&mut self.user_timeline_pages)
     }
 
     fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
         self.methods_called_in_order
             .push(String::from("likes_timeline_next_page"));
-        self.likes_timeline_next_page_answer.clone()
+        next_page(&mut self.likes_timeline_pages)
     }
 
     fn unlike_tweet(&mut self, _tweet: &Tweet) -> Result<(), APIError> {
@@ -302,9 +1301,477 @@ impl TwitterAPI for TestAPI {
         self.unretweet_tweet_answer.clone()
     }
 
-    fn erase_tweet(&mut self, _tweet: &Tweet) -> Result<(), APIError> {
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
         self.methods_called_in_order
             .push(String::from("erase_tweet"));
+        self.erased_tweet_ids.push(tweet.id);
         self.erase_tweet_answer.clone()
     }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        self.methods_called_in_order
+            .push(String::from("direct_messages_next_page"));
+        next_page(&mut self.direct_messages_pages)
+    }
+
+    fn erase_dm(&mut self, _dm: &DirectMessage) -> Result<(), APIError> {
+        self.methods_called_in_order.push(String::from("erase_dm"));
+        self.erase_dm_answer.clone()
+    }
+}
+
+#[cfg(test)]
+fn next_page<T>(pages: &mut VecDeque<Result<Vec<T>, APIError>>) -> Result<Vec<T>, APIError> {
+    pages.pop_front().unwrap_or_else(|| Ok(Vec::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        connect, is_transient, paced_request, rate_limit_wait, time_until_reset, unseen_tweets,
+        user_details_error, APIError, RateLimitPause, TestAPI, TwitterAPI, RATE_LIMIT_WINDOW,
+    };
+    use super::{EraseOutcome, RealAPI, Transport, TransportFuture};
+    use crate::config::Settings;
+    use crate::cursor::Cursors;
+    use crate::pacing::Pacing;
+    use crate::retry::tests::FakeClock;
+    use crate::retry::Backoff;
+    use crate::tests::sample_tweet;
+    use crate::{clear_timeline, Progress};
+    use chrono::Utc;
+    use egg_mode::error::Error;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    #[test]
+    fn waits_until_rate_limit_reset() {
+        assert_eq!(
+            time_until_reset(1_570_000_900, 1_570_000_000),
+            Duration::from_secs(900)
+        );
+        assert_eq!(
+            time_until_reset(1_570_000_000, 1_570_000_000),
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            time_until_reset(1_570_000_000, 1_570_000_900),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn rate_limit_errors_are_transient() {
+        use egg_mode::error::{Error, TwitterErrorCode, TwitterErrors};
+
+        let rate_limited = TwitterErrors {
+            errors: vec![TwitterErrorCode {
+                message: String::from("Rate limit exceeded"),
+                code: 88,
+            }],
+        };
+        assert!(is_transient(&Error::RateLimit(1_570_000_000)));
+        assert!(is_transient(&Error::TwitterError(rate_limited)));
+        assert!(!is_transient(&Error::TwitterError(TwitterErrors {
+            errors: Vec::new()
+        })));
+    }
+
+    #[test]
+    fn waits_a_whole_window_if_the_rate_limit_reset_is_unknown() {
+        use egg_mode::error::{Error, TwitterErrorCode, TwitterErrors};
+
+        let rate_limited = TwitterErrors {
+            errors: vec![TwitterErrorCode {
+                message: String::from("Rate limit exceeded"),
+                code: 88,
+            }],
+        };
+        assert_eq!(
+            rate_limit_wait(&Error::TwitterError(rate_limited)),
+            Some(RATE_LIMIT_WINDOW)
+        );
+        assert_eq!(
+            rate_limit_wait(&Error::TwitterError(TwitterErrors { errors: Vec::new() })),
+            None
+        );
+    }
+
+    #[test]
+    fn pauses_until_a_used_up_rate_limit_window_resets() {
+        let clock = Rc::new(FakeClock::default());
+        let backoff = Backoff::default().with_clock(clock.clone());
+        let response = |remaining| egg_mode::Response {
+            rate_limit: 900,
+            rate_limit_remaining: remaining,
+            rate_limit_reset: 1_570_000_900,
+            response: (),
+        };
+        let pause = RateLimitPause::default();
+
+        pause.record(&response(5));
+        pause.wait_at(&backoff, 1_570_000_000);
+        assert!(clock.sleeps.borrow().is_empty());
+
+        pause.clone().record(&response(0));
+        pause.wait_at(&backoff, 1_570_000_000);
+        pause.wait_at(&backoff, 1_570_000_000);
+        assert_eq!(*clock.sleeps.borrow(), vec![Duration::from_secs(900)]);
+    }
+
+    /// `Transport` that answers the requests without the network, failing once the requests set
+    /// with `fail` before letting them succeed
+    #[derive(Default)]
+    struct FakeTransport {
+        failures: RefCell<HashMap<(&'static str, u64), Error>>,
+        requests: RefCell<Vec<(&'static str, u64)>>,
+    }
+
+    impl FakeTransport {
+        fn fail(&self, request: &'static str, id: u64, error: Error) {
+            self.failures.borrow_mut().insert((request, id), error);
+        }
+
+        fn answer(&self, request: &'static str, id: u64) -> TransportFuture {
+            self.requests.borrow_mut().push((request, id));
+            let result = match self.failures.borrow_mut().remove(&(request, id)) {
+                Some(e) => Err(e),
+                None => Ok(egg_mode::Response {
+                    rate_limit: 900,
+                    rate_limit_remaining: 899,
+                    rate_limit_reset: 0,
+                    response: sample_tweet(40),
+                }),
+            };
+            Box::new(futures::future::result(result))
+        }
+    }
+
+    impl Transport for FakeTransport {
+        fn unlike(&self, id: u64, _token: &egg_mode::Token) -> TransportFuture {
+            self.answer("unlike", id)
+        }
+
+        fn unretweet(&self, id: u64, _token: &egg_mode::Token) -> TransportFuture {
+            self.answer("unretweet", id)
+        }
+
+        fn delete(&self, id: u64, _token: &egg_mode::Token) -> TransportFuture {
+            self.answer("delete", id)
+        }
+    }
+
+    // `RealAPI` of user 7 that sends its requests through `transport`, and waits with `clock`
+    fn offline_api(transport: Rc<FakeTransport>, clock: &Rc<FakeClock>) -> RealAPI<'static> {
+        let keys = egg_mode::KeyPair::new("key", "secret");
+        RealAPI {
+            user_id: 7,
+            token: egg_mode::Token::Access {
+                consumer: keys.clone(),
+                access: keys,
+            },
+            token_user_id: Some(7),
+            tweet_count: None,
+            statuses_count: None,
+            pinned_tweet_id: None,
+            user_timeline: None,
+            likes_timeline: None,
+            received_dms: None,
+            sent_dms: None,
+            api_calls: Rc::new(Cell::new(0)),
+            backoff: Backoff::default().with_clock(clock.clone()),
+            pacing: Pacing::new(Duration::from_millis(0), Duration::from_millis(0)),
+            budget_checked_at: None,
+            rate_limit_pause: RateLimitPause::default(),
+            cursors: Cursors::default(),
+            cursor_path: None,
+            transport,
+        }
+    }
+
+    #[test]
+    fn erases_tweets_through_the_transport() {
+        let transport = Rc::new(FakeTransport::default());
+        transport.fail(
+            "unlike",
+            1,
+            Error::BadStatus(hyper::StatusCode::BAD_GATEWAY),
+        );
+        let clock = Rc::new(FakeClock::default());
+        let mut api = offline_api(transport.clone(), &clock);
+        let mut tweet = sample_tweet(40);
+        tweet.id = 1;
+        tweet.favorited = Some(true);
+
+        api.unlike_tweet(&tweet).unwrap();
+        assert_eq!(api.erase_tweet(&tweet), Ok(EraseOutcome::Deleted));
+
+        // the failed unlike is retried as any other request
+        assert_eq!(
+            *transport.requests.borrow(),
+            vec![("unlike", 1), ("unlike", 1), ("delete", 1)]
+        );
+        assert_eq!(api.api_calls(), 3);
+        assert_eq!(clock.sleeps.borrow().len(), 1);
+    }
+
+    #[test]
+    fn batch_retries_each_tweet_from_the_request_that_failed() {
+        let transport = Rc::new(FakeTransport::default());
+        transport.fail(
+            "delete",
+            2,
+            Error::BadStatus(hyper::StatusCode::BAD_GATEWAY),
+        );
+        let clock = Rc::new(FakeClock::default());
+        let mut api = offline_api(transport.clone(), &clock);
+        let tweets: Vec<_> = (1..=3)
+            .map(|id| {
+                let mut tweet = sample_tweet(40);
+                tweet.id = id;
+                tweet.favorited = Some(id == 2);
+                tweet
+            })
+            .collect();
+        let batch: Vec<_> = tweets.iter().collect();
+
+        let results = api.maintain_tweets(&batch, 3);
+
+        assert_eq!(results, vec![Ok(EraseOutcome::Deleted); 3]);
+        // only the failed erasure is sent again, not the unlike that went through before it
+        assert_eq!(
+            *transport.requests.borrow(),
+            vec![
+                ("delete", 1),
+                ("unlike", 2),
+                ("delete", 2),
+                ("delete", 3),
+                ("delete", 2)
+            ]
+        );
+        assert_eq!(clock.sleeps.borrow().len(), 1);
+    }
+
+    #[test]
+    fn batch_waits_once_a_request_hits_the_rate_limit() {
+        use egg_mode::error::Error;
+        use futures::{future, stream, Future, Stream};
+        use tokio::runtime::current_thread::block_on_all;
+
+        let clock = Rc::new(FakeClock::default());
+        let backoff = Backoff::default().with_clock(clock.clone());
+        let pause = RateLimitPause::default();
+        let reset = Utc::now().timestamp() + 900;
+        let sent = Rc::new(Cell::new(0));
+        let request = |result: Result<(), Error>| {
+            let sent = sent.clone();
+            paced_request(&pause, &backoff, move || {
+                sent.set(sent.get() + 1);
+                future::result(result.map(|response| egg_mode::Response {
+                    rate_limit: 900,
+                    rate_limit_remaining: 5,
+                    rate_limit_reset: 0,
+                    response,
+                }))
+            })
+            .then(Ok::<_, ()>)
+        };
+        let batch = vec![
+            request(Err(Error::RateLimit(reset as i32))),
+            request(Ok(())),
+            request(Ok(())),
+        ];
+
+        let results = block_on_all(stream::iter_ok(batch).buffered(3).collect()).unwrap();
+
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok() && results[2].is_ok());
+        assert_eq!(sent.get(), 3);
+        // the requests after the one that hit the limit waited for the window to reset, once
+        let sleeps = clock.sleeps.borrow();
+        assert_eq!(sleeps.len(), 1);
+        assert!(sleeps[0] > Duration::from_secs(890) && sleeps[0] <= Duration::from_secs(900));
+    }
+
+    #[test]
+    fn tells_apart_missing_and_suspended_users() {
+        use egg_mode::error::{Error, TwitterErrorCode, TwitterErrors};
+
+        let twitter_error = |code| {
+            Error::TwitterError(TwitterErrors {
+                errors: vec![TwitterErrorCode {
+                    message: String::from("error"),
+                    code,
+                }],
+            })
+        };
+
+        assert_eq!(
+            user_details_error("handle", &twitter_error(50)),
+            APIError::UserNotFound(String::from("handle"))
+        );
+        assert_eq!(
+            user_details_error("handle", &twitter_error(63)),
+            APIError::UserSuspended(String::from("handle"))
+        );
+        assert!(matches!(
+            user_details_error("handle", &twitter_error(88)),
+            APIError::UserDetailsError(_)
+        ));
+        assert!(matches!(
+            user_details_error("handle", &Error::RateLimit(1_570_000_000)),
+            APIError::UserDetailsError(_)
+        ));
+    }
+
+    #[test]
+    fn stops_paging_when_a_timeline_repeats_a_page() {
+        let page: Vec<_> = (1..=3)
+            .rev()
+            .map(|id| {
+                let mut tweet = sample_tweet(40);
+                tweet.id = id;
+                tweet
+            })
+            .collect();
+        let mut api = TestAPI {
+            user_timeline_pages: (0..100).map(|_| Ok(page.clone())).collect(),
+            ..Default::default()
+        };
+
+        // as `progress_timeline` does, with the oldest tweet retrieved tracked as the stored
+        // timeline of `RealAPI` tracks it
+        let seen_min_id = Cell::new(None);
+        let timeline = |c_api: &mut dyn TwitterAPI| {
+            let page = unseen_tweets(
+                "user timeline",
+                c_api.user_timeline_next_page()?,
+                seen_min_id.get(),
+            );
+            if let Some(oldest) = page.iter().map(|t| t.id).min() {
+                seen_min_id.set(Some(oldest));
+            }
+            Ok(page)
+        };
+        let summary = clear_timeline(
+            "user timeline",
+            &Settings::default(),
+            &mut api,
+            timeline,
+            None,
+            None,
+            &Progress::default(),
+        )
+        .unwrap();
+
+        assert_eq!(summary.deleted, 3);
+        assert_eq!(api.erased_tweet_ids, vec![3, 2, 1]);
+        let pages = api
+            .methods_called_in_order
+            .iter()
+            .filter(|m| *m == "user_timeline_next_page")
+            .count();
+        assert_eq!(pages, 2);
+    }
+
+    #[test]
+    fn ignores_tweets_of_overlapping_pages() {
+        let page: Vec<_> = [5, 4, 3, 2]
+            .iter()
+            .map(|id| {
+                let mut tweet = sample_tweet(1);
+                tweet.id = *id;
+                tweet
+            })
+            .collect();
+
+        let ids = |page: Vec<egg_mode::tweet::Tweet>| page.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(unseen_tweets("tl", page.clone(), None)),
+            vec![5, 4, 3, 2]
+        );
+        assert_eq!(ids(unseen_tweets("tl", page.clone(), Some(4))), vec![3, 2]);
+        assert!(unseen_tweets("tl", page, Some(2)).is_empty());
+    }
+
+    #[test]
+    fn connect_returns_user_id() {
+        let mut api = TestAPI {
+            get_user_id_answer: Ok(42),
+            ..Default::default()
+        };
+
+        assert_eq!(connect(&mut api, "handle"), Ok(42));
+        assert_eq!(
+            api.methods_called_in_order,
+            vec!["validate_token", "get_user_id"]
+        );
+    }
+
+    #[test]
+    fn connect_fails_if_user_is_not_the_token_owner() {
+        let mut api = TestAPI {
+            get_user_id_answer: Ok(42),
+            token_user_id_answer: Some(7),
+            ..Default::default()
+        };
+
+        let expected = APIError::AccountMismatch {
+            screen_name: String::from("handle"),
+            user_id: 42,
+            token_user_id: 7,
+        };
+        assert_eq!(connect(&mut api, "handle"), Err(expected));
+
+        api.token_user_id_answer = Some(42);
+        assert_eq!(connect(&mut api, "handle"), Ok(42));
+    }
+
+    #[test]
+    fn get_statuses_count_returns_configured_answer() {
+        let mut api = TestAPI {
+            get_statuses_count_answer: Ok(1234),
+            ..Default::default()
+        };
+
+        assert_eq!(api.get_statuses_count(), Ok(1234));
+        assert_eq!(api.methods_called_in_order, vec!["get_statuses_count"]);
+    }
+
+    #[test]
+    fn connect_stops_on_invalid_token() {
+        let mut api = TestAPI {
+            validate_token_answer: Err(APIError::InvalidToken),
+            ..Default::default()
+        };
+
+        assert_eq!(connect(&mut api, "handle"), Err(APIError::InvalidToken));
+        assert_eq!(api.methods_called_in_order, vec!["validate_token"]);
+    }
+
+    #[test]
+    fn maintain_tweets_attaches_tweet_id_to_errors() {
+        let err = APIError::ErasureError(String::from("error"));
+        let mut api = TestAPI {
+            erase_tweet_answer: Err(err.clone()),
+            ..Default::default()
+        };
+        let mut tweet = sample_tweet(1);
+        tweet.id = 42;
+
+        let results = api.maintain_tweets(&[&tweet], 1);
+
+        let expected = APIError::TweetError {
+            id: 42,
+            source: Box::new(err),
+        };
+        assert_eq!(results, vec![Err(expected.clone())]);
+        assert_eq!(expected.tweet_id(), Some(42));
+        assert_eq!(
+            expected.to_string(),
+            "Tweet #42: Failure removing link between tweet and user: error"
+        );
+    }
 }
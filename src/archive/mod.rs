@@ -0,0 +1,129 @@
+use chrono::prelude::*;
+use serde_json::Value;
+use std::io::Read;
+
+// format of the dates in the archive, like `Wed Oct 10 20:19:24 +0000 2018`
+const ARCHIVE_DATE_FORMAT: &str = "%a %b %d %H:%M:%S %z %Y";
+
+/// Tweet listed in the archive of an account
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivedTweet {
+    pub id: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Reads the tweets in a file of the archive Twitter gives to users who request their data, like
+/// `data/tweets.js`, or `data/tweet.js` in older archives. These files are a JavaScript assignment,
+/// `window.YTD.tweets.part0 = [...]`, of an array with an entry per tweet, either the tweet itself
+/// or an object with the tweet in a `tweet` field
+///
+/// # Error scenarios
+///
+/// The method will return an Err(_) if the file can't be read, isn't an array of tweets, or a tweet
+/// has no valid id or date
+pub fn read_archive<R: Read>(mut reader: R) -> Result<Vec<ArchivedTweet>, String> {
+    let mut contents = String::new();
+    reader
+        .read_to_string(&mut contents)
+        .map_err(|e| e.to_string())?;
+    // the array is assigned to a variable, so we take the JSON after the assignment
+    let contents = contents.trim_start_matches('\u{feff}').trim_start();
+    let json = match contents.find('=') {
+        Some(equals) if contents.starts_with("window.") => &contents[equals + 1..],
+        _ => contents,
+    };
+    let entries: Vec<Value> = serde_json::from_str(json.trim().trim_end_matches(';'))
+        .map_err(|e| format!("Not an array of tweets: {}", e))?;
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            archived_tweet(entry.get("tweet").unwrap_or(entry))
+                .map_err(|e| format!("Invalid tweet at position {}: {}", index + 1, e))
+        })
+        .collect()
+}
+
+fn archived_tweet(tweet: &Value) -> Result<ArchivedTweet, String> {
+    let id = match (&tweet["id_str"], &tweet["id"]) {
+        (Value::String(id), _) | (_, Value::String(id)) => id.parse().ok(),
+        (_, Value::Number(id)) => id.as_u64(),
+        _ => None,
+    }
+    .ok_or("missing id")?;
+    let created_at = tweet["created_at"].as_str().ok_or("missing date")?;
+    let created_at = DateTime::parse_from_str(created_at, ARCHIVE_DATE_FORMAT)
+        .map_err(|e| format!("invalid date {:?}: {}", created_at, e))?;
+    Ok(ArchivedTweet {
+        id,
+        created_at: created_at.with_timezone(&Utc),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_archive, ArchivedTweet};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn reads_tweets_from_archives() {
+        let tweets_js = r#"window.YTD.tweets.part0 = [
+  {
+    "tweet" : {
+      "retweeted" : false,
+      "id_str" : "1050118621198921728",
+      "id" : "1050118621198921728",
+      "created_at" : "Wed Oct 10 20:19:24 +0000 2018",
+      "full_text" : "To make room for more expression, we will now count all emojis as equal"
+    }
+  },
+  {
+    "tweet" : {
+      "id_str" : "1234",
+      "created_at" : "Mon Jan 02 08:00:00 +0100 2012"
+    }
+  }
+]"#;
+        // older archives list the tweets without the `tweet` field
+        let tweet_js = r#"window.YTD.tweet.part0 = [ {
+  "id" : "5678",
+  "created_at" : "Sat Mar 21 20:50:14 +0000 2009"
+} ]"#;
+
+        assert_eq!(
+            read_archive(tweets_js.as_bytes()),
+            Ok(vec![
+                ArchivedTweet {
+                    id: 1050118621198921728,
+                    created_at: Utc.ymd(2018, 10, 10).and_hms(20, 19, 24),
+                },
+                ArchivedTweet {
+                    id: 1234,
+                    created_at: Utc.ymd(2012, 1, 2).and_hms(7, 0, 0),
+                },
+            ])
+        );
+        assert_eq!(
+            read_archive(tweet_js.as_bytes()),
+            Ok(vec![ArchivedTweet {
+                id: 5678,
+                created_at: Utc.ymd(2009, 3, 21).and_hms(20, 50, 14),
+            }])
+        );
+        assert_eq!(read_archive("[]".as_bytes()), Ok(vec![]));
+    }
+
+    #[test]
+    fn fails_on_invalid_archives() {
+        assert!(read_archive("window.YTD.tweets.part0 = {}".as_bytes()).is_err());
+        let without_id = r#"window.YTD.tweets.part0 = [
+  { "tweet" : { "created_at" : "Wed Oct 10 20:19:24 +0000 2018" } }
+]"#;
+        assert_eq!(
+            read_archive(without_id.as_bytes()),
+            Err(String::from("Invalid tweet at position 1: missing id"))
+        );
+        let invalid_date = r#"[ { "tweet" : { "id_str" : "1", "created_at" : "yesterday" } } ]"#;
+        assert!(read_archive(invalid_date.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::config::Profile;
+
+/// Options given to the binary in the command line, on top of the configuration
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    /// If true, every account is processed as a dry run, as if `TP_DRY_RUN` was set
+    pub dry_run: bool,
+    /// If true, each timeline continues from the checkpoint of an interrupted run, as if `TP_RESUME`
+    /// was set
+    pub resume: bool,
+    /// Profiles to process, when several are defined
+    pub profile: Profile,
+}
+
+impl Args {
+    /// Parses the arguments given to the binary, without the name of the executable
+    ///
+    /// # Error scenarios
+    ///
+    /// The method will return an Err(_) if an argument is unknown, `--profile` has no name, or
+    /// both `--profile` and `--all-profiles` are given
+    pub fn parse<I>(args: I) -> Result<Args, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let profile = match arg.as_str() {
+                "--dry-run" => {
+                    parsed.dry_run = true;
+                    continue;
+                }
+                "--resume" => {
+                    parsed.resume = true;
+                    continue;
+                }
+                "--all-profiles" => Profile::All,
+                "--profile" => match args.next() {
+                    Some(name) if !name.starts_with("--") => Profile::Named(name),
+                    _ => return Err(String::from("--profile requires the name of a profile")),
+                },
+                other => match other.strip_prefix("--profile=") {
+                    Some(name) if !name.is_empty() => Profile::Named(String::from(name)),
+                    Some(_) => return Err(String::from("--profile requires the name of a profile")),
+                    None => {
+                        return Err(format!(
+                            "Unknown argument {:?}, expected --dry-run, --resume, --profile <name> or --all-profiles",
+                            other
+                        ))
+                    }
+                },
+            };
+            if parsed.profile != Profile::Configured {
+                return Err(String::from(
+                    "Only one of --profile or --all-profiles can be given",
+                ));
+            }
+            parsed.profile = profile;
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Args;
+    use crate::config::Profile;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| String::from(*arg)))
+    }
+
+    #[test]
+    fn parses_arguments() {
+        assert_eq!(parse(&[]), Ok(Args::default()));
+        assert_eq!(
+            parse(&["--dry-run", "--profile", "work"]),
+            Ok(Args {
+                dry_run: true,
+                profile: Profile::Named(String::from("work")),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse(&["--resume", "--all-profiles"]),
+            Ok(Args {
+                resume: true,
+                profile: Profile::All,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse(&["--profile=work"]).map(|args| args.profile),
+            Ok(Profile::Named(String::from("work")))
+        );
+        assert_eq!(
+            parse(&["--all-profiles"]).map(|args| args.profile),
+            Ok(Profile::All)
+        );
+    }
+
+    #[test]
+    fn fails_on_invalid_arguments() {
+        assert!(parse(&["--force"]).is_err());
+        assert!(parse(&["--profile"]).is_err());
+        assert!(parse(&["--profile", "--dry-run"]).is_err());
+        assert!(parse(&["--profile=work", "--all-profiles"]).is_err());
+    }
+}
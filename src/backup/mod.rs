@@ -0,0 +1,279 @@
+use crate::api::{APIError, AccountInfo, DirectMessage, EraseOutcome, TwitterAPI};
+use crate::pacing::RateLimitBudget;
+use chrono::prelude::*;
+use egg_mode::tweet::Tweet;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+/// Line of the backup, with the contents of a tweet as it was before we erased it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub id: u64,
+    pub created_at: DateTime<Utc>,
+    pub text: String,
+    /// Handle of the author of the tweet
+    pub screen_name: Option<String>,
+    pub in_reply_to_status_id: Option<u64>,
+    pub in_reply_to_screen_name: Option<String>,
+    pub quoted_status_id: Option<u64>,
+    /// Id of the original tweet, if this is a retweet
+    pub retweeted_status_id: Option<u64>,
+    pub favorite_count: i32,
+    pub retweet_count: i32,
+    pub lang: Option<String>,
+    pub hashtags: Vec<String>,
+    pub mentions: Vec<String>,
+    /// Links in the text, expanded when Twitter gives the full address
+    pub urls: Vec<String>,
+    /// Addresses of the images and videos attached to the tweet
+    pub media: Vec<String>,
+    /// When the entry was written, right before requesting the erasure
+    pub backed_up_at: DateTime<Utc>,
+}
+
+impl BackupEntry {
+    /// Copies the contents of the tweet that we can't retrieve once it is erased
+    pub fn of(tweet: &Tweet, backed_up_at: DateTime<Utc>) -> BackupEntry {
+        let entities = &tweet.entities;
+        let media = match (&tweet.extended_entities, &entities.media) {
+            (Some(extended), _) => extended.media.as_slice(),
+            (None, Some(media)) => media.as_slice(),
+            (None, None) => &[],
+        };
+        BackupEntry {
+            id: tweet.id,
+            created_at: tweet.created_at,
+            text: tweet.text.clone(),
+            screen_name: tweet.user.as_ref().map(|user| user.screen_name.clone()),
+            in_reply_to_status_id: tweet.in_reply_to_status_id,
+            in_reply_to_screen_name: tweet.in_reply_to_screen_name.clone(),
+            quoted_status_id: tweet.quoted_status_id,
+            retweeted_status_id: tweet.retweeted_status.as_ref().map(|rt| rt.id),
+            favorite_count: tweet.favorite_count,
+            retweet_count: tweet.retweet_count,
+            lang: tweet.lang.clone(),
+            hashtags: entities.hashtags.iter().map(|h| h.text.clone()).collect(),
+            mentions: entities
+                .user_mentions
+                .iter()
+                .map(|m| m.screen_name.clone())
+                .collect(),
+            urls: entities
+                .urls
+                .iter()
+                .map(|u| u.expanded_url.clone().unwrap_or_else(|| u.url.clone()))
+                .collect(),
+            media: media.iter().map(|m| m.media_url_https.clone()).collect(),
+            backed_up_at,
+        }
+    }
+}
+
+/// Wrapper over a `TwitterAPI` that appends every tweet to a backup, as a line of JSON, before
+/// requesting its erasure. If the backup can't be written the tweet is not erased, so we never lose
+/// a tweet we have no copy of.
+pub struct BackupAPI<'r> {
+    api: &'r mut dyn TwitterAPI,
+    path: String,
+    file: File,
+}
+
+impl<'r> BackupAPI<'r> {
+    /// Opens the backup at `path`, appending to it if it exists
+    pub fn open(api: &'r mut dyn TwitterAPI, path: &str) -> Result<BackupAPI<'r>, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Couldn't open backup {}: {}", path, e))?;
+        Ok(BackupAPI {
+            api,
+            path: String::from(path),
+            file,
+        })
+    }
+
+    fn record(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        let entry = BackupEntry::of(tweet, Utc::now());
+        serde_json::to_string(&entry)
+            .map_err(|e| e.to_string())
+            .and_then(|line| {
+                writeln!(self.file, "{}", line)
+                    .and_then(|_| self.file.flush())
+                    .map_err(|e| e.to_string())
+            })
+            .map_err(|e| {
+                APIError::ErasureError(format!("Couldn't write to backup {}: {}", self.path, e))
+                    .for_tweet(tweet.id)
+            })
+    }
+}
+
+impl<'r> TwitterAPI for BackupAPI<'r> {
+    fn validate_token(&mut self) -> Result<(), APIError> {
+        self.api.validate_token()
+    }
+
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError> {
+        self.api.get_user_id(screen_name)
+    }
+
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        self.api.get_account_info(screen_name)
+    }
+
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.api.get_statuses_count()
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        self.api.get_tweet(id)
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        self.api.rate_limit_status()
+    }
+
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        self.api.reply_count(tweet)
+    }
+
+    fn api_calls(&self) -> usize {
+        self.api.api_calls()
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.api.token_user_id()
+    }
+
+    fn tweet_count(&self) -> Option<u64> {
+        self.api.tweet_count()
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.api.pinned_tweet_id()
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        self.api.user_id()
+    }
+
+    fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.user_timeline_next_page()
+    }
+
+    fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.likes_timeline_next_page()
+    }
+
+    fn unlike_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        self.api.unlike_tweet(tweet)
+    }
+
+    fn unretweet_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        self.api.unretweet_tweet(tweet)
+    }
+
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
+        self.record(tweet)?;
+        self.api.erase_tweet(tweet)
+    }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        self.api.direct_messages_next_page()
+    }
+
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError> {
+        self.api.erase_dm(dm)
+    }
+
+    fn maintain_tweets(
+        &mut self,
+        tweets: &[&Tweet],
+        concurrency: usize,
+    ) -> Vec<Result<EraseOutcome, APIError>> {
+        let backups: Vec<Result<(), APIError>> =
+            tweets.iter().map(|tweet| self.record(tweet)).collect();
+        let backed_up: Vec<&Tweet> = tweets
+            .iter()
+            .zip(&backups)
+            .filter(|(_, backup)| backup.is_ok())
+            .map(|(tweet, _)| *tweet)
+            .collect();
+        let mut results = self
+            .api
+            .maintain_tweets(&backed_up, concurrency)
+            .into_iter();
+        backups
+            .into_iter()
+            .map(|backup| match backup {
+                Ok(()) => results
+                    .next()
+                    .unwrap_or_else(|| Err(APIError::ErasureError(String::from("Missing result")))),
+                Err(e) => Err(e),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BackupAPI, BackupEntry};
+    use crate::api::{APIError, TestAPI, TwitterAPI};
+    use crate::tests::sample_tweet;
+    use chrono::{TimeZone, Utc};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn appends_tweets_before_erasing_them() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_backup_{}.jsonl",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let mut first = sample_tweet(1);
+        first.id = 1;
+        first.created_at = Utc.ymd(2020, 5, 17).and_hms(10, 30, 0);
+        first.text = String::from("Good morning\nand good night");
+        first.favorite_count = 12;
+        let mut second = sample_tweet(1);
+        second.id = 2;
+        second.in_reply_to_status_id = Some(1);
+        // the tweet is in the backup even if erasing it fails, so retries are covered too
+        let mut api = TestAPI {
+            erase_tweet_answer: Err(APIError::ErasureError(String::from("error"))),
+            ..Default::default()
+        };
+
+        {
+            let mut backup = BackupAPI::open(&mut api, path).unwrap();
+            backup.erase_tweet(&first).unwrap_err();
+            backup.maintain_tweets(&[&second], 2);
+        }
+        let lines = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let entries: Vec<BackupEntry> = lines
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0],
+            BackupEntry {
+                backed_up_at: entries[0].backed_up_at,
+                ..BackupEntry::of(&first, Utc::now())
+            }
+        );
+        assert_eq!(entries[0].text, "Good morning\nand good night");
+        assert_eq!(entries[0].favorite_count, 12);
+        assert_eq!(entries[1].id, 2);
+        assert_eq!(entries[1].in_reply_to_status_id, Some(1));
+        assert_eq!(
+            api.methods_called_in_order,
+            vec!["erase_tweet", "erase_tweet"]
+        );
+    }
+}
@@ -3,6 +3,7 @@ extern crate twitter_privacy;
 
 #[macro_use]
 extern crate log;
+extern crate ctrlc;
 extern crate dotenv;
 extern crate pretty_env_logger;
 extern crate tokio;
@@ -17,12 +18,47 @@ fn main() {
         panic!("Missing logger. Aborting!")
     };
 
+    // on Ctrl-C or SIGTERM we let the current tweet finish and stop processing, so cursors are saved
+    // and we can still report what was done. A second signal stops the process right away
+    if let Err(e) = ctrlc::set_handler(|| {
+        let cancellation = twitter_privacy::Cancellation::global();
+        if cancellation.is_cancelled() {
+            warn!("Received a second stop signal, stopping now");
+            std::process::exit(130);
+        }
+        warn!("Received a stop signal, stopping after the current tweet");
+        cancellation.cancel()
+    }) {
+        warn!(
+            "Couldn't install the signal handler, the process will stop immediately on Ctrl-C: {}",
+            e
+        );
+    };
+
+    // with --dry-run we only log what would be erased, as with TP_DRY_RUN, and --profile or
+    // --all-profiles select the profiles to process
+    let args = match twitter_privacy::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            error!("{}", e);
+            panic!("Invalid arguments. Aborting!")
+        }
+    };
+    let result = twitter_privacy::clear_old_tweets_for(&args);
+
     // call method to clean old tweets. All the logic happens in the lib. We receive a Result and exit accordingly.
-    match twitter_privacy::clear_old_tweets() {
-        Ok(_) => info!("Tweets erased, stopping process. Thanks for using this application!"),
-        Err(e) =>{
+    match result {
+        Ok(ref summary) if summary.failed.is_empty() => {
+            info!("{}", summary);
+            info!("Tweets erased, stopping process. Thanks for using this application!")
+        }
+        Ok(summary) => {
+            error!("{}", summary);
+            panic!("Unrecoverable error while trying to erase Tweets for some accounts. Aborting!")
+        }
+        Err(e) => {
             error!("There's been an error:\n {}", e);
             panic!("Unrecoverable error while trying to erase Tweets. Aborting!")
-        },
+        }
     };
 }
@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Flag shared between the processing loop and whoever wants to stop it (like a signal handler).
+///
+/// The processing loop only checks the flag between tweets, so cancelling never leaves a tweet
+/// half processed.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    /// Returns the flag shared by the whole process, used when building `Settings` from the environment
+    pub fn global() -> Cancellation {
+        static GLOBAL: OnceLock<Cancellation> = OnceLock::new();
+        GLOBAL.get_or_init(Cancellation::default).clone()
+    }
+
+    /// Requests processing to stop at the next tweet boundary
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if processing should stop
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// two flags are equal if they are the same flag
+impl PartialEq for Cancellation {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cancellation;
+
+    #[test]
+    fn clones_share_the_flag() {
+        let cancellation = Cancellation::default();
+        let clone = cancellation.clone();
+        assert!(!clone.is_cancelled());
+
+        cancellation.cancel();
+
+        assert!(clone.is_cancelled());
+        assert_eq!(cancellation, clone);
+        assert_ne!(cancellation, Cancellation::default());
+    }
+}
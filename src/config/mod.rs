@@ -1,5 +1,21 @@
+use crate::cancellation::Cancellation;
+use crate::filters::{hashtag_names, lowercase_all, Filters, Patterns};
+use crate::predicate::Predicate;
+use chrono::prelude::*;
+use chrono::Duration;
+use chrono_tz::Tz;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::env::VarError;
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use toml::Value;
+
+// source of the value of each variable, by name
+type Lookup = Box<dyn Fn(&str) -> Result<String, VarError>>;
 
 /// List of values that we will need to interact with Twitter.
 /// Intended to be used to build our Configuration structs
@@ -13,7 +29,85 @@ pub struct EnvValues {
     pub access_key: String,
     pub access_secret: String,
     pub user_handle: String,
-    pub preserve_days: i64,
+    pub preserve_days: Duration,
+    pub minimum_age_days: i64,
+    pub confirm_delete: bool,
+    pub delete_all: bool,
+    pub delete_all_acknowledged: bool,
+    pub preserve_sources: Vec<String>,
+    pub delete_sources: Vec<String>,
+    pub preserve_hashtags: Vec<String>,
+    pub preserve_patterns: Patterns,
+    pub delete_patterns: Patterns,
+    pub concurrency: usize,
+    pub delete_withheld: bool,
+    pub preserve_quotes: bool,
+    pub delete_zero_engagement_only: bool,
+    pub delete_geotagged_only: bool,
+    pub delete_link_tweets_only: bool,
+    pub preserve_link_tweets: bool,
+    pub keep_media_tweets: bool,
+    pub delete_replies_only: bool,
+    pub delete_retweets_only: bool,
+    pub min_text_length: Option<usize>,
+    pub min_favs_to_keep: Option<u32>,
+    pub filter: Option<Predicate>,
+    /// Tweets with at least this many replies are kept. Replies are counted with Twitter search,
+    /// which only returns the last 7 days, so it can't keep tweets older than that
+    pub min_replies: Option<u64>,
+    pub preserve_own_engagement: bool,
+    pub delete_dms: bool,
+    pub preserve_dm_days: Duration,
+    pub delete_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    pub preserve_pinned: bool,
+    pub pinned_tweet_id: Option<u64>,
+    pub progress_every: usize,
+    pub log_every: usize,
+    pub preserve_active_threads: bool,
+    pub preserve_self_threads: bool,
+    pub clear_user_timeline: bool,
+    pub clear_likes_timeline: bool,
+    pub check_ownership: bool,
+    pub max_empty_pages: Option<usize>,
+    pub keep_latest: usize,
+    pub max_deletions: Option<usize>,
+    pub oldest_first: bool,
+    pub delete_delay_ms: u64,
+    pub delete_jitter_ms: u64,
+    pub retry_attempts: u32,
+    pub retry_base_delay_ms: u64,
+    pub retry_jitter_ms: u64,
+    pub adaptive_delay_ms: u64,
+    pub cooldown_every: Option<usize>,
+    pub cooldown_seconds: u64,
+    pub keep_self_likes: bool,
+    pub keep_likes: bool,
+    pub dry_run: bool,
+    pub action_log_path: Option<String>,
+    pub skip_logged: bool,
+    pub journal_path: Option<String>,
+    pub backup_path: Option<String>,
+    pub export_path: Option<String>,
+    pub media_path: Option<String>,
+    pub store_path: Option<String>,
+    pub retry_queue_path: Option<String>,
+    pub retry_queue_first: bool,
+    pub review_html: String,
+    pub cursor_path: Option<String>,
+    pub resume: bool,
+    pub timezone: Tz,
+    pub ignore_unlike_errors: bool,
+    pub ignore_unretweet_errors: bool,
+    pub continue_on_error: bool,
+    pub action_order: ActionOrder,
+    pub timeline_rules: HashMap<TimelineKind, TimelineRules>,
+    pub mode: Mode,
+    pub delete_ids_file: Option<String>,
+    pub archive_paths: Vec<String>,
+    pub keep_ids_file: Option<String>,
+    pub summary_path: Option<String>,
+    pub metrics_path: Option<String>,
+    pub last_run_file: Option<String>,
 }
 
 impl EnvValues {
@@ -24,12 +118,142 @@ impl EnvValues {
     const ACCESS_SECRET: &'static str = "TP_ACCESS_SECRET";
     const USER_HANDLE: &'static str = "TP_USER_HANDLE";
     const PRESERVE_DAYS: &'static str = "TP_PRESERVE_DAYS";
+    const CONFIRM_DELETE: &'static str = "TP_CONFIRM_DELETE";
+    const DELETE_ALL: &'static str = "TP_DELETE_ALL";
+    const DELETE_ALL_ACKNOWLEDGED: &'static str = "TP_DELETE_ALL_ACKNOWLEDGED";
+    const PRESERVE_SOURCES: &'static str = "TP_PRESERVE_SOURCES";
+    const PRESERVE_HASHTAGS: &'static str = "TP_PRESERVE_HASHTAGS";
+    const PRESERVE_PATTERNS: &'static str = "TP_PRESERVE_PATTERNS";
+    const DELETE_PATTERNS: &'static str = "TP_DELETE_PATTERNS";
+    const DELETE_SOURCES: &'static str = "TP_DELETE_SOURCES";
+    const CONCURRENCY: &'static str = "TP_CONCURRENCY";
+    const CONFIG_FILE: &'static str = "TP_CONFIG_FILE";
+    const PROFILES: &'static str = "TP_PROFILES";
+    const PROFILE: &'static str = "TP_PROFILE";
+    const DELETE_WITHHELD: &'static str = "TP_DELETE_WITHHELD";
+    const PRESERVE_QUOTES: &'static str = "TP_PRESERVE_QUOTES";
+    const SUMMARY_PATH: &'static str = "TP_SUMMARY_PATH";
+    const METRICS_PATH: &'static str = "TP_METRICS_PATH";
+    const LAST_RUN_FILE: &'static str = "TP_LAST_RUN_FILE";
+    const MINIMUM_AGE_DAYS: &'static str = "TP_MINIMUM_AGE_DAYS";
+    const DELETE_ZERO_ENGAGEMENT_ONLY: &'static str = "TP_DELETE_ZERO_ENGAGEMENT_ONLY";
+    const DELETE_GEOTAGGED_ONLY: &'static str = "TP_DELETE_GEOTAGGED_ONLY";
+    const DELETE_LINK_TWEETS_ONLY: &'static str = "TP_DELETE_LINK_TWEETS_ONLY";
+    const PRESERVE_LINK_TWEETS: &'static str = "TP_PRESERVE_LINK_TWEETS";
+    const KEEP_MEDIA_TWEETS: &'static str = "TP_KEEP_MEDIA_TWEETS";
+    const DELETE_REPLIES_ONLY: &'static str = "TP_DELETE_REPLIES_ONLY";
+    const DELETE_RETWEETS_ONLY: &'static str = "TP_DELETE_RETWEETS_ONLY";
+    const MIN_TEXT_LENGTH: &'static str = "TP_MIN_TEXT_LENGTH";
+    const MIN_FAVS_TO_KEEP: &'static str = "TP_MIN_FAVS_TO_KEEP";
+    const PRESERVE_OWN_ENGAGEMENT: &'static str = "TP_PRESERVE_OWN_ENGAGEMENT";
+    const DELETE_DMS: &'static str = "TP_DELETE_DMS";
+    const PRESERVE_DM_DAYS: &'static str = "TP_PRESERVE_DM_DAYS";
+    const DELETE_FROM: &'static str = "TP_DELETE_FROM";
+    const DELETE_TO: &'static str = "TP_DELETE_TO";
+    const PRESERVE_PINNED: &'static str = "TP_PRESERVE_PINNED";
+    const PINNED_TWEET_ID: &'static str = "TP_PINNED_TWEET_ID";
+    const PROGRESS_EVERY: &'static str = "TP_PROGRESS_EVERY";
+    const LOG_EVERY: &'static str = "TP_LOG_EVERY";
+    const PRESERVE_ACTIVE_THREADS: &'static str = "TP_PRESERVE_ACTIVE_THREADS";
+    const PRESERVE_SELF_THREADS: &'static str = "TP_PRESERVE_SELF_THREADS";
+    /// Shorthands for the `PRESERVE_DAYS` of a single timeline, see `load_timeline_rules`
+    const PRESERVE_DAYS_TWEETS: &'static str = "TP_PRESERVE_DAYS_TWEETS";
+    const PRESERVE_DAYS_LIKES: &'static str = "TP_PRESERVE_DAYS_LIKES";
+    const CLEAR_USER_TIMELINE: &'static str = "TP_CLEAR_USER_TIMELINE";
+    const CLEAR_LIKES_TIMELINE: &'static str = "TP_CLEAR_LIKES_TIMELINE";
+    const CHECK_OWNERSHIP: &'static str = "TP_CHECK_OWNERSHIP";
+    const MAX_EMPTY_PAGES: &'static str = "TP_MAX_EMPTY_PAGES";
+    const MAX_DELETIONS: &'static str = "TP_MAX_DELETIONS";
+    const OLDEST_FIRST: &'static str = "TP_OLDEST_FIRST";
+    const DELETE_DELAY_MS: &'static str = "TP_DELETE_DELAY_MS";
+    const DELETE_JITTER_MS: &'static str = "TP_DELETE_JITTER_MS";
+    const RETRY_ATTEMPTS: &'static str = "TP_RETRY_ATTEMPTS";
+    const RETRY_BASE_DELAY_MS: &'static str = "TP_RETRY_BASE_DELAY_MS";
+    const RETRY_JITTER_MS: &'static str = "TP_RETRY_JITTER_MS";
+    const ADAPTIVE_DELAY_MS: &'static str = "TP_ADAPTIVE_DELAY_MS";
+    const COOLDOWN_EVERY: &'static str = "TP_COOLDOWN_EVERY";
+    const COOLDOWN_SECONDS: &'static str = "TP_COOLDOWN_SECONDS";
+    const KEEP_SELF_LIKES: &'static str = "TP_KEEP_SELF_LIKES";
+    const KEEP_LIKES: &'static str = "TP_KEEP_LIKES";
+    const DRY_RUN: &'static str = "TP_DRY_RUN";
+    const ACTION_LOG: &'static str = "TP_ACTION_LOG";
+    const JOURNAL_PATH: &'static str = "TP_JOURNAL_PATH";
+    const BACKUP_PATH: &'static str = "TP_BACKUP_PATH";
+    const EXPORT_PATH: &'static str = "TP_EXPORT_PATH";
+    const MEDIA_PATH: &'static str = "TP_MEDIA_PATH";
+    const STORE_PATH: &'static str = "TP_STORE_PATH";
+    const RETRY_QUEUE: &'static str = "TP_RETRY_QUEUE";
+    const RETRY_QUEUE_FIRST: &'static str = "TP_RETRY_QUEUE_FIRST";
+    const SKIP_LOGGED: &'static str = "TP_SKIP_LOGGED";
+    const REVIEW_HTML: &'static str = "TP_REVIEW_HTML";
+    const DEFAULT_REVIEW_HTML: &'static str = "review.html";
+    const TIMEZONE: &'static str = "TP_TIMEZONE";
+    const CURSOR_PATH: &'static str = "TP_CURSOR_PATH";
+    const RESUME: &'static str = "TP_RESUME";
+    const IGNORE_UNLIKE_ERRORS: &'static str = "TP_IGNORE_UNLIKE_ERRORS";
+    const IGNORE_UNRETWEET_ERRORS: &'static str = "TP_IGNORE_UNRETWEET_ERRORS";
+    const CONTINUE_ON_ERROR: &'static str = "TP_CONTINUE_ON_ERROR";
+    const MODE: &'static str = "TP_MODE";
+    const ACTION_ORDER: &'static str = "TP_ACTION_ORDER";
+    const MIN_REPLIES: &'static str = "TP_MIN_REPLIES";
+    const FILTER: &'static str = "TP_FILTER";
+    const DELETE_IDS_FILE: &'static str = "TP_DELETE_IDS_FILE";
+    const ARCHIVE_PATH: &'static str = "TP_ARCHIVE_PATH";
+    const KEEP_IDS_FILE: &'static str = "TP_KEEP_IDS_FILE";
+    const KEEP_LATEST: &'static str = "TP_KEEP_LATEST";
+
+    /// Variables that can be set for a single timeline, see `load_timeline_rules`
+    const TIMELINE_RULES: [&'static str; 19] = [
+        EnvValues::PRESERVE_DAYS,
+        EnvValues::PRESERVE_SOURCES,
+        EnvValues::DELETE_SOURCES,
+        EnvValues::PRESERVE_HASHTAGS,
+        EnvValues::PRESERVE_PATTERNS,
+        EnvValues::DELETE_PATTERNS,
+        EnvValues::DELETE_WITHHELD,
+        EnvValues::PRESERVE_QUOTES,
+        EnvValues::DELETE_ZERO_ENGAGEMENT_ONLY,
+        EnvValues::DELETE_GEOTAGGED_ONLY,
+        EnvValues::DELETE_LINK_TWEETS_ONLY,
+        EnvValues::PRESERVE_LINK_TWEETS,
+        EnvValues::KEEP_MEDIA_TWEETS,
+        EnvValues::DELETE_REPLIES_ONLY,
+        EnvValues::DELETE_RETWEETS_ONLY,
+        EnvValues::MIN_TEXT_LENGTH,
+        EnvValues::MIN_FAVS_TO_KEEP,
+        EnvValues::PRESERVE_OWN_ENGAGEMENT,
+        EnvValues::FILTER,
+    ];
+
+    /// Meta variable that replaces the `TP` prefix of the rest of environment variables
+    const PREFIX: &'static str = "TWITTER_PRIVACY_PREFIX";
+    const DEFAULT_PREFIX: &'static str = "TP";
+
+    /// Value `TP_CONFIRM_DELETE` must have before we erase anything
+    pub const CONFIRM_DELETE_VALUE: &'static str = "yes-delete-my-tweets";
+    /// Value `TP_DELETE_ALL_ACKNOWLEDGED` must have before we erase tweets of any age
+    pub const DELETE_ALL_ACKNOWLEDGED_VALUE: &'static str = "yes-delete-all-my-tweets";
+
+    // default values for optional environment variables
+    const DEFAULT_PRESERVE_DAYS: i64 = 30;
+    const DEFAULT_MINIMUM_AGE_DAYS: i64 = 1;
+    const DEFAULT_CONCURRENCY: usize = 1;
+    const DEFAULT_PROGRESS_EVERY: usize = 500;
+    const DEFAULT_LOG_EVERY: usize = 100;
+    const DEFAULT_COOLDOWN_SECONDS: u64 = 300;
+    // days of replies Twitter search returns, so the most `TP_MIN_REPLIES` can count
+    const SEARCH_WINDOW_DAYS: i64 = 7;
+    /// Configuration file we read, if it exists, when `TP_CONFIG_FILE` is not set
+    pub const DEFAULT_CONFIG_FILE: &'static str = "twitter_privacy.toml";
 
     /// Loads a set of environmnt variables into a `EnvValues` struct
     ///
+    /// Variables use the `TP_` prefix, unless `TWITTER_PRIVACY_PREFIX` sets a different one.
+    /// Variables not set are read from the configuration file, see `load_accounts`.
+    ///
     /// # Side effects
     ///
-    /// Reads from environment variables
+    /// Reads from environment variables, and the configuration file if any
     ///
     /// # Error scenarios
     ///
@@ -37,42 +261,831 @@ impl EnvValues {
     ///
     /// - any of the needed environment variables is missing, or the wrong format
     pub fn load() -> Result<EnvValues, String> {
+        EnvValues::load_from(EnvValues::config_lookup()?)
+    }
+
+    /// Builds the token to authenticate against Twitter API from the consumer and access keys
+    pub fn token(&self) -> egg_mode::Token {
+        egg_mode::Token::Access {
+            consumer: egg_mode::KeyPair::new(
+                self.consumer_key.clone(),
+                self.consumer_secret.clone(),
+            ),
+            access: egg_mode::KeyPair::new(self.access_key.clone(), self.access_secret.clone()),
+        }
+    }
+
+    /// Builds a `EnvValues` struct reading each variable via the given `lookup` function.
+    /// `load` uses `std::env::var`, tests can provide their own set of values.
+    ///
+    /// # Error scenarios
+    ///
+    /// The method will return an Err(_) if:
+    ///
+    /// - any of the required variables is missing, or any variable has the wrong format
+    pub fn load_from<F>(lookup: F) -> Result<EnvValues, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
         info!("Loading environment variables and parsing to proper types");
 
         //We load configuration from environment. Fail early (using ?) if something is wrong
-        let consumer_key = EnvValues::get_env_var(EnvValues::CONSUMER_KEY)?;
-        let consumer_secret = EnvValues::get_env_var(EnvValues::CONSUMER_SECRET)?;
-        let access_key = EnvValues::get_env_var(EnvValues::ACCESS_KEY)?;
-        let access_secret = EnvValues::get_env_var(EnvValues::ACCESS_SECRET)?;
-        let user_handle = EnvValues::get_env_var(EnvValues::USER_HANDLE)?;
-
-        let preserve_days = EnvValues::get_env_var(EnvValues::PRESERVE_DAYS)?;
-        // on this code (parse()) the macro try! or the shortcut '?' break inference, so we need to unroll them
-        let preserve_days: i64 = match preserve_days.parse::<i64>() {
-            Ok(i) => i,
+        let consumer_key = EnvValues::get_env_var(&lookup, EnvValues::CONSUMER_KEY)?;
+        let consumer_secret = EnvValues::get_env_var(&lookup, EnvValues::CONSUMER_SECRET)?;
+        let access_key = EnvValues::get_env_var(&lookup, EnvValues::ACCESS_KEY)?;
+        let access_secret = EnvValues::get_env_var(&lookup, EnvValues::ACCESS_SECRET)?;
+        let user_handle = EnvValues::get_env_var(&lookup, EnvValues::USER_HANDLE)?;
+
+        // preserve days is optional, but if present it must be a valid number of days or duration
+        let preserve_days: Duration = match lookup(EnvValues::PRESERVE_DAYS) {
+            Err(VarError::NotPresent) => {
+                info!(
+                    "{} not set, defaulting to {} days",
+                    EnvValues::PRESERVE_DAYS,
+                    EnvValues::DEFAULT_PRESERVE_DAYS
+                );
+                Duration::days(EnvValues::DEFAULT_PRESERVE_DAYS)
+            }
             Err(e) => {
-                return Err(format!(
-                    "Error parsing {} to an i64: {}",
+                return Err(EnvValues::varerror_to_string(String::from(
                     EnvValues::PRESERVE_DAYS,
-                    e
+                ))(e));
+            }
+            // on this code (parse()) the macro try! or the shortcut '?' break inference, so we need to unroll them
+            Ok(preserve_days) => match preserve_days.trim().parse::<Retention>() {
+                Ok(Retention(duration)) => duration,
+                Err(e) => {
+                    return Err(format!(
+                        "Error parsing {} with value {:?}: {}",
+                        EnvValues::PRESERVE_DAYS,
+                        preserve_days,
+                        e
+                    ));
+                }
+            },
+        };
+
+        let confirm_delete = EnvValues::get_optional_env_var(&lookup, EnvValues::CONFIRM_DELETE)?
+            .as_deref()
+            == Some(EnvValues::CONFIRM_DELETE_VALUE);
+        let delete_all = EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_ALL, false)?;
+        let delete_all_acknowledged =
+            EnvValues::get_optional_env_var(&lookup, EnvValues::DELETE_ALL_ACKNOWLEDGED)?
+                .as_deref()
+                == Some(EnvValues::DELETE_ALL_ACKNOWLEDGED_VALUE);
+
+        let preserve_sources = EnvValues::get_list_env_var(&lookup, EnvValues::PRESERVE_SOURCES)?;
+        let preserve_hashtags = EnvValues::get_list_env_var(&lookup, EnvValues::PRESERVE_HASHTAGS)?;
+        let preserve_patterns = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::PRESERVE_PATTERNS,
+            Patterns::default(),
+        )?;
+        let delete_patterns = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::DELETE_PATTERNS,
+            Patterns::default(),
+        )?;
+        let delete_sources = EnvValues::get_list_env_var(&lookup, EnvValues::DELETE_SOURCES)?;
+        let concurrency = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::CONCURRENCY,
+            EnvValues::DEFAULT_CONCURRENCY,
+        )?;
+        if concurrency == 0 {
+            return Err(format!("{} must be at least 1", EnvValues::CONCURRENCY));
+        }
+        let delete_withheld =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_WITHHELD, false)?;
+        let preserve_quotes =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::PRESERVE_QUOTES, false)?;
+        let delete_zero_engagement_only =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_ZERO_ENGAGEMENT_ONLY, false)?;
+        let delete_geotagged_only =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_GEOTAGGED_ONLY, false)?;
+        let delete_link_tweets_only =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_LINK_TWEETS_ONLY, false)?;
+        let preserve_link_tweets =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::PRESERVE_LINK_TWEETS, false)?;
+        let keep_media_tweets =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::KEEP_MEDIA_TWEETS, false)?;
+        let delete_replies_only =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_REPLIES_ONLY, false)?;
+        let delete_retweets_only =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_RETWEETS_ONLY, false)?;
+        let min_text_length =
+            EnvValues::get_optional_parsed_env_var(&lookup, EnvValues::MIN_TEXT_LENGTH)?;
+        let min_favs_to_keep =
+            EnvValues::get_optional_parsed_env_var(&lookup, EnvValues::MIN_FAVS_TO_KEEP)?;
+        let preserve_own_engagement =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::PRESERVE_OWN_ENGAGEMENT, false)?;
+        let delete_dms = EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_DMS, false)?;
+        let Retention(preserve_dm_days) = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::PRESERVE_DM_DAYS,
+            Retention(preserve_days),
+        )?;
+        let timezone = EnvValues::get_parsed_env_var(&lookup, EnvValues::TIMEZONE, Tz::UTC)?;
+        let cursor_path = EnvValues::get_optional_env_var(&lookup, EnvValues::CURSOR_PATH)?;
+        let resume = EnvValues::get_parsed_env_var(&lookup, EnvValues::RESUME, false)?;
+        let ignore_unlike_errors =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::IGNORE_UNLIKE_ERRORS, false)?;
+        let ignore_unretweet_errors =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::IGNORE_UNRETWEET_ERRORS, false)?;
+        let continue_on_error =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::CONTINUE_ON_ERROR, false)?;
+        let action_order = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::ACTION_ORDER,
+            ActionOrder::default(),
+        )?;
+        let delete_from =
+            EnvValues::get_date_env_var(&lookup, EnvValues::DELETE_FROM, false, timezone)?;
+        let delete_to = EnvValues::get_date_env_var(&lookup, EnvValues::DELETE_TO, true, timezone)?;
+        let delete_range = match (delete_from, delete_to) {
+            (Some(from), Some(to)) if from > to => {
+                return Err(format!(
+                    "{} must not be after {}",
+                    EnvValues::DELETE_FROM,
+                    EnvValues::DELETE_TO
+                ));
+            }
+            (Some(from), Some(to)) => Some((from, to)),
+            (None, None) => None,
+            _ => {
+                return Err(format!(
+                    "{} and {} must be set together",
+                    EnvValues::DELETE_FROM,
+                    EnvValues::DELETE_TO
                 ));
             }
         };
+        let summary_path = EnvValues::get_optional_env_var(&lookup, EnvValues::SUMMARY_PATH)?;
+        let metrics_path = EnvValues::get_optional_env_var(&lookup, EnvValues::METRICS_PATH)?;
+        let last_run_file = EnvValues::get_optional_env_var(&lookup, EnvValues::LAST_RUN_FILE)?;
+        let preserve_pinned =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::PRESERVE_PINNED, true)?;
+        let pinned_tweet_id =
+            EnvValues::get_optional_parsed_env_var(&lookup, EnvValues::PINNED_TWEET_ID)?;
+        let max_empty_pages =
+            EnvValues::get_optional_parsed_env_var(&lookup, EnvValues::MAX_EMPTY_PAGES)?;
+        let max_deletions =
+            EnvValues::get_optional_parsed_env_var(&lookup, EnvValues::MAX_DELETIONS)?;
+        let oldest_first = EnvValues::get_parsed_env_var(&lookup, EnvValues::OLDEST_FIRST, false)?;
+        let delete_delay_ms =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_DELAY_MS, 0)?;
+        let delete_jitter_ms =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::DELETE_JITTER_MS, 0)?;
+        let retry_attempts = EnvValues::get_parsed_env_var(&lookup, EnvValues::RETRY_ATTEMPTS, 3)?;
+        if retry_attempts == 0 {
+            return Err(format!("{} must be at least 1", EnvValues::RETRY_ATTEMPTS));
+        }
+        let retry_base_delay_ms =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::RETRY_BASE_DELAY_MS, 1000)?;
+        let retry_jitter_ms =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::RETRY_JITTER_MS, 0)?;
+        let adaptive_delay_ms =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::ADAPTIVE_DELAY_MS, 0)?;
+        let cooldown_every =
+            EnvValues::get_optional_parsed_env_var(&lookup, EnvValues::COOLDOWN_EVERY)?;
+        let cooldown_seconds = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::COOLDOWN_SECONDS,
+            EnvValues::DEFAULT_COOLDOWN_SECONDS,
+        )?;
+        if cooldown_every == Some(0) {
+            return Err(format!("{} must be at least 1", EnvValues::COOLDOWN_EVERY));
+        }
+        let keep_self_likes =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::KEEP_SELF_LIKES, false)?;
+        let keep_likes = EnvValues::get_parsed_env_var(&lookup, EnvValues::KEEP_LIKES, false)?;
+        let dry_run = EnvValues::get_parsed_env_var(&lookup, EnvValues::DRY_RUN, false)?;
+        let action_log_path = EnvValues::get_optional_env_var(&lookup, EnvValues::ACTION_LOG)?;
+        let journal_path = EnvValues::get_optional_env_var(&lookup, EnvValues::JOURNAL_PATH)?;
+        let backup_path = EnvValues::get_optional_env_var(&lookup, EnvValues::BACKUP_PATH)?;
+        let export_path = EnvValues::get_optional_env_var(&lookup, EnvValues::EXPORT_PATH)?;
+        let media_path = EnvValues::get_optional_env_var(&lookup, EnvValues::MEDIA_PATH)?;
+        let store_path = EnvValues::get_optional_env_var(&lookup, EnvValues::STORE_PATH)?;
+        let retry_queue_path = EnvValues::get_optional_env_var(&lookup, EnvValues::RETRY_QUEUE)?;
+        let retry_queue_first =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::RETRY_QUEUE_FIRST, false)?;
+        let skip_logged = EnvValues::get_parsed_env_var(&lookup, EnvValues::SKIP_LOGGED, false)?;
+        let review_html = EnvValues::get_optional_env_var(&lookup, EnvValues::REVIEW_HTML)?
+            .unwrap_or_else(|| String::from(EnvValues::DEFAULT_REVIEW_HTML));
+        let progress_every = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::PROGRESS_EVERY,
+            EnvValues::DEFAULT_PROGRESS_EVERY,
+        )?;
+        let log_every = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::LOG_EVERY,
+            EnvValues::DEFAULT_LOG_EVERY,
+        )?;
+        let preserve_active_threads =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::PRESERVE_ACTIVE_THREADS, false)?;
+        let preserve_self_threads =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::PRESERVE_SELF_THREADS, false)?;
+        let clear_user_timeline =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::CLEAR_USER_TIMELINE, true)?;
+        let clear_likes_timeline =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::CLEAR_LIKES_TIMELINE, true)?;
+        let check_ownership =
+            EnvValues::get_parsed_env_var(&lookup, EnvValues::CHECK_OWNERSHIP, false)?;
+        let mode = EnvValues::get_parsed_env_var(&lookup, EnvValues::MODE, Mode::Erase)?;
+        let min_replies = EnvValues::get_optional_parsed_env_var(&lookup, EnvValues::MIN_REPLIES)?;
+        let filter = EnvValues::get_optional_parsed_env_var(&lookup, EnvValues::FILTER)?;
+        let delete_ids_file = EnvValues::get_optional_env_var(&lookup, EnvValues::DELETE_IDS_FILE)?;
+        let archive_paths = EnvValues::get_list_env_var(&lookup, EnvValues::ARCHIVE_PATH)?;
+        let keep_ids_file = EnvValues::get_optional_env_var(&lookup, EnvValues::KEEP_IDS_FILE)?;
+        let keep_latest = EnvValues::get_parsed_env_var(&lookup, EnvValues::KEEP_LATEST, 0)?;
+        let mut timeline_rules = HashMap::new();
+        for timeline in &[TimelineKind::User, TimelineKind::Likes] {
+            if let Some(rules) = EnvValues::load_timeline_rules(&lookup, *timeline, preserve_days)?
+            {
+                timeline_rules.insert(*timeline, rules);
+            }
+        }
+        let minimum_age_days = EnvValues::get_parsed_env_var(
+            &lookup,
+            EnvValues::MINIMUM_AGE_DAYS,
+            EnvValues::DEFAULT_MINIMUM_AGE_DAYS,
+        )?;
+        if minimum_age_days < 0 {
+            return Err(format!("{} can't be negative", EnvValues::MINIMUM_AGE_DAYS));
+        }
 
-        Ok(EnvValues {
+        let env_values = EnvValues {
             consumer_key,
             consumer_secret,
             access_key,
             access_secret,
             user_handle,
             preserve_days,
-        })
+            minimum_age_days,
+            confirm_delete,
+            delete_all,
+            delete_all_acknowledged,
+            preserve_sources,
+            preserve_hashtags,
+            preserve_patterns,
+            delete_patterns,
+            delete_sources,
+            concurrency,
+            delete_withheld,
+            preserve_quotes,
+            delete_zero_engagement_only,
+            delete_geotagged_only,
+            delete_link_tweets_only,
+            preserve_link_tweets,
+            keep_media_tweets,
+            delete_replies_only,
+            delete_retweets_only,
+            min_text_length,
+            min_favs_to_keep,
+            filter,
+            min_replies,
+            preserve_own_engagement,
+            delete_dms,
+            preserve_dm_days,
+            delete_range,
+            preserve_pinned,
+            pinned_tweet_id,
+            progress_every,
+            log_every,
+            preserve_active_threads,
+            preserve_self_threads,
+            clear_user_timeline,
+            clear_likes_timeline,
+            check_ownership,
+            max_empty_pages,
+            keep_latest,
+            max_deletions,
+            oldest_first,
+            delete_delay_ms,
+            delete_jitter_ms,
+            retry_attempts,
+            retry_base_delay_ms,
+            retry_jitter_ms,
+            adaptive_delay_ms,
+            cooldown_every,
+            cooldown_seconds,
+            keep_self_likes,
+            keep_likes,
+            dry_run,
+            action_log_path,
+            journal_path,
+            backup_path,
+            export_path,
+            media_path,
+            store_path,
+            retry_queue_path,
+            retry_queue_first,
+            skip_logged,
+            review_html,
+            cursor_path,
+            resume,
+            timezone,
+            ignore_unlike_errors,
+            ignore_unretweet_errors,
+            continue_on_error,
+            action_order,
+            timeline_rules,
+            mode,
+            delete_ids_file,
+            archive_paths,
+            keep_ids_file,
+            summary_path,
+            metrics_path,
+            last_run_file,
+        };
+        if let Some(warning) = env_values.min_replies_warning() {
+            warn!("{}", warning);
+        }
+        Ok(env_values)
+    }
+
+    /// Returns a warning if `TP_MIN_REPLIES` is set but will hardly keep any tweet, as we only erase
+    /// tweets older than the replies Twitter search returns, so only their latest replies count
+    pub fn min_replies_warning(&self) -> Option<String> {
+        let search_window = Duration::days(EnvValues::SEARCH_WINDOW_DAYS);
+        let erased_after = self.preserve_days.max(Duration::days(
+            self.minimum_age_days.min(EnvValues::SEARCH_WINDOW_DAYS),
+        ));
+        if self.min_replies.is_some() && erased_after >= search_window {
+            Some(format!(
+                "{} will hardly keep any tweet: Twitter search only returns replies from the last {} \
+                 days, and only tweets older than {} days are erased",
+                EnvValues::MIN_REPLIES,
+                EnvValues::SEARCH_WINDOW_DAYS,
+                erased_after.num_days()
+            ))
+        } else {
+            None
+        }
+    }
+
+    // loads the rules for the given timeline from the variables with the timeline in their name, like
+    // `TP_LIKES_TIMELINE_PRESERVE_DAYS`, or `TP_PRESERVE_DAYS_LIKES` for the preserve window. Rules
+    // not set for the timeline fall back to the general variables. Returns None if no variable is set
+    // for the timeline
+    fn load_timeline_rules<F>(
+        lookup: &F,
+        timeline: TimelineKind,
+        preserve_days: Duration,
+    ) -> Result<Option<TimelineRules>, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        let (prefix, preserve_days_shorthand) = match timeline {
+            TimelineKind::User => ("TP_USER_TIMELINE", EnvValues::PRESERVE_DAYS_TWEETS),
+            TimelineKind::Likes => ("TP_LIKES_TIMELINE", EnvValues::PRESERVE_DAYS_LIKES),
+        };
+        let timeline_name = |name: &str| name.replacen(EnvValues::DEFAULT_PREFIX, prefix, 1);
+        let timeline_var = |name: &str| match lookup(&timeline_name(name)) {
+            Err(VarError::NotPresent) if name == EnvValues::PRESERVE_DAYS => {
+                lookup(preserve_days_shorthand)
+            }
+            other => other,
+        };
+        let timeline_lookup = |name: &str| match timeline_var(name) {
+            Err(VarError::NotPresent) => lookup(name),
+            other => other,
+        };
+        if !EnvValues::TIMELINE_RULES
+            .iter()
+            .any(|name| timeline_var(name).is_ok())
+        {
+            return Ok(None);
+        }
+
+        info!("Loading rules for the {}", timeline);
+        let get_flag = |name| EnvValues::get_parsed_env_var(&timeline_lookup, name, false);
+        let filters = Filters {
+            preserve_sources: lowercase_all(&EnvValues::get_list_env_var(
+                &timeline_lookup,
+                EnvValues::PRESERVE_SOURCES,
+            )?),
+            delete_sources: lowercase_all(&EnvValues::get_list_env_var(
+                &timeline_lookup,
+                EnvValues::DELETE_SOURCES,
+            )?),
+            preserve_hashtags: hashtag_names(&EnvValues::get_list_env_var(
+                &timeline_lookup,
+                EnvValues::PRESERVE_HASHTAGS,
+            )?),
+            preserve_patterns: EnvValues::get_parsed_env_var(
+                &timeline_lookup,
+                EnvValues::PRESERVE_PATTERNS,
+                Patterns::default(),
+            )?,
+            delete_patterns: EnvValues::get_parsed_env_var(
+                &timeline_lookup,
+                EnvValues::DELETE_PATTERNS,
+                Patterns::default(),
+            )?,
+            delete_withheld: get_flag(EnvValues::DELETE_WITHHELD)?,
+            preserve_quotes: get_flag(EnvValues::PRESERVE_QUOTES)?,
+            delete_zero_engagement_only: get_flag(EnvValues::DELETE_ZERO_ENGAGEMENT_ONLY)?,
+            delete_geotagged_only: get_flag(EnvValues::DELETE_GEOTAGGED_ONLY)?,
+            delete_link_tweets_only: get_flag(EnvValues::DELETE_LINK_TWEETS_ONLY)?,
+            preserve_link_tweets: get_flag(EnvValues::PRESERVE_LINK_TWEETS)?,
+            keep_media_tweets: get_flag(EnvValues::KEEP_MEDIA_TWEETS)?,
+            delete_replies_only: get_flag(EnvValues::DELETE_REPLIES_ONLY)?,
+            delete_retweets_only: get_flag(EnvValues::DELETE_RETWEETS_ONLY)?,
+            min_text_length: EnvValues::get_optional_parsed_env_var(
+                &timeline_lookup,
+                EnvValues::MIN_TEXT_LENGTH,
+            )?,
+            min_favs_to_keep: EnvValues::get_optional_parsed_env_var(
+                &timeline_lookup,
+                EnvValues::MIN_FAVS_TO_KEEP,
+            )?,
+            preserve_own_engagement: get_flag(EnvValues::PRESERVE_OWN_ENGAGEMENT)?,
+            filter: EnvValues::get_optional_parsed_env_var(&timeline_lookup, EnvValues::FILTER)?,
+        };
+        let Retention(preserve_days) = EnvValues::get_parsed_env_var(
+            &timeline_lookup,
+            EnvValues::PRESERVE_DAYS,
+            Retention(preserve_days),
+        )?;
+        Ok(Some(TimelineRules {
+            preserve_days,
+            filters,
+        }))
+    }
+
+    /// Loads a set of values from the given TOML file into a `EnvValues` struct
+    ///
+    /// Keys in the file use the name of the environment variables, without the `TP_` prefix and in
+    /// lowercase (`consumer_key`, `preserve_days`...). Rules for a single timeline go in the
+    /// `[user_timeline]` and `[likes_timeline]` tables. Any environment variable that is set overrides
+    /// the corresponding value in the file.
+    ///
+    /// # Side effects
+    ///
+    /// Reads the given file and environment variables
+    ///
+    /// # Error scenarios
+    ///
+    /// The method will return an Err(_) if:
+    ///
+    /// - the file can't be read or is not valid TOML
+    /// - any of the required values is missing from both file and environment, or has the wrong format
+    pub fn from_file(path: &str) -> Result<EnvValues, String> {
+        let file_lookup = EnvValues::file_lookup(path)?;
+        EnvValues::load_from(EnvValues::override_with_env(file_lookup))
+    }
+
+    /// Loads the list of accounts to process from environment variables
+    ///
+    /// Multiple accounts are described with numbered variables, starting at 1 (`TP_USER_HANDLE_1`,
+    /// `TP_ACCESS_KEY_1`, `TP_USER_HANDLE_2`...). A numbered variable missing for an account falls back
+    /// to the unnumbered one, so values like `TP_CONSUMER_KEY` can be shared by all accounts.
+    /// If `TP_USER_HANDLE_1` is not set, we load a single account from the unnumbered variables.
+    ///
+    /// Values are also read from the TOML file in `TP_CONFIG_FILE`, or from `twitter_privacy.toml` in
+    /// the working directory if that variable is not set and the file exists. Environment variables
+    /// that are set override the values in the file.
+    ///
+    /// # Side effects
+    ///
+    /// Reads from environment variables, and the configuration file if any
+    ///
+    /// # Error scenarios
+    ///
+    /// The method will return an Err(_) if any of the accounts can't be loaded, as per `load`
+    pub fn load_accounts() -> Result<Vec<EnvValues>, String> {
+        EnvValues::load_profiles(&Profile::Configured)
+    }
+
+    /// Same as `load_accounts`, loading the given profiles if any are defined in `TP_PROFILES`
+    ///
+    /// Each profile is described with variables ending in its name in uppercase
+    /// (`TP_USER_HANDLE_WORK`, `TP_ACCESS_KEY_WORK`...). As with numbered accounts, a variable missing
+    /// for a profile falls back to the one without a suffix.
+    ///
+    /// # Side effects
+    ///
+    /// Reads from environment variables, and the configuration file if any
+    ///
+    /// # Error scenarios
+    ///
+    /// The method will return an Err(_) if the selected profile isn't defined, or if any of the
+    /// accounts can't be loaded, as per `load`
+    pub fn load_profiles(profile: &Profile) -> Result<Vec<EnvValues>, String> {
+        EnvValues::load_profiles_from(EnvValues::config_lookup()?, profile)
+    }
+
+    /// Same as `load_profiles`, reading each variable via the given `lookup` function
+    pub fn load_profiles_from<F>(lookup: F, profile: &Profile) -> Result<Vec<EnvValues>, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        let profiles = EnvValues::get_list_env_var(&lookup, EnvValues::PROFILES)?;
+        let configured = EnvValues::get_optional_env_var(&lookup, EnvValues::PROFILE)?;
+        let selected = match profile {
+            Profile::Named(name) => Some(name.clone()),
+            Profile::Configured => configured,
+            Profile::All => None,
+        };
+        if profiles.is_empty() {
+            return match selected {
+                Some(name) => Err(format!(
+                    "Profile {:?} not found, no profiles are defined in {}",
+                    name,
+                    EnvValues::PROFILES
+                )),
+                None => EnvValues::load_accounts_from(lookup),
+            };
+        }
+
+        let names = match selected {
+            Some(name) if profiles.contains(&name) => vec![name],
+            Some(name) => {
+                return Err(format!(
+                    "Profile {:?} not found, expected one of {}",
+                    name,
+                    profiles.join(", ")
+                ))
+            }
+            None => profiles,
+        };
+        let mut accounts = Vec::new();
+        for name in names {
+            info!("Loading values for profile {}", name);
+            let suffix = name.to_uppercase();
+            let profile_lookup = |var: &str| match lookup(&format!("{}_{}", var, suffix)) {
+                Err(VarError::NotPresent) => lookup(var),
+                other => other,
+            };
+            accounts.push(EnvValues::load_from(profile_lookup)?);
+        }
+        Ok(accounts)
+    }
+
+    // reads environment variables, falling back to the values of the configuration file if any
+    fn config_lookup() -> Result<Lookup, String> {
+        let env_lookup = EnvValues::env_lookup();
+        let default_exists = Path::new(EnvValues::DEFAULT_CONFIG_FILE).exists();
+        match EnvValues::config_file(&env_lookup, default_exists) {
+            Some(path) => {
+                info!("Loading configuration file {}", path);
+                let file_lookup = EnvValues::file_lookup(&path)?;
+                Ok(Box::new(EnvValues::override_with_env(file_lookup)))
+            }
+            None => Ok(Box::new(env_lookup)),
+        }
+    }
+
+    // path of the configuration file to read: the one in `TP_CONFIG_FILE`, or the default one if
+    // it exists
+    fn config_file<F>(lookup: &F, default_exists: bool) -> Option<String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        match lookup(EnvValues::CONFIG_FILE) {
+            Ok(path) => Some(path),
+            Err(_) if default_exists => Some(String::from(EnvValues::DEFAULT_CONFIG_FILE)),
+            Err(_) => None,
+        }
+    }
+
+    /// Same as `load_accounts`, reading each variable via the given `lookup` function
+    pub fn load_accounts_from<F>(lookup: F) -> Result<Vec<EnvValues>, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        let numbered = |name: &str, n: usize| format!("{}_{}", name, n);
+
+        let mut accounts = Vec::new();
+        let mut n = 1;
+        while lookup(&numbered(EnvValues::USER_HANDLE, n)).is_ok() {
+            info!("Loading values for account #{}", n);
+            let account_lookup = |name: &str| match lookup(&numbered(name, n)) {
+                Err(VarError::NotPresent) => lookup(name),
+                other => other,
+            };
+            accounts.push(EnvValues::load_from(account_lookup)?);
+            n += 1;
+        }
+
+        if accounts.is_empty() {
+            accounts.push(EnvValues::load_from(lookup)?);
+        }
+        Ok(accounts)
+    }
+
+    // reads the given TOML file and builds a lookup function over its values
+    fn file_lookup(path: &str) -> Result<impl Fn(&str) -> Result<String, VarError>, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading configuration file {}: {}", path, e))?;
+        EnvValues::toml_lookup(&contents)
+            .map_err(|e| format!("Error parsing configuration file {}: {}", path, e))
+    }
+
+    // builds a lookup function over the values in the given TOML document. Values are mapped to the
+    // name of the equivalent environment variable and converted to the format we expect in it
+    fn toml_lookup(contents: &str) -> Result<impl Fn(&str) -> Result<String, VarError>, String> {
+        fn as_env_value(key: &str, value: Value) -> Result<String, String> {
+            match value {
+                Value::String(s) => Ok(s),
+                Value::Array(values) => values
+                    .into_iter()
+                    .map(|v| as_env_value(key, v))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|vs| vs.join(",")),
+                Value::Table(_) => Err(format!("Unsupported nested table in key {}", key)),
+                other => Ok(other.to_string()),
+            }
+        }
+
+        // adds the values of `table` to `values`, with `suffix` at the end of their names
+        fn add_values(
+            values: &mut HashMap<String, String>,
+            table: toml::value::Table,
+            suffix: &str,
+        ) -> Result<(), String> {
+            for (key, value) in table {
+                match value {
+                    // the rules of a timeline map to the variables with the timeline in their name
+                    Value::Table(rules) if key == "user_timeline" || key == "likes_timeline" => {
+                        for (rule, value) in rules {
+                            let value = as_env_value(&rule, value)?;
+                            let name = format!("TP_{}_{}{}", key, rule, suffix).to_uppercase();
+                            values.insert(name, value);
+                        }
+                    }
+                    value => {
+                        let value = as_env_value(&key, value)?;
+                        values.insert(format!("TP_{}{}", key, suffix).to_uppercase(), value);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        let mut table: toml::value::Table = toml::from_str(contents).map_err(|e| e.to_string())?;
+        let mut values = HashMap::new();
+        // each profile maps to the variables with its name at the end
+        match table.remove("profiles") {
+            Some(Value::Table(profiles)) => {
+                let names: Vec<String> = profiles.keys().cloned().collect();
+                for (name, profile) in profiles {
+                    match profile {
+                        Value::Table(profile) => {
+                            add_values(&mut values, profile, &format!("_{}", name))?
+                        }
+                        _ => return Err(format!("Profile {} must be a table", name)),
+                    }
+                }
+                values.insert(String::from(EnvValues::PROFILES), names.join(","));
+            }
+            Some(_) => return Err(String::from("profiles must be a table of profiles")),
+            None => (),
+        }
+        add_values(&mut values, table, "")?;
+
+        Ok(move |name: &str| values.get(name).cloned().ok_or(VarError::NotPresent))
+    }
+
+    // wraps a lookup function so environment variables, if set, take precedence over it
+    fn override_with_env<F>(fallback: F) -> impl Fn(&str) -> Result<String, VarError>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        let env_lookup = EnvValues::env_lookup();
+        move |name| match env_lookup(name) {
+            Err(VarError::NotPresent) => fallback(name),
+            other => other,
+        }
+    }
+
+    // reads environment variables, using the prefix set in `TWITTER_PRIVACY_PREFIX`
+    fn env_lookup() -> impl Fn(&str) -> Result<String, VarError> {
+        EnvValues::with_prefix(|name: &str| env::var(name))
+    }
+
+    /// Wraps a lookup function so requests for `TP_` variables read the variables with the prefix
+    /// set in `TWITTER_PRIVACY_PREFIX` instead. `TP` is used if that variable is not set.
+    pub fn with_prefix<F>(lookup: F) -> impl Fn(&str) -> Result<String, VarError>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        let prefix =
+            lookup(EnvValues::PREFIX).unwrap_or_else(|_| String::from(EnvValues::DEFAULT_PREFIX));
+        let default_prefix = format!("{}_", EnvValues::DEFAULT_PREFIX);
+
+        move |name| match name.strip_prefix(&default_prefix) {
+            Some(unprefixed) => lookup(&format!("{}_{}", prefix, unprefixed)),
+            None => lookup(name),
+        }
     }
 
     // loads the environment variable with the given name
-    fn get_env_var(name: &str) -> Result<String, String> {
+    fn get_env_var<F>(lookup: &F, name: &str) -> Result<String, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
         let map_if_err = EnvValues::varerror_to_string(String::from(name));
-        env::var(name).map_err(map_if_err)
+        lookup(name).map_err(map_if_err)
+    }
+
+    // loads the environment variable with the given name, returning None if it is not set
+    fn get_optional_env_var<F>(lookup: &F, name: &str) -> Result<Option<String>, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        match lookup(name) {
+            Ok(v) => Ok(Some(v)),
+            Err(VarError::NotPresent) => Ok(None),
+            Err(e) => Err(EnvValues::varerror_to_string(String::from(name))(e)),
+        }
+    }
+
+    // loads a comma separated list from the environment variable with the given name. Empty if not set
+    fn get_list_env_var<F>(lookup: &F, name: &str) -> Result<Vec<String>, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        let value = EnvValues::get_optional_env_var(lookup, name)?.unwrap_or_default();
+        Ok(value
+            .split(',')
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    // loads and parses the environment variable with the given name, using `default` if it is not set
+    fn get_parsed_env_var<F, T>(lookup: &F, name: &str, default: T) -> Result<T, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        EnvValues::get_optional_parsed_env_var(lookup, name).map(|value| value.unwrap_or(default))
+    }
+
+    // like `get_parsed_env_var`, but returns `None` if the variable is not set
+    fn get_optional_parsed_env_var<F, T>(lookup: &F, name: &str) -> Result<Option<T>, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        EnvValues::get_optional_env_var(lookup, name)?
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<T>()
+                    .map_err(|e| format!("Error parsing {} with value {:?}: {}", name, value, e))
+            })
+            .transpose()
+    }
+
+    // loads a date from the environment variable with the given name, as RFC3339 or `YYYY-MM-DD`.
+    // Plain dates are taken at the start of the day, or at its end if `end_of_day` is true
+    fn get_date_env_var<F>(
+        lookup: &F,
+        name: &str,
+        end_of_day: bool,
+        timezone: Tz,
+    ) -> Result<Option<DateTime<Utc>>, String>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        let value = match EnvValues::get_optional_env_var(lookup, name)? {
+            None => return Ok(None),
+            Some(value) => value,
+        };
+        let value = value.trim();
+
+        if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+            return Ok(Some(date_time.with_timezone(&Utc)));
+        }
+        // plain dates are days in the time zone of the user
+        let local = match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            Ok(date) if end_of_day => timezone
+                .from_local_datetime(&date.and_hms_milli(23, 59, 59, 999))
+                .latest(),
+            Ok(date) => timezone
+                .from_local_datetime(&date.and_hms(0, 0, 0))
+                .earliest(),
+            Err(e) => {
+                return Err(format!(
+                    "Error parsing {} with value {:?}, expected RFC3339 or YYYY-MM-DD: {}",
+                    name, value, e
+                ))
+            }
+        };
+        match local {
+            Some(date_time) => Ok(Some(date_time.with_timezone(&Utc))),
+            None => Err(format!(
+                "Error parsing {} with value {:?}: the time doesn't exist in {}",
+                name, value, timezone
+            )),
+        }
     }
 
     // used to map VarError to Strings with the corresponding message
@@ -87,11 +1100,1468 @@ impl EnvValues {
     }
 }
 
+/// Builder of `EnvValues` for library users that don't configure the application through
+/// environment variables. Values are given with the names of the variables documented in the
+/// Readme, and are parsed and defaulted exactly as `EnvValues::load` does
+///
+/// ```
+/// # use twitter_privacy::ClearOptions;
+/// let env_values = ClearOptions::new("consumer", "secret", "access", "secret", "handle")
+///     .preserve_days(90)
+///     .dry_run(true)
+///     .set("TP_PRESERVE_QUOTES", "true")
+///     .build()
+///     .unwrap();
+/// assert_eq!(env_values.preserve_days, chrono::Duration::days(90));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClearOptions {
+    values: HashMap<String, String>,
+}
+
+impl ClearOptions {
+    /// Builds the options with the keys to access Twitter API and the handle of the user whose
+    /// tweets we erase
+    pub fn new(
+        consumer_key: &str,
+        consumer_secret: &str,
+        access_key: &str,
+        access_secret: &str,
+        user_handle: &str,
+    ) -> ClearOptions {
+        ClearOptions {
+            values: HashMap::new(),
+        }
+        .set(EnvValues::CONSUMER_KEY, consumer_key)
+        .set(EnvValues::CONSUMER_SECRET, consumer_secret)
+        .set(EnvValues::ACCESS_KEY, access_key)
+        .set(EnvValues::ACCESS_SECRET, access_secret)
+        .set(EnvValues::USER_HANDLE, user_handle)
+    }
+
+    /// Tweets newer than this amount of days are not erased
+    pub fn preserve_days(self, preserve_days: i64) -> ClearOptions {
+        self.set(EnvValues::PRESERVE_DAYS, &preserve_days.to_string())
+    }
+
+    /// Tweets posted less than this amount of days ago are never erased, whatever the other rules
+    pub fn minimum_age_days(self, minimum_age_days: i64) -> ClearOptions {
+        self.set(EnvValues::MINIMUM_AGE_DAYS, &minimum_age_days.to_string())
+    }
+
+    /// Confirms that tweets will be erased permanently, as `TP_CONFIRM_DELETE` does
+    pub fn confirm_delete(self, confirm_delete: bool) -> ClearOptions {
+        if confirm_delete {
+            self.set(EnvValues::CONFIRM_DELETE, EnvValues::CONFIRM_DELETE_VALUE)
+        } else {
+            self.unset(EnvValues::CONFIRM_DELETE)
+        }
+    }
+
+    /// If true, requests to unlike, unretweet or erase tweets are logged instead of sent
+    pub fn dry_run(self, dry_run: bool) -> ClearOptions {
+        self.set(EnvValues::DRY_RUN, &dry_run.to_string())
+    }
+
+    /// What the run does with the timelines of the user
+    pub fn mode(self, mode: &str) -> ClearOptions {
+        self.set(EnvValues::MODE, mode)
+    }
+
+    /// Sets any other value, by the name of its environment variable, like `TP_TIMEZONE`
+    pub fn set(mut self, name: &str, value: &str) -> ClearOptions {
+        self.values.insert(String::from(name), String::from(value));
+        self
+    }
+
+    /// Removes a value set before, so its default is used
+    pub fn unset(mut self, name: &str) -> ClearOptions {
+        self.values.remove(name);
+        self
+    }
+
+    /// Builds the `EnvValues` with the given values, and the defaults for the rest
+    ///
+    /// # Error scenarios
+    ///
+    /// The method will return an Err(_) if any value has the wrong format, as per `EnvValues::load`
+    pub fn build(self) -> Result<EnvValues, String> {
+        let values = self.values;
+        EnvValues::load_from(move |name: &str| {
+            values.get(name).cloned().ok_or(VarError::NotPresent)
+        })
+    }
+}
+
+/// Profiles to load when several are defined in `TP_PROFILES`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Profile {
+    /// The profile in `TP_PROFILE`, or all of them if that variable is not set
+    #[default]
+    Configured,
+    /// The profile with the given name
+    Named(String),
+    /// All the profiles, whatever the value of `TP_PROFILE`
+    All,
+}
+
+/// Timelines of a user we can erase tweets from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimelineKind {
+    /// Tweets posted or retweeted by the user
+    User,
+    /// Tweets liked by the user
+    Likes,
+}
+
+impl Display for TimelineKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimelineKind::User => write!(f, "User Timeline"),
+            TimelineKind::Likes => write!(f, "Likes Timeline"),
+        }
+    }
+}
+
+/// Period of time whose tweets we erase, as long as the rest of rules allow it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateWindow {
+    /// Tweets older than the given duration, a window that moves forward with each run
+    OlderThan(Duration),
+    /// Tweets posted between the two dates, both included
+    Between(DateTime<Utc>, DateTime<Utc>),
+}
+
+impl DateWindow {
+    /// Returns true if a tweet posted at `created_at` is in the window. Tweets not older than
+    /// `minimum_age_days` are never in it
+    pub fn contains(&self, created_at: DateTime<Utc>, minimum_age_days: i64) -> bool {
+        let age = Utc::now().signed_duration_since(created_at);
+        if age <= Duration::days(minimum_age_days) {
+            return false;
+        }
+        match *self {
+            // exclusively older than `preserve`
+            DateWindow::OlderThan(preserve) => age > preserve,
+            DateWindow::Between(from, to) => {
+                age > Duration::zero() && from <= created_at && created_at <= to
+            }
+        }
+    }
+}
+
+/// Converts the inclusive pair of dates of the deprecated `Settings::delete_range`
+impl From<(DateTime<Utc>, DateTime<Utc>)> for DateWindow {
+    fn from((from, to): (DateTime<Utc>, DateTime<Utc>)) -> DateWindow {
+        DateWindow::Between(from, to)
+    }
+}
+
+/// What a run does with the timelines of the user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Erases the selected tweets
+    Erase,
+    /// Only pages through the timelines, counting the tweets, to measure how fast we retrieve them
+    Scan,
+    /// Writes the tweets we would erase to an HTML page, without modifying them
+    Review,
+    /// Erases the tweets listed in `TP_DELETE_IDS_FILE`, or in the standard input, whatever their
+    /// age or the filters
+    EraseIds,
+    /// Compares the tweets we can reach in the user timeline with those posted by the user, to warn
+    /// about older tweets the timeline doesn't return
+    Reconcile,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "erase" => Ok(Mode::Erase),
+            "scan" => Ok(Mode::Scan),
+            "review" => Ok(Mode::Review),
+            "erase_ids" => Ok(Mode::EraseIds),
+            "reconcile" => Ok(Mode::Reconcile),
+            other => Err(format!(
+                "unknown mode {:?}, expected erase, scan, review, erase_ids or reconcile",
+                other
+            )),
+        }
+    }
+}
+
+/// How long tweets or messages are kept, parsed from a whole number of days, like `90`, or from a
+/// duration with units, like `36h`, `2w` or `6 months`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Retention(Duration);
+
+impl FromStr for Retention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(days) = s.parse::<i64>() {
+            // `Duration::days` panics with days out of the range of a `Duration`
+            return days
+                .checked_mul(24 * 60 * 60 * 1000)
+                .map(|millis| Retention(Duration::milliseconds(millis)))
+                .ok_or_else(|| format!("{} days is too long", days));
+        }
+        let duration = humantime::parse_duration(s).map_err(|e| {
+            format!(
+                "expected a number of days or a duration like 36h, 2w or 6 months: {}",
+                e
+            )
+        })?;
+        Duration::from_std(duration)
+            .map(Retention)
+            .map_err(|e| format!("duration too long: {}", e))
+    }
+}
+
+/// Request that removes a tweet, or our engagement with it, from the timelines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubAction {
+    Unlike,
+    Unretweet,
+    Erase,
+}
+
+/// Order in which we unlike, unretweet and erase each tweet. Unlikes and unretweets are only sent
+/// for tweets we liked or retweeted
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionOrder(Vec<SubAction>);
+
+impl ActionOrder {
+    /// Requests to send, in order
+    pub fn steps(&self) -> &[SubAction] {
+        &self.0
+    }
+}
+
+impl Default for ActionOrder {
+    fn default() -> Self {
+        ActionOrder(vec![
+            SubAction::Unlike,
+            SubAction::Unretweet,
+            SubAction::Erase,
+        ])
+    }
+}
+
+impl FromStr for ActionOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = Vec::new();
+        for name in s.split(',').map(|name| name.trim().to_lowercase()) {
+            let step = match name.as_str() {
+                "unlike" => SubAction::Unlike,
+                "unretweet" => SubAction::Unretweet,
+                "erase" => SubAction::Erase,
+                other => {
+                    return Err(format!(
+                        "unknown action {:?}, expected unlike, unretweet or erase",
+                        other
+                    ))
+                }
+            };
+            if steps.contains(&step) {
+                return Err(format!("action {:?} is listed more than once", name));
+            }
+            steps.push(step);
+        }
+        if steps.len() != ActionOrder::default().0.len() {
+            return Err(String::from(
+                "expected unlike, unretweet and erase, in any order",
+            ));
+        }
+        Ok(ActionOrder(steps))
+    }
+}
+
+/// Preserve window and filters for a single timeline, replacing those of the whole run
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineRules {
+    pub preserve_days: Duration,
+    pub filters: Filters,
+}
+
+/// Settings that control how we process the timelines of a user
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Tweets newer than this are never erased
+    pub preserve_days: Duration,
+    /// Tweets newer than this amount of days are never erased, even if `preserve_days` is lower
+    /// or a filter forces their erasure. Protects against configuration mistakes
+    pub minimum_age_days: i64,
+    /// If true, tweets are erased whatever their age, ignoring `preserve_days`, `delete_range`
+    /// and `minimum_age_days`
+    pub delete_all: bool,
+    /// Rules, besides age, that decide which tweets can be erased
+    pub filters: Filters,
+    /// Maximum number of tweets being erased at the same time
+    pub concurrency: usize,
+    /// Timelines to process, in order
+    pub timelines: Vec<TimelineKind>,
+    /// If true, the tweet pinned to the profile of the user is never erased
+    pub preserve_pinned: bool,
+    /// Number of tweets between logs of the progress of the run, 0 to disable them
+    pub progress_every: usize,
+    /// Number of tweets deleted between logs of the running total, 0 to disable them
+    pub log_every: usize,
+    /// If true, we log what we would erase instead of erasing it
+    pub dry_run: bool,
+    /// If true, a thread of replies is only erased once all its tweets can be erased. Requires
+    /// keeping the whole timeline in memory before erasing anything
+    pub preserve_active_threads: bool,
+    /// If true, a chain of the user's replies to their own tweets is only erased once none of its
+    /// tweets is too recent or preserved by other rules. Requires keeping the whole timeline in memory
+    pub preserve_self_threads: bool,
+    /// If true, tweets posted by other users are only unliked and unretweeted, we don't try to erase them
+    pub check_ownership: bool,
+    /// If set, we stop processing a timeline after this many consecutive pages without tweets old
+    /// enough to erase. Only applies to timelines that return the oldest tweets first
+    pub max_empty_pages: Option<usize>,
+    /// Number of latest tweets of the user timeline that are never erased, whatever their age
+    pub keep_latest: usize,
+    /// If set, we stop once this many tweets are deleted, counting those of all timelines. Tweets
+    /// processed by a custom action are not counted
+    pub max_deletions: Option<usize>,
+    /// If true, the tweets selected in all the timelines are erased together, from the oldest to the
+    /// newest, so `max_deletions` applies to the oldest tweets. Requires keeping the whole timelines
+    /// in memory before erasing anything
+    pub oldest_first: bool,
+    /// If true, tweets in the likes timeline posted by the user are neither unliked nor erased
+    pub keep_self_likes: bool,
+    /// If true, tweets we liked are only unretweeted, never unliked or erased, so the like is kept.
+    /// Tweets are then erased one at a time
+    pub keep_likes: bool,
+    /// If set, tweets with at least this many replies are never erased. Counting the replies takes
+    /// a request for each tweet selected for erasure, and only finds the replies of the last 7 days
+    pub min_replies: Option<u64>,
+    /// If set, every unlike, unretweet and erase that succeeds is appended to this file, as JSON lines
+    pub action_log_path: Option<String>,
+    /// If true, tweets recorded in the action log by previous runs are not processed again
+    pub skip_logged: bool,
+    /// If set, every tweet we delete is appended to this file as a Markdown entry
+    pub journal_path: Option<String>,
+    /// If set, every tweet is appended to this file as a line of JSON before we request its erasure
+    pub backup_path: Option<String>,
+    /// If set, this file is replaced with a CSV of the tweets processed in the run and what happened
+    /// to each of them
+    pub export_path: Option<String>,
+    /// If set, the media attached to a tweet is saved in a directory named after its id, inside this
+    /// one, before we request its erasure
+    pub media_path: Option<String>,
+    /// If set, every tweet of the timelines we page through, and every request we send about it, is
+    /// appended to this file as a line of JSON
+    pub store_path: Option<String>,
+    /// If set, the id of every tweet we fail to erase is appended to this file, with the error
+    pub retry_queue_path: Option<String>,
+    /// If true, the tweets in the retry queue are erased before processing the timelines
+    pub retry_queue_first: bool,
+    /// Ids of the tweets we never process, like those handled by previous runs
+    pub skipped_ids: HashSet<u64>,
+    /// If set, file with the ids of the tweets we must never unlike, unretweet or erase, one per line
+    pub keep_ids_file: Option<String>,
+    /// Ids of the tweets we must never unlike, unretweet or erase, loaded from `keep_ids_file`
+    pub kept_ids: HashSet<u64>,
+    /// If set, the time the last complete run started is kept in this file, see `last_run`
+    pub last_run_file: Option<String>,
+    /// When the last complete run started. Tweets that were already older than `preserve_days`
+    /// back then were handled by that run, so in the user timeline we only erase tweets newer than
+    /// `last_run - preserve_days`, and stop paging once we reach older tweets. Ignored with
+    /// `delete_range` or `delete_all`
+    pub last_run: Option<DateTime<Utc>>,
+    /// Time zone of the dates we log
+    pub timezone: Tz,
+    /// If true, a failed unlike is logged and we still try to erase the tweet. Only applies to
+    /// tweets erased one at a time
+    pub ignore_unlike_errors: bool,
+    /// Same as `ignore_unlike_errors`, for unretweets
+    pub ignore_unretweet_errors: bool,
+    /// If true, a tweet we fail to unlike, unretweet or erase is logged and counted as failed in
+    /// the summary, and we go on with the rest of tweets instead of stopping
+    pub continue_on_error: bool,
+    /// Order of the requests that remove each tweet. With an order other than the default, tweets
+    /// are erased one at a time
+    pub action_order: ActionOrder,
+    /// Rules that replace `preserve_days` and `filters` for some timelines
+    pub timeline_rules: HashMap<TimelineKind, TimelineRules>,
+    /// If true, we also erase direct messages older than `preserve_dm_days`
+    pub delete_dms: bool,
+    pub preserve_dm_days: Duration,
+    /// If set, only tweets within this window are erased, instead of those older than `preserve_days`
+    pub date_window: Option<DateWindow>,
+    /// If set, only tweets created within this inclusive range are erased, regardless of `preserve_days`
+    #[deprecated(note = "set `date_window` instead, converting the range with `DateWindow::from`")]
+    pub delete_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// Flag checked between tweets, to stop processing early
+    pub cancellation: Cancellation,
+}
+
+impl Settings {
+    /// Builds the settings from the values loaded from the environment
+    #[allow(deprecated)]
+    pub fn from_env(env_values: &EnvValues) -> Settings {
+        Settings {
+            preserve_days: env_values.preserve_days,
+            minimum_age_days: env_values.minimum_age_days,
+            delete_all: env_values.delete_all,
+            filters: Filters::from_env(env_values),
+            concurrency: env_values.concurrency,
+            timelines: Settings::enabled_timelines(env_values),
+            dry_run: env_values.dry_run,
+            delete_dms: env_values.delete_dms,
+            preserve_dm_days: env_values.preserve_dm_days,
+            date_window: env_values.delete_range.map(DateWindow::from),
+            delete_range: None,
+            preserve_pinned: env_values.preserve_pinned,
+            progress_every: env_values.progress_every,
+            log_every: env_values.log_every,
+            preserve_active_threads: env_values.preserve_active_threads,
+            preserve_self_threads: env_values.preserve_self_threads,
+            check_ownership: env_values.check_ownership,
+            max_empty_pages: env_values.max_empty_pages,
+            keep_latest: env_values.keep_latest,
+            max_deletions: env_values.max_deletions,
+            oldest_first: env_values.oldest_first,
+            keep_self_likes: env_values.keep_self_likes,
+            keep_likes: env_values.keep_likes,
+            min_replies: env_values.min_replies,
+            action_log_path: env_values.action_log_path.clone(),
+            journal_path: env_values.journal_path.clone(),
+            backup_path: env_values.backup_path.clone(),
+            export_path: env_values.export_path.clone(),
+            media_path: env_values.media_path.clone(),
+            store_path: env_values.store_path.clone(),
+            retry_queue_path: env_values.retry_queue_path.clone(),
+            retry_queue_first: env_values.retry_queue_first,
+            skip_logged: env_values.skip_logged,
+            skipped_ids: HashSet::new(),
+            keep_ids_file: env_values.keep_ids_file.clone(),
+            kept_ids: HashSet::new(),
+            last_run_file: env_values.last_run_file.clone(),
+            last_run: None,
+            timezone: env_values.timezone,
+            ignore_unlike_errors: env_values.ignore_unlike_errors,
+            ignore_unretweet_errors: env_values.ignore_unretweet_errors,
+            continue_on_error: env_values.continue_on_error,
+            action_order: env_values.action_order.clone(),
+            timeline_rules: env_values.timeline_rules.clone(),
+            cancellation: Cancellation::global(),
+        }
+    }
+
+    /// Returns the timelines the environment asks to process, in the order we process them
+    fn enabled_timelines(env_values: &EnvValues) -> Vec<TimelineKind> {
+        let enabled = [
+            (TimelineKind::User, env_values.clear_user_timeline),
+            (TimelineKind::Likes, env_values.clear_likes_timeline),
+        ];
+        enabled
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(timeline, _)| *timeline)
+            .collect()
+    }
+
+    /// Returns the window of the tweets we erase: `date_window` if set, then the deprecated
+    /// `delete_range`, or else the tweets older than `preserve_days`
+    #[allow(deprecated)]
+    pub fn erasure_window(&self) -> DateWindow {
+        self.date_window
+            .or_else(|| self.delete_range.map(DateWindow::from))
+            .unwrap_or(DateWindow::OlderThan(self.preserve_days))
+    }
+
+    /// Returns the settings to process the given timeline, with its own rules if it has any
+    pub fn for_timeline(&self, timeline: TimelineKind) -> Settings {
+        let mut settings = self.clone();
+        if let Some(rules) = self.timeline_rules.get(&timeline) {
+            settings.preserve_days = rules.preserve_days;
+            settings.filters = rules.filters.clone();
+        }
+        // the latest tweets we keep are those posted by the user
+        if timeline != TimelineKind::User {
+            settings.keep_latest = 0;
+        }
+        // self-likes are found in the likes timeline, tweets we posted are erased as usual
+        if timeline != TimelineKind::Likes {
+            settings.keep_self_likes = false;
+        }
+        // we may like an old tweet after the last run, so its age doesn't tell if we handled it
+        if timeline != TimelineKind::User {
+            settings.last_run = None;
+        }
+        settings
+    }
+}
+
+impl Default for Settings {
+    #[allow(deprecated)]
+    fn default() -> Self {
+        Settings {
+            preserve_days: Duration::days(EnvValues::DEFAULT_PRESERVE_DAYS),
+            minimum_age_days: EnvValues::DEFAULT_MINIMUM_AGE_DAYS,
+            delete_all: false,
+            filters: Filters::default(),
+            concurrency: EnvValues::DEFAULT_CONCURRENCY,
+            timelines: vec![TimelineKind::User, TimelineKind::Likes],
+            dry_run: false,
+            delete_dms: false,
+            preserve_dm_days: Duration::days(EnvValues::DEFAULT_PRESERVE_DAYS),
+            date_window: None,
+            delete_range: None,
+            preserve_pinned: true,
+            progress_every: EnvValues::DEFAULT_PROGRESS_EVERY,
+            log_every: EnvValues::DEFAULT_LOG_EVERY,
+            preserve_active_threads: false,
+            preserve_self_threads: false,
+            check_ownership: false,
+            max_empty_pages: None,
+            keep_latest: 0,
+            max_deletions: None,
+            oldest_first: false,
+            keep_self_likes: false,
+            keep_likes: false,
+            min_replies: None,
+            action_log_path: None,
+            journal_path: None,
+            backup_path: None,
+            export_path: None,
+            media_path: None,
+            store_path: None,
+            retry_queue_path: None,
+            retry_queue_first: false,
+            skip_logged: false,
+            skipped_ids: HashSet::new(),
+            keep_ids_file: None,
+            kept_ids: HashSet::new(),
+            last_run_file: None,
+            last_run: None,
+            timezone: Tz::UTC,
+            ignore_unlike_errors: false,
+            ignore_unretweet_errors: false,
+            continue_on_error: false,
+            action_order: ActionOrder::default(),
+            timeline_rules: HashMap::new(),
+            cancellation: Cancellation::default(),
+        }
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
+    use std::collections::HashMap;
     use std::ffi::OsString;
 
+    /// Builds a lookup function that reads from the given list of pairs instead of the environment
+    pub fn lookup_from(values: Vec<(&str, &str)>) -> impl Fn(&str) -> Result<String, VarError> {
+        let map: HashMap<String, String> = values
+            .into_iter()
+            .map(|(k, v)| (String::from(k), String::from(v)))
+            .collect();
+        move |name| map.get(name).cloned().ok_or(VarError::NotPresent)
+    }
+
+    /// List of the variables we always require, with dummy values
+    pub fn required_values() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("TP_CONSUMER_KEY", "consumer_key"),
+            ("TP_CONSUMER_SECRET", "consumer_secret"),
+            ("TP_ACCESS_KEY", "access_key"),
+            ("TP_ACCESS_SECRET", "access_secret"),
+            ("TP_USER_HANDLE", "handle"),
+        ]
+    }
+
+    #[test]
+    fn with_prefix_reads_variables_with_custom_prefix() {
+        let values = vec![
+            ("TWITTER_PRIVACY_PREFIX", "OTHER"),
+            ("OTHER_CONSUMER_KEY", "consumer_key"),
+            ("OTHER_CONSUMER_SECRET", "consumer_secret"),
+            ("OTHER_ACCESS_KEY", "access_key"),
+            ("OTHER_ACCESS_SECRET", "access_secret"),
+            ("OTHER_USER_HANDLE", "other_handle"),
+            ("OTHER_PRESERVE_DAYS", "5"),
+            ("TP_PRESERVE_DAYS", "50"),
+        ];
+        let env = EnvValues::load_from(EnvValues::with_prefix(lookup_from(values))).unwrap();
+
+        assert_eq!(env.user_handle, "other_handle");
+        assert_eq!(env.preserve_days, Duration::days(5));
+    }
+
+    #[test]
+    fn with_prefix_defaults_to_tp() {
+        let lookup = EnvValues::with_prefix(lookup_from(required_values()));
+        let env = EnvValues::load_from(lookup).unwrap();
+
+        assert_eq!(env.user_handle, "handle");
+    }
+
+    #[test]
+    fn token_uses_consumer_and_access_keys() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        match env.token() {
+            egg_mode::Token::Access { consumer, access } => {
+                assert_eq!(
+                    (consumer.key, consumer.secret),
+                    ("consumer_key".into(), "consumer_secret".into())
+                );
+                assert_eq!(
+                    (access.key, access.secret),
+                    ("access_key".into(), "access_secret".into())
+                );
+            }
+            other => panic!("Unexpected token {:?}", other),
+        }
+    }
+
+    #[test]
+    fn load_uses_default_preserve_days_if_missing() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+
+        assert_eq!(
+            env.preserve_days,
+            Duration::days(EnvValues::DEFAULT_PRESERVE_DAYS)
+        );
+        assert_eq!(env.user_handle, "handle");
+    }
+
+    #[test]
+    fn load_uses_given_preserve_days() {
+        let mut values = required_values();
+        values.push(("TP_PRESERVE_DAYS", "90"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+
+        assert_eq!(env.preserve_days, Duration::days(90));
+    }
+
+    #[test]
+    fn load_parses_preserve_days_with_units() {
+        let cases = vec![
+            ("36h", Duration::hours(36)),
+            ("2w", Duration::weeks(2)),
+            ("1d 12h", Duration::hours(36)),
+            ("6 months", Duration::seconds(6 * 2_630_016)),
+        ];
+        for (value, expected) in cases {
+            let mut values = required_values();
+            values.push(("TP_PRESERVE_DAYS", value));
+            values.push(("TP_PRESERVE_DAYS_LIKES", value));
+            let env = EnvValues::load_from(lookup_from(values)).unwrap();
+            let likes_rules = env.timeline_rules.get(&TimelineKind::Likes);
+
+            assert_eq!(env.preserve_days, expected, "{}", value);
+            assert_eq!(env.preserve_dm_days, expected, "{}", value);
+            assert_eq!(likes_rules.map(|r| r.preserve_days), Some(expected));
+        }
+    }
+
+    #[test]
+    fn load_fails_on_malformed_preserve_days() {
+        let mut values = required_values();
+        values.push(("TP_PRESERVE_DAYS", "ninety"));
+
+        assert!(EnvValues::load_from(lookup_from(values)).is_err());
+    }
+
+    #[test]
+    fn load_fails_on_too_long_preserve_days() {
+        for days in &[i64::MAX.to_string(), i64::MIN.to_string()] {
+            let mut values = required_values();
+            values.push(("TP_PRESERVE_DAYS", days));
+
+            assert!(EnvValues::load_from(lookup_from(values)).is_err());
+        }
+
+        let mut values = required_values();
+        values.push(("TP_PRESERVE_DAYS", "36500"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.preserve_days, Duration::days(36500));
+    }
+
+    #[test]
+    fn load_requires_exact_confirmation_value() {
+        let mut values = required_values();
+        values.push(("TP_CONFIRM_DELETE", "yes"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.confirm_delete, false);
+
+        let mut values = required_values();
+        values.push(("TP_CONFIRM_DELETE", EnvValues::CONFIRM_DELETE_VALUE));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.confirm_delete, true);
+    }
+
+    #[test]
+    fn load_parses_lists() {
+        let mut values = required_values();
+        values.push(("TP_PRESERVE_SOURCES", " Twitter Web App, ,Tweetdeck "));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+
+        assert_eq!(env.preserve_sources, vec!["Twitter Web App", "Tweetdeck"]);
+        assert!(env.delete_sources.is_empty());
+    }
+
+    #[test]
+    fn load_parses_retry_policy() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(
+            (
+                env.retry_attempts,
+                env.retry_base_delay_ms,
+                env.retry_jitter_ms
+            ),
+            (3, 1000, 0)
+        );
+
+        let mut values = required_values();
+        values.push(("TP_RETRY_ATTEMPTS", "5"));
+        values.push(("TP_RETRY_BASE_DELAY_MS", "250"));
+        values.push(("TP_RETRY_JITTER_MS", "100"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(
+            (
+                env.retry_attempts,
+                env.retry_base_delay_ms,
+                env.retry_jitter_ms
+            ),
+            (5, 250, 100)
+        );
+
+        let mut values = required_values();
+        values.push(("TP_RETRY_ATTEMPTS", "0"));
+        assert!(EnvValues::load_from(lookup_from(values)).is_err());
+    }
+
+    #[test]
+    fn load_validates_concurrency() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(env.concurrency, 1);
+
+        let mut values = required_values();
+        values.push(("TP_CONCURRENCY", "4"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.concurrency, 4);
+
+        let mut values = required_values();
+        values.push(("TP_CONCURRENCY", "0"));
+        assert!(EnvValues::load_from(lookup_from(values)).is_err());
+    }
+
+    #[test]
+    fn load_defaults_preserve_dm_days_to_preserve_days() {
+        let mut values = required_values();
+        values.push(("TP_PRESERVE_DAYS", "10"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert!(!env.delete_dms);
+        assert_eq!(env.preserve_dm_days, Duration::days(10));
+
+        let mut values = required_values();
+        values.push(("TP_DELETE_DMS", "true"));
+        values.push(("TP_PRESERVE_DM_DAYS", "3"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert!(env.delete_dms);
+        assert_eq!(env.preserve_dm_days, Duration::days(3));
+    }
+
+    #[test]
+    fn load_parses_delete_range() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(env.delete_range, None);
+
+        let mut values = required_values();
+        values.push(("TP_DELETE_FROM", "2019-01-01"));
+        values.push(("TP_DELETE_TO", "2019-12-31T10:00:00+02:00"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        let expected = (
+            Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+            Utc.ymd(2019, 12, 31).and_hms(8, 0, 0),
+        );
+        assert_eq!(env.delete_range, Some(expected));
+
+        let mut values = required_values();
+        values.push(("TP_DELETE_FROM", "2019-06-01"));
+        values.push(("TP_DELETE_TO", "2019-06-01"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        let (from, to) = env.delete_range.unwrap();
+        assert_eq!(
+            to - from,
+            chrono::Duration::milliseconds(24 * 60 * 60 * 1000 - 1)
+        );
+    }
+
+    #[test]
+    fn load_reads_plain_dates_in_the_given_timezone() {
+        let mut values = required_values();
+        values.push(("TP_TIMEZONE", "Europe/Madrid"));
+        values.push(("TP_DELETE_FROM", "2019-01-01"));
+        values.push(("TP_DELETE_TO", "2019-06-30"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+
+        let expected = (
+            Utc.ymd(2018, 12, 31).and_hms(23, 0, 0),
+            Utc.ymd(2019, 6, 30).and_hms_milli(21, 59, 59, 999),
+        );
+        assert_eq!(env.timezone, Tz::Europe__Madrid);
+        assert_eq!(env.delete_range, Some(expected));
+    }
+
+    #[test]
+    fn load_fails_on_unknown_timezone() {
+        let mut values = required_values();
+        values.push(("TP_TIMEZONE", "Europe/Atlantis"));
+        assert!(EnvValues::load_from(lookup_from(values)).is_err());
+    }
+
+    #[test]
+    fn load_validates_delete_range() {
+        let invalid = vec![
+            vec![("TP_DELETE_FROM", "2019-01-01")],
+            vec![("TP_DELETE_TO", "2019-01-01")],
+            vec![
+                ("TP_DELETE_FROM", "2020-01-01"),
+                ("TP_DELETE_TO", "2019-01-01"),
+            ],
+            vec![
+                ("TP_DELETE_FROM", "01/01/2019"),
+                ("TP_DELETE_TO", "2019-12-31"),
+            ],
+        ];
+
+        for extra in invalid {
+            let mut values = required_values();
+            values.extend(extra);
+            assert!(EnvValues::load_from(lookup_from(values)).is_err());
+        }
+    }
+
+    #[test]
+    fn load_parses_filter() {
+        let mut values = required_values();
+        values.push(("TP_FILTER", "has:media AND lang=en"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.filter, Some("has:media AND lang=en".parse().unwrap()));
+
+        let mut values = required_values();
+        values.push(("TP_FILTER", "has:media AND lang"));
+        assert_eq!(
+            EnvValues::load_from(lookup_from(values)).err(),
+            Some(String::from(
+                "Error parsing TP_FILTER with value \"has:media AND lang\": \
+                 unexpected end of the filter, expected a comparison like '<' or '='"
+            ))
+        );
+    }
+
+    #[test]
+    fn load_parses_action_order() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(env.action_order, ActionOrder::default());
+
+        let mut values = required_values();
+        values.push(("TP_ACTION_ORDER", "Erase, unlike,unretweet"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(
+            env.action_order.steps(),
+            &[SubAction::Erase, SubAction::Unlike, SubAction::Unretweet]
+        );
+
+        for invalid in &[
+            "erase,unlike,delete",
+            "erase,unlike",
+            "erase,unlike,erase",
+            "",
+        ] {
+            let mut values = required_values();
+            values.push(("TP_ACTION_ORDER", invalid));
+            assert!(
+                EnvValues::load_from(lookup_from(values)).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn load_parses_cooldown() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(env.cooldown_every, None);
+        assert_eq!(env.cooldown_seconds, EnvValues::DEFAULT_COOLDOWN_SECONDS);
+
+        let mut values = required_values();
+        values.push(("TP_COOLDOWN_EVERY", "200"));
+        values.push(("TP_COOLDOWN_SECONDS", "900"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.cooldown_every, Some(200));
+        assert_eq!(env.cooldown_seconds, 900);
+
+        let mut values = required_values();
+        values.push(("TP_COOLDOWN_EVERY", "0"));
+        assert!(EnvValues::load_from(lookup_from(values)).is_err());
+    }
+
+    #[test]
+    fn load_validates_minimum_age_days() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(env.minimum_age_days, EnvValues::DEFAULT_MINIMUM_AGE_DAYS);
+
+        let mut values = required_values();
+        values.push(("TP_MINIMUM_AGE_DAYS", "0"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.minimum_age_days, 0);
+
+        let mut values = required_values();
+        values.push(("TP_MINIMUM_AGE_DAYS", "-1"));
+        assert!(EnvValues::load_from(lookup_from(values)).is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn picks_the_erasure_window() {
+        let from = Utc.ymd(2014, 1, 1).and_hms(0, 0, 0);
+        let to = Utc.ymd(2016, 12, 31).and_hms_milli(23, 59, 59, 999);
+        let mut values = required_values();
+        values.push(("TP_DELETE_FROM", "2014-01-01"));
+        values.push(("TP_DELETE_TO", "2016-12-31"));
+        let settings = Settings::from_env(&EnvValues::load_from(lookup_from(values)).unwrap());
+        assert_eq!(settings.erasure_window(), DateWindow::Between(from, to));
+
+        let settings = Settings {
+            preserve_days: Duration::days(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.erasure_window(),
+            DateWindow::OlderThan(Duration::days(10))
+        );
+
+        // the deprecated range is still honoured, unless a window is set
+        let legacy = Settings {
+            delete_range: Some((from, to)),
+            ..settings
+        };
+        assert_eq!(legacy.erasure_window(), DateWindow::from((from, to)));
+        let window = DateWindow::OlderThan(Duration::days(3));
+        let settings = Settings {
+            date_window: Some(window),
+            ..legacy
+        };
+        assert_eq!(settings.erasure_window(), window);
+
+        let between = DateWindow::Between(from, to);
+        assert!(between.contains(Utc.ymd(2015, 6, 1).and_hms(12, 0, 0), 1));
+        assert!(!between.contains(Utc.ymd(2017, 1, 1).and_hms(0, 0, 0), 1));
+        assert!(window.contains(Utc::now() - Duration::days(4), 1));
+        assert!(!window.contains(Utc::now() - Duration::days(2), 1));
+    }
+
+    #[test]
+    fn window_between_dates_includes_both_ends() {
+        let from = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+        let to = Utc.ymd(2019, 12, 31).and_hms(23, 59, 59);
+        let window = DateWindow::Between(from, to);
+
+        assert!(window.contains(from, 1));
+        assert!(window.contains(Utc.ymd(2019, 6, 15).and_hms(12, 0, 0), 1));
+        assert!(window.contains(to, 1));
+        assert!(!window.contains(Utc.ymd(2018, 12, 31).and_hms(23, 59, 59), 1));
+        assert!(!window.contains(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0), 1));
+    }
+
+    #[test]
+    fn window_between_dates_respects_minimum_age() {
+        let now = Utc::now();
+        let window = DateWindow::Between(now - Duration::days(10), now);
+
+        assert!(!window.contains(now, 1));
+    }
+
+    quickcheck! {
+        fn window_older_than_works_on_dates_as_expected(days_past: u32) -> bool {
+            let now = Utc::now().timestamp();
+            // not more than 10 years ago for testing purposes
+            let bounded = i64::from(days_past % (365 * 10));
+            let seconds_past = bounded * 24 * 60 * 60;
+
+            let dt = NaiveDateTime::from_timestamp(now - seconds_past, 0);
+            let date = DateTime::from_utc(dt, Utc);
+            let older_than = |days| DateWindow::OlderThan(Duration::days(days)).contains(date, 0);
+
+            // check the full range of date differences
+            let mut boundary_after_date = true;
+            for i in 0..bounded {
+                boundary_after_date = boundary_after_date && older_than(i);
+            }
+
+            let boundary_on_date = older_than(bounded);
+
+            let mut boundary_before_date = false;
+            for i in (bounded+1)..(bounded + 365) {
+                boundary_before_date = boundary_before_date && older_than(i);
+            }
+            boundary_after_date && boundary_on_date && !boundary_before_date
+        }
+    }
+
+    #[test]
+    fn window_older_than_never_contains_tweets_newer_than_minimum_age() {
+        let now = Utc::now();
+        let window = DateWindow::OlderThan(Duration::zero());
+
+        assert!(!window.contains(now, 1));
+        assert!(!window.contains(now - Duration::hours(12), 1));
+        assert!(window.contains(now - Duration::hours(12), 0));
+        assert!(window.contains(now - Duration::days(2), 1));
+    }
+
+    #[test]
+    fn window_older_than_compares_ages_with_periods_shorter_than_a_day() {
+        let twelve_hours_ago = Utc::now() - Duration::hours(12);
+
+        assert!(DateWindow::OlderThan(Duration::hours(6)).contains(twelve_hours_ago, 0));
+        assert!(!DateWindow::OlderThan(Duration::hours(36)).contains(twelve_hours_ago, 0));
+    }
+
+    #[test]
+    fn warns_when_min_replies_cant_keep_any_tweet() {
+        for (preserve_days, warns) in &[(None, true), (Some("10"), true), (Some("3"), false)] {
+            let mut values = required_values();
+            values.push(("TP_MIN_REPLIES", "2"));
+            if let Some(preserve_days) = preserve_days {
+                values.push(("TP_PRESERVE_DAYS", preserve_days));
+            }
+            let env = EnvValues::load_from(lookup_from(values)).unwrap();
+
+            assert_eq!(env.min_replies_warning().is_some(), *warns);
+        }
+
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(env.min_replies_warning(), None);
+    }
+
+    #[test]
+    fn toml_lookup_parses_valid_files() {
+        let contents = r#"
+            consumer_key = "consumer_key"
+            consumer_secret = "consumer_secret"
+            access_key = "access_key"
+            access_secret = "access_secret"
+            user_handle = "handle"
+            preserve_days = 45
+            preserve_sources = ["Twitter Web App", "Tweetdeck"]
+        "#;
+        let lookup = EnvValues::toml_lookup(contents).unwrap();
+        let env = EnvValues::load_from(lookup).unwrap();
+
+        assert_eq!(env.consumer_key, "consumer_key");
+        assert_eq!(env.user_handle, "handle");
+        assert_eq!(env.preserve_days, Duration::days(45));
+        assert_eq!(env.preserve_sources, vec!["Twitter Web App", "Tweetdeck"]);
+    }
+
+    #[test]
+    fn toml_lookup_fails_on_missing_required_values() {
+        let contents = r#"
+            consumer_key = "consumer_key"
+            consumer_secret = "consumer_secret"
+            access_key = "access_key"
+            user_handle = "handle"
+        "#;
+        let lookup = EnvValues::toml_lookup(contents).unwrap();
+
+        assert_eq!(
+            EnvValues::load_from(lookup).unwrap_err(),
+            "Environment variable \"TP_ACCESS_SECRET\" not found"
+        );
+    }
+
+    #[test]
+    fn reads_default_config_file_if_it_exists() {
+        let lookup = lookup_from(required_values());
+        assert_eq!(EnvValues::config_file(&lookup, false), None);
+        assert_eq!(
+            EnvValues::config_file(&lookup, true),
+            Some(String::from("twitter_privacy.toml"))
+        );
+
+        let mut values = required_values();
+        values.push(("TP_CONFIG_FILE", "other.toml"));
+        let lookup = lookup_from(values);
+        assert_eq!(
+            EnvValues::config_file(&lookup, true),
+            Some(String::from("other.toml"))
+        );
+    }
+
+    #[test]
+    fn toml_lookup_fails_on_invalid_files() {
+        assert!(EnvValues::toml_lookup("consumer_key = ").is_err());
+        assert!(EnvValues::toml_lookup("[nested]\nkey = 1").is_err());
+    }
+
+    #[test]
+    fn toml_lookup_parses_timeline_rules() {
+        let contents = r#"
+            consumer_key = "consumer_key"
+            consumer_secret = "consumer_secret"
+            access_key = "access_key"
+            access_secret = "access_secret"
+            user_handle = "handle"
+            preserve_days = 45
+            preserve_quotes = true
+
+            [user_timeline]
+            preserve_days = 90
+            preserve_sources = ["Twitter Web App"]
+
+            [likes_timeline]
+            preserve_days = 7
+            preserve_quotes = false
+        "#;
+        let lookup = EnvValues::toml_lookup(contents).unwrap();
+        let env = EnvValues::load_from(lookup).unwrap();
+
+        let user_rules = TimelineRules {
+            preserve_days: Duration::days(90),
+            filters: Filters {
+                preserve_sources: vec![String::from("twitter web app")],
+                preserve_quotes: true,
+                ..Default::default()
+            },
+        };
+        let likes_rules = TimelineRules {
+            preserve_days: Duration::days(7),
+            filters: Filters::default(),
+        };
+        assert_eq!(env.preserve_days, Duration::days(45));
+        assert_eq!(
+            env.timeline_rules.get(&TimelineKind::User),
+            Some(&user_rules)
+        );
+        assert_eq!(
+            env.timeline_rules.get(&TimelineKind::Likes),
+            Some(&likes_rules)
+        );
+    }
+
+    #[test]
+    fn load_skips_timelines_without_rules() {
+        let mut values = required_values();
+        values.push(("TP_LIKES_TIMELINE_PRESERVE_DAYS", "7"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        let settings = Settings::from_env(&env);
+
+        assert_eq!(settings.for_timeline(TimelineKind::User), settings);
+        assert_eq!(
+            settings.for_timeline(TimelineKind::Likes).preserve_days,
+            Duration::days(7)
+        );
+    }
+
+    #[test]
+    fn load_reads_preserve_days_shorthands() {
+        let mut values = required_values();
+        values.push(("TP_PRESERVE_DAYS_TWEETS", "90"));
+        values.push(("TP_PRESERVE_DAYS_LIKES", "7"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        let settings = Settings::from_env(&env);
+
+        assert_eq!(
+            settings.for_timeline(TimelineKind::User).preserve_days,
+            Duration::days(90)
+        );
+        assert_eq!(
+            settings.for_timeline(TimelineKind::Likes).preserve_days,
+            Duration::days(7)
+        );
+
+        let mut values = required_values();
+        values.push(("TP_PRESERVE_DAYS_LIKES", "7"));
+        values.push(("TP_LIKES_TIMELINE_PRESERVE_DAYS", "14"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        let settings = Settings::from_env(&env);
+
+        assert_eq!(settings.for_timeline(TimelineKind::User), settings);
+        assert_eq!(
+            settings.for_timeline(TimelineKind::Likes).preserve_days,
+            Duration::days(14)
+        );
+    }
+
+    #[test]
+    fn keeps_self_likes_only_in_likes_timeline() {
+        let mut values = required_values();
+        values.push(("TP_KEEP_SELF_LIKES", "true"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        let settings = Settings::from_env(&env);
+
+        assert!(settings.for_timeline(TimelineKind::Likes).keep_self_likes);
+        assert!(!settings.for_timeline(TimelineKind::User).keep_self_likes);
+    }
+
+    #[test]
+    fn builds_values_without_environment() {
+        let env = ClearOptions::new("ck", "cs", "ak", "as", "handle")
+            .preserve_days(7)
+            .confirm_delete(true)
+            .set("TP_DELETE_SOURCES", "Buffer")
+            .build()
+            .unwrap();
+
+        assert_eq!(env.consumer_key, "ck");
+        assert_eq!(env.user_handle, "handle");
+        assert_eq!(env.preserve_days, Duration::days(7));
+        assert_eq!(env.minimum_age_days, EnvValues::DEFAULT_MINIMUM_AGE_DAYS);
+        assert!(env.confirm_delete);
+        assert!(!env.dry_run);
+        assert!(!env.resume);
+        assert_eq!(env.delete_sources, vec!["Buffer"]);
+
+        let env = ClearOptions::new("ck", "cs", "ak", "as", "handle")
+            .confirm_delete(true)
+            .confirm_delete(false)
+            .build()
+            .unwrap();
+        assert!(!env.confirm_delete);
+    }
+
+    #[test]
+    fn builder_fails_on_malformed_values() {
+        let result = ClearOptions::new("ck", "cs", "ak", "as", "handle")
+            .set("TP_DRY_RUN", "maybe")
+            .build();
+
+        assert!(result.unwrap_err().contains("TP_DRY_RUN"));
+    }
+
+    #[test]
+    fn load_reads_dry_run() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert!(!Settings::from_env(&env).dry_run);
+
+        let mut values = required_values();
+        values.push(("TP_DRY_RUN", "true"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert!(Settings::from_env(&env).dry_run);
+    }
+
+    #[test]
+    fn load_reads_enabled_timelines() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(
+            Settings::from_env(&env).timelines,
+            vec![TimelineKind::User, TimelineKind::Likes]
+        );
+
+        let mut values = required_values();
+        values.push(("TP_CLEAR_USER_TIMELINE", "false"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(
+            Settings::from_env(&env).timelines,
+            vec![TimelineKind::Likes]
+        );
+
+        let mut values = required_values();
+        values.push(("TP_CLEAR_LIKES_TIMELINE", "false"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(Settings::from_env(&env).timelines, vec![TimelineKind::User]);
+    }
+
+    #[test]
+    fn load_parses_mode() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert_eq!(env.mode, Mode::Erase);
+
+        let mut values = required_values();
+        values.push(("TP_MODE", "Scan"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.mode, Mode::Scan);
+
+        let mut values = required_values();
+        values.push(("TP_MODE", "erase_ids"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert_eq!(env.mode, Mode::EraseIds);
+
+        let mut values = required_values();
+        values.push(("TP_MODE", "delete"));
+        assert!(EnvValues::load_from(lookup_from(values)).is_err());
+    }
+
+    #[test]
+    fn from_file_fails_on_missing_file() {
+        assert!(EnvValues::from_file("/this/file/does/not/exist.toml").is_err());
+    }
+
+    #[test]
+    fn load_parses_flags() {
+        let env = EnvValues::load_from(lookup_from(required_values())).unwrap();
+        assert!(!env.delete_withheld);
+        assert!(!env.preserve_quotes);
+        assert!(!env.delete_zero_engagement_only);
+        assert!(!env.delete_geotagged_only);
+        assert!(!env.delete_link_tweets_only);
+        assert!(!env.preserve_link_tweets);
+        assert!(!env.keep_media_tweets);
+        assert!(!env.delete_replies_only);
+        assert!(!env.delete_retweets_only);
+        assert!(env.preserve_pinned);
+        assert_eq!(env.pinned_tweet_id, None);
+
+        let mut values = required_values();
+        values.push(("TP_DELETE_WITHHELD", "true"));
+        values.push(("TP_PRESERVE_QUOTES", "true"));
+        values.push(("TP_DELETE_ZERO_ENGAGEMENT_ONLY", "true"));
+        values.push(("TP_DELETE_GEOTAGGED_ONLY", "true"));
+        values.push(("TP_DELETE_LINK_TWEETS_ONLY", "true"));
+        values.push(("TP_PRESERVE_LINK_TWEETS", "true"));
+        values.push(("TP_KEEP_MEDIA_TWEETS", "true"));
+        values.push(("TP_DELETE_REPLIES_ONLY", "true"));
+        values.push(("TP_DELETE_RETWEETS_ONLY", "true"));
+        values.push(("TP_PRESERVE_PINNED", "false"));
+        values.push(("TP_PINNED_TWEET_ID", "1234"));
+        let env = EnvValues::load_from(lookup_from(values)).unwrap();
+        assert!(env.delete_withheld);
+        assert!(env.preserve_quotes);
+        assert!(env.delete_zero_engagement_only);
+        assert!(env.delete_geotagged_only);
+        assert!(env.delete_link_tweets_only);
+        assert!(env.preserve_link_tweets);
+        assert!(env.keep_media_tweets);
+        assert!(env.delete_replies_only);
+        assert!(env.delete_retweets_only);
+        assert!(!env.preserve_pinned);
+        assert_eq!(env.pinned_tweet_id, Some(1234));
+
+        let mut values = required_values();
+        values.push(("TP_DELETE_WITHHELD", "maybe"));
+        assert!(EnvValues::load_from(lookup_from(values)).is_err());
+    }
+
+    #[test]
+    fn load_accounts_defaults_to_single_account() {
+        let accounts = EnvValues::load_accounts_from(lookup_from(required_values())).unwrap();
+
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].user_handle, "handle");
+    }
+
+    #[test]
+    fn load_accounts_reads_numbered_values_with_fallback() {
+        let mut values = required_values();
+        values.push(("TP_USER_HANDLE_1", "first"));
+        values.push(("TP_ACCESS_KEY_1", "first_key"));
+        values.push(("TP_USER_HANDLE_2", "second"));
+        values.push(("TP_PRESERVE_DAYS_2", "7"));
+        let accounts = EnvValues::load_accounts_from(lookup_from(values)).unwrap();
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].user_handle, "first");
+        assert_eq!(accounts[0].access_key, "first_key");
+        assert_eq!(
+            accounts[0].preserve_days,
+            Duration::days(EnvValues::DEFAULT_PRESERVE_DAYS)
+        );
+        assert_eq!(accounts[1].user_handle, "second");
+        assert_eq!(accounts[1].access_key, "access_key");
+        assert_eq!(accounts[1].preserve_days, Duration::days(7));
+    }
+
+    #[test]
+    fn load_profiles_reads_values_of_each_profile() {
+        let mut values = required_values();
+        values.push(("TP_PROFILES", "work, personal"));
+        values.push(("TP_USER_HANDLE_WORK", "work_handle"));
+        values.push(("TP_ACCESS_KEY_WORK", "work_key"));
+        values.push(("TP_PRESERVE_DAYS_PERSONAL", "7"));
+        let lookup = lookup_from(values.clone());
+
+        let accounts = EnvValues::load_profiles_from(&lookup, &Profile::Configured).unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].user_handle, "work_handle");
+        assert_eq!(accounts[0].access_key, "work_key");
+        assert_eq!(accounts[1].user_handle, "handle");
+        assert_eq!(accounts[1].preserve_days, Duration::days(7));
+
+        let named = Profile::Named(String::from("personal"));
+        let accounts = EnvValues::load_profiles_from(&lookup, &named).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].preserve_days, Duration::days(7));
+
+        values.push(("TP_PROFILE", "work"));
+        let lookup = lookup_from(values);
+        let accounts = EnvValues::load_profiles_from(&lookup, &Profile::Configured).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].user_handle, "work_handle");
+        let accounts = EnvValues::load_profiles_from(&lookup, &Profile::All).unwrap();
+        assert_eq!(accounts.len(), 2);
+    }
+
+    #[test]
+    fn load_profiles_fails_on_unknown_profile() {
+        let named = Profile::Named(String::from("work"));
+        let result = EnvValues::load_profiles_from(lookup_from(required_values()), &named);
+        assert!(result.unwrap_err().contains("no profiles are defined"));
+
+        let mut values = required_values();
+        values.push(("TP_PROFILES", "personal"));
+        let result = EnvValues::load_profiles_from(lookup_from(values), &named);
+        assert!(result.unwrap_err().contains("expected one of personal"));
+    }
+
+    #[test]
+    fn toml_lookup_parses_profiles() {
+        let contents = r#"
+            consumer_key = "consumer_key"
+            consumer_secret = "consumer_secret"
+            preserve_days = 30
+
+            [profiles.work]
+            access_key = "work_key"
+            access_secret = "work_secret"
+            user_handle = "work_handle"
+
+            [profiles.work.likes_timeline]
+            preserve_days = 7
+
+            [profiles.personal]
+            access_key = "personal_key"
+            access_secret = "personal_secret"
+            user_handle = "personal_handle"
+            preserve_days = 90
+        "#;
+        let lookup = EnvValues::toml_lookup(contents).unwrap();
+        let accounts = EnvValues::load_profiles_from(lookup, &Profile::Configured).unwrap();
+
+        let handles: Vec<&str> = accounts.iter().map(|a| a.user_handle.as_str()).collect();
+        assert_eq!(handles, vec!["personal_handle", "work_handle"]);
+        assert_eq!(accounts[0].preserve_days, Duration::days(90));
+        assert_eq!(accounts[1].preserve_days, Duration::days(30));
+        assert_eq!(accounts[1].access_secret, "work_secret");
+        let likes_rules = accounts[1].timeline_rules.get(&TimelineKind::Likes);
+        assert_eq!(
+            likes_rules.map(|rules| rules.preserve_days),
+            Some(Duration::days(7))
+        );
+    }
+
+    #[test]
+    fn load_fails_on_missing_tokens() {
+        let values = vec![("TP_USER_HANDLE", "handle"), ("TP_PRESERVE_DAYS", "90")];
+
+        assert_eq!(
+            EnvValues::load_from(lookup_from(values)).unwrap_err(),
+            "Environment variable \"TP_CONSUMER_KEY\" not found"
+        );
+    }
+
     // These tests are quite useless, just added to play around with QuickCheck
     quickcheck! {
         fn for_not_present(n: String) -> bool {
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+
+/// Checkpoint of a run: the oldest tweet of the pages already processed from each timeline, keyed
+/// by the name of the timeline, so a run that was interrupted can resume each timeline from where it
+/// stopped. Stored as a JSON map
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cursors(BTreeMap<String, u64>);
+
+impl Cursors {
+    /// Returns the id of the oldest tweet of the pages processed from the given timeline, if any
+    pub fn get(&self, timeline: &str) -> Option<u64> {
+        self.0.get(timeline).copied()
+    }
+
+    /// Records the oldest tweet of the pages processed from the given timeline. `None` means we
+    /// reached its end, or processed no page yet, so the next run starts from the newest tweets
+    pub fn set(&mut self, timeline: &str, oldest_id: Option<u64>) {
+        match oldest_id {
+            Some(id) => self.0.insert(String::from(timeline), id),
+            None => self.0.remove(timeline),
+        };
+    }
+
+    /// Reads the cursors written by `write_to` from the given file. A missing file has no cursors
+    pub fn read_from(path: &str) -> Result<Cursors, String> {
+        match File::open(path) {
+            Ok(file) => serde_json::from_reader(file)
+                .map_err(|e| format!("Couldn't read cursors from {}: {}", path, e)),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(Cursors::default()),
+            Err(e) => Err(format!("Couldn't open {}: {}", path, e)),
+        }
+    }
+
+    /// Writes the cursors to the given file, replacing its contents
+    pub fn write_to(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("Couldn't write cursors to {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cursors;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn writes_and_reads_a_cursor_per_timeline() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_cursors_{}.json",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let mut cursors = Cursors::default();
+        cursors.set("user_timeline", Some(1234));
+        cursors.set("likes_timeline", Some(5678));
+
+        cursors.write_to(path).unwrap();
+        let json = fs::read_to_string(path).unwrap();
+        let read = Cursors::read_from(path);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({"user_timeline": 1234, "likes_timeline": 5678})
+        );
+        assert_eq!(read, Ok(cursors));
+    }
+
+    #[test]
+    fn forgets_timelines_that_reached_the_end() {
+        let mut cursors = Cursors::default();
+        cursors.set("user_timeline", Some(1234));
+        cursors.set("likes_timeline", Some(5678));
+        cursors.set("user_timeline", None);
+
+        assert_eq!(cursors.get("user_timeline"), None);
+        assert_eq!(cursors.get("likes_timeline"), Some(5678));
+    }
+
+    #[test]
+    fn missing_file_has_no_cursors() {
+        assert_eq!(
+            Cursors::read_from("/non/existent/cursors.json"),
+            Ok(Cursors::default())
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::api::{APIError, AccountInfo, DirectMessage, EraseOutcome, TwitterAPI};
+use crate::pacing::RateLimitBudget;
+use egg_mode::tweet::Tweet;
+
+/// Wrapper over a `TwitterAPI` that reads timelines from the wrapped API, but only logs the
+/// unlike, unretweet and erase requests instead of sending them. The requests succeed, so a
+/// `RunSummary` of a dry run counts what a real run would have done.
+pub struct DryRunAPI<'r> {
+    api: &'r mut dyn TwitterAPI,
+}
+
+impl<'r> DryRunAPI<'r> {
+    pub fn new(api: &'r mut dyn TwitterAPI) -> DryRunAPI<'r> {
+        DryRunAPI { api }
+    }
+}
+
+impl<'r> TwitterAPI for DryRunAPI<'r> {
+    fn validate_token(&mut self) -> Result<(), APIError> {
+        self.api.validate_token()
+    }
+
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError> {
+        self.api.get_user_id(screen_name)
+    }
+
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        self.api.get_account_info(screen_name)
+    }
+
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.api.get_statuses_count()
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        self.api.get_tweet(id)
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        self.api.rate_limit_status()
+    }
+
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        self.api.reply_count(tweet)
+    }
+
+    fn api_calls(&self) -> usize {
+        self.api.api_calls()
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.api.token_user_id()
+    }
+
+    fn tweet_count(&self) -> Option<u64> {
+        self.api.tweet_count()
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.api.pinned_tweet_id()
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        self.api.user_id()
+    }
+
+    fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.user_timeline_next_page()
+    }
+
+    fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.likes_timeline_next_page()
+    }
+
+    fn unlike_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        info!("[dry run] Would unlike tweet #{}", tweet.id);
+        Ok(())
+    }
+
+    fn unretweet_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        info!("[dry run] Would unretweet tweet #{}", tweet.id);
+        Ok(())
+    }
+
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
+        info!(
+            "[dry run] Would erase tweet #{} posted at {}: {}",
+            tweet.id, tweet.created_at, tweet.text
+        );
+        Ok(EraseOutcome::Deleted)
+    }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        self.api.direct_messages_next_page()
+    }
+
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError> {
+        info!("[dry run] Would erase direct message #{}", dm.id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DryRunAPI;
+    use crate::api::{EraseOutcome, TestAPI, TwitterAPI};
+    use crate::tests::sample_tweet;
+
+    #[test]
+    fn never_sends_destructive_requests() {
+        let mut api = TestAPI {
+            ..Default::default()
+        };
+        let mut dry_run = DryRunAPI::new(&mut api);
+        let tweet = sample_tweet(1);
+
+        dry_run.user_timeline_next_page().unwrap();
+        dry_run.unlike_tweet(&tweet).unwrap();
+        dry_run.unretweet_tweet(&tweet).unwrap();
+        assert_eq!(dry_run.erase_tweet(&tweet), Ok(EraseOutcome::Deleted));
+
+        assert_eq!(api.methods_called_in_order, vec!["user_timeline_next_page"]);
+    }
+}
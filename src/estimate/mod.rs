@@ -0,0 +1,207 @@
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Duration;
+
+/// Rough projection of the work needed to process a number of tweets, logged before a run starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub tweets: u64,
+    /// Requests to Twitter API, to retrieve the pages and to erase each tweet
+    pub requests: u64,
+    pub duration: Duration,
+}
+
+impl Estimate {
+    /// Average time a request to Twitter API takes, including the waits imposed by rate limits
+    pub const TIME_PER_REQUEST: Duration = Duration::from_millis(1000);
+
+    /// Estimates the work to process `tweets` tweets, retrieved in pages of `page_size` and erased
+    /// up to `concurrency` at the same time
+    pub fn new(tweets: u64, page_size: u64, concurrency: usize) -> Estimate {
+        let pages = div_ceil(tweets, page_size);
+        // requests erasing tweets at the same time only take the time of one of them
+        let erasure_rounds = div_ceil(tweets, concurrency as u64);
+        let rounds = u32::try_from(pages + erasure_rounds).unwrap_or(u32::MAX);
+
+        Estimate {
+            tweets,
+            requests: pages + tweets,
+            duration: Estimate::TIME_PER_REQUEST.saturating_mul(rounds),
+        }
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let minutes = div_ceil(self.duration.as_secs(), 60);
+        write!(
+            f,
+            "~{} tweets, estimated {} minutes",
+            with_thousands_separator(self.tweets),
+            with_thousands_separator(minutes)
+        )
+    }
+}
+
+/// Counts the tweets retrieved during a run and logs the progress every `every` tweets,
+/// compared to the `total` we expect to process, if known. Also counts the tweets deleted, logging
+/// a running total every `deleted_every` deletions
+#[derive(Debug, Default)]
+pub struct Progress {
+    total: Option<u64>,
+    every: usize,
+    processed: Cell<usize>,
+    deleted_every: usize,
+    deleted: Cell<usize>,
+}
+
+impl Progress {
+    pub fn new(total: Option<u64>, every: usize) -> Progress {
+        Progress {
+            total,
+            every,
+            processed: Cell::new(0),
+            deleted_every: 0,
+            deleted: Cell::new(0),
+        }
+    }
+
+    /// Returns a copy of this `Progress` that logs the tweets deleted so far every `deleted_every`
+    /// deletions, 0 to disable these logs
+    pub fn log_deleted_every(self, deleted_every: usize) -> Progress {
+        Progress {
+            deleted_every,
+            ..self
+        }
+    }
+
+    /// Number of tweets deleted so far
+    pub fn deleted(&self) -> usize {
+        self.deleted.get()
+    }
+
+    /// Counts a deleted tweet. Returns true if we logged the running total
+    pub fn record_deleted(&self) -> bool {
+        let deleted = self.deleted.get() + 1;
+        self.deleted.set(deleted);
+
+        let checkpoint = self.deleted_every > 0 && deleted.is_multiple_of(self.deleted_every);
+        if checkpoint {
+            info!(
+                "{} tweets deleted so far",
+                with_thousands_separator(deleted as u64)
+            );
+        }
+        checkpoint
+    }
+
+    /// Number of tweets retrieved so far
+    pub fn processed(&self) -> usize {
+        self.processed.get()
+    }
+
+    /// Adds `tweets` to the count, logging the progress if we passed a multiple of `every`
+    pub fn record(&self, tweets: usize) {
+        let before = self.processed.get();
+        let after = before + tweets;
+        self.processed.set(after);
+
+        if self.every > 0 && before / self.every != after / self.every {
+            info!("{}", self);
+        }
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let processed = self.processed.get() as u64;
+        write!(f, "Processed {}", with_thousands_separator(processed))?;
+        match self.total {
+            Some(total) if total > 0 => write!(
+                f,
+                " / ~{} tweets ({}%)",
+                with_thousands_separator(total),
+                (processed * 100 / total).min(100)
+            ),
+            _ => write!(f, " tweets"),
+        }
+    }
+}
+
+// division rounding up, treating a divisor of 0 as 1
+fn div_ceil(value: u64, divisor: u64) -> u64 {
+    value.div_ceil(divisor.max(1))
+}
+
+fn with_thousands_separator(value: u64) -> String {
+    let digits = value.to_string();
+    let mut result = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Estimate, Progress};
+    use std::time::Duration;
+
+    #[test]
+    fn estimates_pages_and_erasures() {
+        let estimate = Estimate::new(8400, 25, 1);
+
+        assert_eq!(estimate.requests, 336 + 8400);
+        assert_eq!(estimate.duration, Duration::from_secs(336 + 8400));
+        assert_eq!(estimate.to_string(), "~8,400 tweets, estimated 146 minutes");
+    }
+
+    #[test]
+    fn concurrency_reduces_the_duration() {
+        let estimate = Estimate::new(8400, 25, 4);
+
+        assert_eq!(estimate.requests, 336 + 8400);
+        assert_eq!(estimate.duration, Duration::from_secs(336 + 2100));
+        assert_eq!(estimate.to_string(), "~8,400 tweets, estimated 41 minutes");
+    }
+
+    #[test]
+    fn counts_progress_across_pages() {
+        let progress = Progress::new(Some(8400), 1000);
+
+        progress.record(25);
+        progress.record(975);
+        progress.record(2000);
+
+        assert_eq!(progress.processed(), 3000);
+        assert_eq!(
+            progress.to_string(),
+            "Processed 3,000 / ~8,400 tweets (35%)"
+        );
+        assert_eq!(Progress::new(None, 1).to_string(), "Processed 0 tweets");
+    }
+
+    #[test]
+    fn logs_deleted_tweets_every_given_deletions() {
+        let progress = Progress::new(None, 0).log_deleted_every(100);
+
+        let checkpoints = (0..250).filter(|_| progress.record_deleted()).count();
+
+        assert_eq!(checkpoints, 2);
+        assert_eq!(progress.deleted(), 250);
+        let disabled = Progress::default();
+        assert!(!(0..10).any(|_| disabled.record_deleted()));
+    }
+
+    #[test]
+    fn handles_empty_accounts() {
+        let estimate = Estimate::new(0, 25, 1);
+
+        assert_eq!(estimate.duration, Duration::from_secs(0));
+        assert_eq!(estimate.to_string(), "~0 tweets, estimated 0 minutes");
+    }
+}
@@ -0,0 +1,114 @@
+use crate::run::Outcome;
+use chrono::prelude::*;
+use egg_mode::tweet::Tweet;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+
+/// Row of the CSV export, with a tweet selected for erasure and what happened to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportRow {
+    pub id: u64,
+    pub created_at: DateTime<Utc>,
+    pub text: String,
+    pub favorite_count: i32,
+    pub retweet_count: i32,
+    /// One of `deleted`, `skipped` or `failed`
+    pub action: String,
+    /// Why the tweet was skipped, or the error if it failed. Empty for deleted tweets
+    pub reason: String,
+}
+
+impl ExportRow {
+    /// Builds the row of the given tweet, with what happened to it
+    pub fn of(tweet: &Tweet, outcome: &Outcome) -> ExportRow {
+        let (action, reason) = match outcome {
+            Outcome::Deleted => ("deleted", ""),
+            Outcome::Skipped(reason) => ("skipped", reason.as_str()),
+            Outcome::Failed(reason) => ("failed", reason.as_str()),
+        };
+        ExportRow {
+            id: tweet.id,
+            created_at: tweet.created_at,
+            text: tweet.text.clone(),
+            favorite_count: tweet.favorite_count,
+            retweet_count: tweet.retweet_count,
+            action: String::from(action),
+            reason: String::from(reason),
+        }
+    }
+}
+
+/// CSV file with a row for every tweet processed in a run, to open the results in a spreadsheet.
+/// Failing to write a row doesn't stop the run.
+pub struct CsvExport {
+    path: String,
+    writer: csv::Writer<File>,
+}
+
+impl CsvExport {
+    /// Creates the export at `path`, replacing the one of a previous run
+    pub fn create(path: &str) -> Result<CsvExport, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Couldn't create export {}: {}", path, e))?;
+        Ok(CsvExport {
+            path: String::from(path),
+            writer: csv::Writer::from_writer(file),
+        })
+    }
+
+    /// Writes the row of the given tweet. Rows are flushed as they are written, so the export is
+    /// complete even if the run stops
+    pub fn record(&mut self, tweet: &Tweet, outcome: &Outcome) {
+        let written = self
+            .writer
+            .serialize(ExportRow::of(tweet, outcome))
+            .and_then(|_| self.writer.flush().map_err(csv::Error::from));
+        if let Err(e) = written {
+            warn!("Couldn't write to export {}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CsvExport;
+    use crate::run::Outcome;
+    use crate::tests::sample_tweet;
+    use chrono::{TimeZone, Utc};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn writes_a_row_per_tweet() {
+        let path =
+            env::temp_dir().join(format!("twitter_privacy_export_{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut first = sample_tweet(1);
+        first.id = 1;
+        first.created_at = Utc.ymd(2020, 5, 17).and_hms(10, 30, 0);
+        first.text = String::from("Hello, \"world\"");
+        first.favorite_count = 12;
+        first.retweet_count = 3;
+        let mut second = sample_tweet(1);
+        second.id = 2;
+        second.created_at = Utc.ymd(2020, 5, 18).and_hms(23, 5, 0);
+        second.text = String::from("Bye");
+        second.favorite_count = 0;
+        second.retweet_count = 0;
+
+        {
+            let mut export = CsvExport::create(path).unwrap();
+            export.record(&first, &Outcome::Deleted);
+            export.record(&second, &Outcome::Failed(String::from("Rate limited")));
+        }
+        let csv = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let expected = "\
+id,created_at,text,favorite_count,retweet_count,action,reason
+1,2020-05-17T10:30:00Z,\"Hello, \"\"world\"\"\",12,3,deleted,
+2,2020-05-18T23:05:00Z,Bye,0,0,failed,Rate limited
+";
+        assert_eq!(csv, expected);
+    }
+}
@@ -0,0 +1,647 @@
+use crate::predicate::{has_media, Predicate};
+use crate::EnvValues;
+use egg_mode::tweet::Tweet;
+use regex::Regex;
+use std::str::FromStr;
+
+/// Set of rules, besides the age of a tweet, that decide if a tweet can be erased.
+/// By default all tweets are allowed, each rule restricts which ones we erase.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filters {
+    /// Tweets posted from a client matching any of these names are never erased
+    pub preserve_sources: Vec<String>,
+    /// If not empty, only tweets posted from a client matching any of these names are erased
+    pub delete_sources: Vec<String>,
+    /// Tweets with any of these hashtags, in lowercase and without the leading `#`, are never erased
+    pub preserve_hashtags: Vec<String>,
+    /// Tweets whose text matches any of these patterns are never erased
+    pub preserve_patterns: Patterns,
+    /// Tweets whose text matches any of these patterns are erased regardless of their age
+    pub delete_patterns: Patterns,
+    /// Tweets withheld in any country are erased regardless of their age
+    pub delete_withheld: bool,
+    /// Quote tweets are never erased, as they include our own commentary
+    pub preserve_quotes: bool,
+    /// If true, only tweets nobody liked or retweeted are erased
+    pub delete_zero_engagement_only: bool,
+    /// If true, only tweets with coordinates or a place attached are erased
+    pub delete_geotagged_only: bool,
+    /// If true, only tweets with links are erased
+    pub delete_link_tweets_only: bool,
+    /// Tweets with links are never erased
+    pub preserve_link_tweets: bool,
+    /// Tweets with pictures or videos are never erased
+    pub keep_media_tweets: bool,
+    /// If true, only replies are erased, standalone tweets are preserved
+    pub delete_replies_only: bool,
+    /// If true, only retweets are erased, tweets authored by the user are preserved
+    pub delete_retweets_only: bool,
+    /// If set, only tweets with less characters than this are erased
+    pub min_text_length: Option<usize>,
+    /// If set, tweets with at least this many likes are never erased
+    pub min_favs_to_keep: Option<u32>,
+    /// Tweets the user liked or retweeted are never erased
+    pub preserve_own_engagement: bool,
+    /// If set, only tweets matching this expression are erased
+    pub filter: Option<Predicate>,
+}
+
+impl Filters {
+    /// Builds the filters from the values loaded from the environment
+    pub fn from_env(env_values: &EnvValues) -> Filters {
+        Filters {
+            preserve_sources: lowercase_all(&env_values.preserve_sources),
+            delete_sources: lowercase_all(&env_values.delete_sources),
+            preserve_hashtags: hashtag_names(&env_values.preserve_hashtags),
+            preserve_patterns: env_values.preserve_patterns.clone(),
+            delete_patterns: env_values.delete_patterns.clone(),
+            delete_withheld: env_values.delete_withheld,
+            preserve_quotes: env_values.preserve_quotes,
+            delete_zero_engagement_only: env_values.delete_zero_engagement_only,
+            delete_geotagged_only: env_values.delete_geotagged_only,
+            delete_link_tweets_only: env_values.delete_link_tweets_only,
+            preserve_link_tweets: env_values.preserve_link_tweets,
+            keep_media_tweets: env_values.keep_media_tweets,
+            delete_replies_only: env_values.delete_replies_only,
+            delete_retweets_only: env_values.delete_retweets_only,
+            min_text_length: env_values.min_text_length,
+            min_favs_to_keep: env_values.min_favs_to_keep,
+            preserve_own_engagement: env_values.preserve_own_engagement,
+            filter: env_values.filter.clone(),
+        }
+    }
+
+    /// Returns true if the given tweet must be erased regardless of its age.
+    /// The tweet must still be allowed by the rest of filters, see `allows`.
+    pub fn forces_erasure(&self, tweet: &Tweet) -> bool {
+        if let Some(pattern) = self.delete_patterns.find_match(&tweet.text) {
+            debug!(
+                "Tweet #{} matches {:?}, erased regardless of its age",
+                tweet.id,
+                pattern.as_str()
+            );
+            return true;
+        }
+        self.delete_withheld && is_withheld(tweet)
+    }
+
+    /// Returns true if the filters allow erasing the given tweet
+    pub fn allows(&self, tweet: &Tweet) -> bool {
+        let source = tweet.source.name.to_lowercase();
+        let source_matches = |names: &Vec<String>| names.iter().any(|n| source.contains(n));
+
+        if !self.delete_sources.is_empty() && !source_matches(&self.delete_sources) {
+            debug!(
+                "Tweet #{} from source {:?} not in the list of sources to delete",
+                tweet.id, tweet.source.name
+            );
+            return false;
+        }
+        if source_matches(&self.preserve_sources) {
+            debug!(
+                "Tweet #{} from source {:?} is preserved",
+                tweet.id, tweet.source.name
+            );
+            return false;
+        }
+        if let Some(hashtag) = tweet
+            .entities
+            .hashtags
+            .iter()
+            .find(|h| self.preserve_hashtags.contains(&h.text.to_lowercase()))
+        {
+            debug!(
+                "Tweet #{} has hashtag #{}, preserved",
+                tweet.id, hashtag.text
+            );
+            return false;
+        }
+        if let Some(pattern) = self.preserve_patterns.find_match(&tweet.text) {
+            debug!(
+                "Tweet #{} matches {:?}, preserved",
+                tweet.id,
+                pattern.as_str()
+            );
+            return false;
+        }
+        if self.preserve_quotes && tweet.quoted_status_id.is_some() {
+            debug!("Tweet #{} is a quote tweet, preserved", tweet.id);
+            return false;
+        }
+        if self.delete_zero_engagement_only && (tweet.favorite_count > 0 || tweet.retweet_count > 0)
+        {
+            debug!(
+                "Tweet #{} has {} likes and {} retweets, preserved",
+                tweet.id, tweet.favorite_count, tweet.retweet_count
+            );
+            return false;
+        }
+        if self.delete_geotagged_only && !is_geotagged(tweet) {
+            debug!("Tweet #{} has no location, preserved", tweet.id);
+            return false;
+        }
+        let has_links = !tweet.entities.urls.is_empty();
+        if self.delete_link_tweets_only && !has_links {
+            debug!("Tweet #{} has no links, preserved", tweet.id);
+            return false;
+        }
+        if self.preserve_link_tweets && has_links {
+            debug!("Tweet #{} has links, preserved", tweet.id);
+            return false;
+        }
+        if self.keep_media_tweets && has_media(tweet) {
+            debug!("Tweet #{} has pictures or videos, preserved", tweet.id);
+            return false;
+        }
+        // same check as `has:reply`, tweets that only start with a mention are not replies
+        if self.delete_replies_only && tweet.in_reply_to_status_id.is_none() {
+            debug!("Tweet #{} is not a reply, preserved", tweet.id);
+            return false;
+        }
+        if self.delete_retweets_only && tweet.retweeted_status.is_none() {
+            debug!("Tweet #{} is not a retweet, preserved", tweet.id);
+            return false;
+        }
+        // unlike the counts above, these flags tell if the authenticated user liked or retweeted it
+        if self.preserve_own_engagement
+            && (tweet.favorited == Some(true) || tweet.retweeted == Some(true))
+        {
+            debug!(
+                "Tweet #{} liked or retweeted by the user, preserved",
+                tweet.id
+            );
+            return false;
+        }
+        if let Some(min_text_length) = self.min_text_length {
+            // length in characters, not bytes, so tweets with accents or emoji aren't penalised
+            let length = tweet.text.chars().count();
+            if length >= min_text_length {
+                debug!("Tweet #{} has {} characters, preserved", tweet.id, length);
+                return false;
+            }
+        }
+        if let Some(min_favs_to_keep) = self.min_favs_to_keep {
+            if i64::from(tweet.favorite_count) >= i64::from(min_favs_to_keep) {
+                debug!(
+                    "Tweet #{} has {} likes, preserved",
+                    tweet.id, tweet.favorite_count
+                );
+                return false;
+            }
+        }
+        if let Some(filter) = &self.filter {
+            if !filter.matches(tweet) {
+                debug!("Tweet #{} doesn't match the filter, preserved", tweet.id);
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+// a tweet is withheld if it is blocked in some countries, or has a withheld scope
+/// List of regular expressions matched against the text of tweets
+#[derive(Debug, Clone, Default)]
+pub struct Patterns(Vec<Regex>);
+
+impl Patterns {
+    /// Returns the first pattern matching the given text, if any
+    pub fn find_match(&self, text: &str) -> Option<&Regex> {
+        self.0.iter().find(|pattern| pattern.is_match(text))
+    }
+}
+
+// regexes don't implement equality, two lists are equal if they were parsed from the same patterns
+impl PartialEq for Patterns {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(&other.0)
+                .all(|(a, b)| a.as_str() == b.as_str())
+    }
+}
+
+impl FromStr for Patterns {
+    type Err = String;
+
+    /// Parses a comma separated list of patterns. Commas that are part of a pattern are escaped as
+    /// `\,`, so `[0-9]{1\,3}` is parsed as `[0-9]{1,3}`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut patterns = Vec::new();
+        let mut current = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&',') => {
+                    current.push(',');
+                    chars.next();
+                }
+                ',' => patterns.push(std::mem::take(&mut current)),
+                c => current.push(c),
+            }
+        }
+        patterns.push(current);
+
+        patterns
+            .iter()
+            .map(|pattern| pattern.trim())
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| format!("invalid pattern {:?}: {}", pattern, e))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Patterns)
+    }
+}
+
+fn is_withheld(tweet: &Tweet) -> bool {
+    let withheld_in_countries = tweet
+        .withheld_in_countries
+        .as_ref()
+        .is_some_and(|countries| !countries.is_empty());
+    withheld_in_countries || tweet.withheld_scope.is_some()
+}
+
+// a tweet is geotagged if it has exact coordinates or is associated to a place
+fn is_geotagged(tweet: &Tweet) -> bool {
+    tweet.coordinates.is_some() || tweet.place.is_some()
+}
+
+// we compare sources case-insensitively, so we store them in lowercase
+pub(crate) fn lowercase_all(values: &[String]) -> Vec<String> {
+    values.iter().map(|v| v.to_lowercase()).collect()
+}
+
+// hashtags can be configured with or without the leading `#`, which the tweet entities don't have
+pub(crate) fn hashtag_names(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .map(|v| v.trim_start_matches('#').to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hashtag_names, Filters, Patterns};
+    use crate::tests::sample_tweet;
+    use egg_mode::entities::{
+        HashtagEntity, MediaEntity, MediaSize, MediaSizes, MediaType, ResizeMode, UrlEntity,
+    };
+    use egg_mode::tweet::ExtendedTweetEntities;
+
+    #[test]
+    fn allows_everything_by_default() {
+        assert!(Filters::default().allows(&sample_tweet(1)));
+    }
+
+    #[test]
+    fn preserves_matching_sources() {
+        let filters = Filters {
+            preserve_sources: vec![String::from("name")],
+            ..Default::default()
+        };
+        assert!(!filters.allows(&sample_tweet(1)));
+
+        let filters = Filters {
+            preserve_sources: vec![String::from("web client")],
+            ..Default::default()
+        };
+        assert!(filters.allows(&sample_tweet(1)));
+    }
+
+    #[test]
+    fn only_deletes_matching_sources() {
+        let filters = Filters {
+            delete_sources: vec![String::from("defunct app"), String::from("source")],
+            ..Default::default()
+        };
+        assert!(filters.allows(&sample_tweet(1)));
+
+        let filters = Filters {
+            delete_sources: vec![String::from("defunct app")],
+            ..Default::default()
+        };
+        assert!(!filters.allows(&sample_tweet(1)));
+    }
+
+    #[test]
+    fn forces_erasure_of_withheld_tweets_if_enabled() {
+        let mut withheld = sample_tweet(1);
+        withheld.withheld_in_countries = Some(vec![String::from("DE")]);
+        let mut withheld_scope = sample_tweet(1);
+        withheld_scope.withheld_scope = Some(String::from("status"));
+        let mut empty_countries = sample_tweet(1);
+        empty_countries.withheld_in_countries = Some(Vec::new());
+
+        let filters = Filters {
+            delete_withheld: true,
+            ..Default::default()
+        };
+        assert!(filters.forces_erasure(&withheld));
+        assert!(filters.forces_erasure(&withheld_scope));
+        assert!(!filters.forces_erasure(&empty_countries));
+        assert!(!filters.forces_erasure(&sample_tweet(1)));
+
+        assert!(!Filters::default().forces_erasure(&withheld));
+    }
+
+    #[test]
+    fn preserves_quote_tweets_if_enabled() {
+        let mut quote = sample_tweet(1);
+        quote.quoted_status_id = Some(2);
+
+        let filters = Filters {
+            preserve_quotes: true,
+            ..Default::default()
+        };
+        assert!(!filters.allows(&quote));
+        assert!(filters.allows(&sample_tweet(1)));
+
+        assert!(Filters::default().allows(&quote));
+    }
+
+    #[test]
+    fn only_deletes_tweets_without_engagement_if_enabled() {
+        let filters = Filters {
+            delete_zero_engagement_only: true,
+            ..Default::default()
+        };
+        let mut liked = sample_tweet(1);
+        liked.favorite_count = 1;
+        liked.retweet_count = 0;
+        let mut retweeted = sample_tweet(1);
+        retweeted.favorite_count = 0;
+        retweeted.retweet_count = 1;
+        let mut ignored = sample_tweet(1);
+        ignored.favorite_count = 0;
+        ignored.retweet_count = 0;
+
+        assert!(!filters.allows(&liked));
+        assert!(!filters.allows(&retweeted));
+        assert!(filters.allows(&ignored));
+        assert!(Filters::default().allows(&liked));
+    }
+
+    #[test]
+    fn only_deletes_geotagged_tweets_if_enabled() {
+        let filters = Filters {
+            delete_geotagged_only: true,
+            ..Default::default()
+        };
+        let mut geotagged = sample_tweet(1);
+        geotagged.coordinates = Some((-0.1276, 51.5072));
+
+        assert!(filters.allows(&geotagged));
+        assert!(!filters.allows(&sample_tweet(1)));
+        assert!(Filters::default().allows(&sample_tweet(1)));
+    }
+
+    #[test]
+    fn selects_tweets_by_links_if_enabled() {
+        let mut with_link = sample_tweet(1);
+        with_link.entities.urls.push(UrlEntity {
+            display_url: String::from("example.com"),
+            expanded_url: Some(String::from("https://example.com")),
+            range: (0, 23),
+            url: String::from("https://t.co/abcdef"),
+        });
+        let without_link = sample_tweet(1);
+
+        let delete_only = Filters {
+            delete_link_tweets_only: true,
+            ..Default::default()
+        };
+        assert!(delete_only.allows(&with_link));
+        assert!(!delete_only.allows(&without_link));
+
+        let preserve = Filters {
+            preserve_link_tweets: true,
+            ..Default::default()
+        };
+        assert!(!preserve.allows(&with_link));
+        assert!(preserve.allows(&without_link));
+    }
+
+    #[test]
+    fn preserves_tweets_with_media_if_enabled() {
+        let size = || MediaSize {
+            w: 100,
+            h: 100,
+            resize: ResizeMode::Fit,
+        };
+        let mut with_media = sample_tweet(1);
+        with_media.extended_entities = Some(ExtendedTweetEntities {
+            media: vec![MediaEntity {
+                display_url: String::from("pic.twitter.com/abcdef"),
+                expanded_url: String::from("https://twitter.com/user/status/1/photo/1"),
+                id: 1,
+                range: (0, 23),
+                media_url: String::from("http://pbs.twimg.com/media/abcdef.jpg"),
+                media_url_https: String::from("https://pbs.twimg.com/media/abcdef.jpg"),
+                sizes: MediaSizes {
+                    thumb: size(),
+                    small: size(),
+                    medium: size(),
+                    large: size(),
+                },
+                source_status_id: None,
+                media_type: MediaType::Photo,
+                url: String::from("https://t.co/abcdef"),
+                video_info: None,
+                ext_alt_text: None,
+            }],
+        });
+        let text_only = sample_tweet(1);
+
+        let filters = Filters {
+            keep_media_tweets: true,
+            ..Default::default()
+        };
+        assert!(!filters.allows(&with_media));
+        assert!(filters.allows(&text_only));
+
+        assert!(Filters::default().allows(&with_media));
+    }
+
+    #[test]
+    fn only_deletes_replies_if_enabled() {
+        let mut reply = sample_tweet(1);
+        reply.in_reply_to_screen_name = Some(String::from("stranger"));
+        reply.in_reply_to_status_id = Some(7);
+        let standalone = sample_tweet(1);
+        let mut mention = sample_tweet(1);
+        mention.in_reply_to_screen_name = Some(String::from("stranger"));
+
+        let filters = Filters {
+            delete_replies_only: true,
+            ..Default::default()
+        };
+        assert!(filters.allows(&reply));
+        assert!(!filters.allows(&standalone));
+        assert!(!filters.allows(&mention));
+
+        assert!(Filters::default().allows(&standalone));
+    }
+
+    #[test]
+    fn only_deletes_retweets_if_enabled() {
+        let mut retweet = sample_tweet(1);
+        retweet.retweeted_status = Some(Box::new(sample_tweet(2)));
+        let original = sample_tweet(1);
+
+        let filters = Filters {
+            delete_retweets_only: true,
+            ..Default::default()
+        };
+        assert!(filters.allows(&retweet));
+        assert!(!filters.allows(&original));
+
+        assert!(Filters::default().allows(&original));
+    }
+
+    #[test]
+    fn preserves_tweets_liked_or_retweeted_by_the_user_if_enabled() {
+        let filters = Filters {
+            preserve_own_engagement: true,
+            ..Default::default()
+        };
+        let mut liked = sample_tweet(1);
+        liked.favorited = Some(true);
+        let mut retweeted = sample_tweet(1);
+        retweeted.retweeted = Some(true);
+        let mut neither = sample_tweet(1);
+        neither.favorited = Some(false);
+        neither.retweeted = Some(false);
+
+        assert!(!filters.allows(&liked));
+        assert!(!filters.allows(&retweeted));
+        assert!(filters.allows(&neither));
+        assert!(filters.allows(&sample_tweet(1)));
+        assert!(Filters::default().allows(&liked));
+    }
+
+    #[test]
+    fn only_deletes_short_tweets_if_enabled() {
+        let filters = Filters {
+            min_text_length: Some(5),
+            ..Default::default()
+        };
+        let mut short = sample_tweet(1);
+        // 4 characters, but 8 bytes
+        short.text = String::from("ñoño");
+        let mut long = sample_tweet(1);
+        long.text = String::from("ñoños");
+
+        assert!(filters.allows(&short));
+        assert!(!filters.allows(&long));
+    }
+
+    #[test]
+    fn preserves_tweets_with_hashtags_to_keep() {
+        let filters = Filters {
+            preserve_hashtags: hashtag_names(&[String::from("#Keep"), String::from("archive")]),
+            ..Default::default()
+        };
+        let with_hashtag = |text: &str| {
+            let mut tweet = sample_tweet(400);
+            tweet.entities.hashtags.push(HashtagEntity {
+                range: (0, text.len() + 1),
+                text: String::from(text),
+            });
+            tweet
+        };
+
+        assert!(!filters.allows(&with_hashtag("keep")));
+        assert!(!filters.allows(&with_hashtag("ARCHIVE")));
+        assert!(filters.allows(&with_hashtag("keeper")));
+        assert!(filters.allows(&sample_tweet(400)));
+    }
+
+    #[test]
+    fn preserves_tweets_matching_patterns() {
+        let filters = Filters {
+            preserve_patterns: "(?i)\\bkeep\\b, ^RT @[a-z]{1\\,3}:".parse().unwrap(),
+            ..Default::default()
+        };
+        let with_text = |text: &str| {
+            let mut tweet = sample_tweet(400);
+            tweet.text = String::from(text);
+            tweet
+        };
+
+        assert!(!filters.allows(&with_text("Please KEEP this one")));
+        assert!(!filters.allows(&with_text("RT @abc: hello")));
+        assert!(filters.allows(&with_text("RT @abcd: hello")));
+        assert!(filters.allows(&with_text("keeper")));
+    }
+
+    #[test]
+    fn forces_erasure_of_tweets_matching_patterns() {
+        let filters = Filters {
+            delete_patterns: "@old_handle, (?i)embarrassing".parse().unwrap(),
+            ..Default::default()
+        };
+        let with_text = |text: &str| {
+            let mut tweet = sample_tweet(1);
+            tweet.text = String::from(text);
+            tweet
+        };
+
+        assert!(filters.forces_erasure(&with_text("cc @old_handle")));
+        assert!(filters.forces_erasure(&with_text("So Embarrassing")));
+        assert!(!filters.forces_erasure(&with_text("nothing to see")));
+        assert!(filters.allows(&with_text("cc @old_handle")));
+    }
+
+    #[test]
+    fn fails_on_invalid_patterns() {
+        let result = "valid, (unclosed".parse::<Patterns>();
+        assert!(result.unwrap_err().contains("(unclosed"));
+        assert_eq!(" , ".parse::<Patterns>(), Ok(Patterns::default()));
+    }
+
+    #[test]
+    fn preserves_tweets_with_many_likes_if_enabled() {
+        let filters = Filters {
+            min_favs_to_keep: Some(10),
+            ..Default::default()
+        };
+        let mut popular = sample_tweet(400);
+        popular.favorite_count = 10;
+        let mut unpopular = sample_tweet(400);
+        unpopular.favorite_count = 9;
+
+        assert!(!filters.allows(&popular));
+        assert!(filters.allows(&unpopular));
+        assert!(Filters::default().allows(&popular));
+    }
+
+    #[test]
+    fn only_deletes_tweets_matching_the_filter_if_set() {
+        let filters = Filters {
+            filter: Some("favorites<10 AND has:reply".parse().unwrap()),
+            ..Default::default()
+        };
+        let mut reply = sample_tweet(1);
+        reply.favorite_count = 3;
+        reply.in_reply_to_status_id = Some(7);
+        let mut popular_reply = reply.clone();
+        popular_reply.favorite_count = 30;
+
+        assert!(filters.allows(&reply));
+        assert!(!filters.allows(&popular_reply));
+        assert!(!filters.allows(&sample_tweet(1)));
+    }
+
+    #[test]
+    fn matches_sources_ignoring_case() {
+        let mut tweet = sample_tweet(1);
+        tweet.source.name = String::from("Twitter Web Client");
+        let env = crate::tests::sample_env(vec![("TP_PRESERVE_SOURCES", "twitter WEB")]);
+
+        assert!(!Filters::from_env(&env).allows(&tweet));
+    }
+}
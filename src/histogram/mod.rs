@@ -0,0 +1,112 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::fmt;
+
+/// Number of tweets by age, to help choosing `preserve_days`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgeHistogram {
+    /// Tweets less than 30 days old
+    pub up_to_30_days: usize,
+    /// Tweets between 30 and 90 days old
+    pub up_to_90_days: usize,
+    /// Tweets between 90 days and a year old
+    pub up_to_365_days: usize,
+    /// Tweets a year old or older
+    pub older: usize,
+}
+
+impl AgeHistogram {
+    /// Longest bar we draw when displaying the histogram
+    const BAR_WIDTH: usize = 40;
+
+    /// Counts a tweet created at `created_at`, with its age measured at `now`
+    pub fn record(&mut self, created_at: DateTime<Utc>, now: DateTime<Utc>) {
+        let age = now - created_at;
+        let bucket = if age < Duration::days(30) {
+            &mut self.up_to_30_days
+        } else if age < Duration::days(90) {
+            &mut self.up_to_90_days
+        } else if age < Duration::days(365) {
+            &mut self.up_to_365_days
+        } else {
+            &mut self.older
+        };
+        *bucket += 1;
+    }
+
+    /// Number of tweets counted
+    pub fn total(&self) -> usize {
+        self.up_to_30_days + self.up_to_90_days + self.up_to_365_days + self.older
+    }
+
+    fn buckets(&self) -> [(&'static str, usize); 4] {
+        [
+            ("0-30 days", self.up_to_30_days),
+            ("30-90 days", self.up_to_90_days),
+            ("90-365 days", self.up_to_365_days),
+            ("1 year+", self.older),
+        ]
+    }
+}
+
+impl fmt::Display for AgeHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let buckets = self.buckets();
+        let max = buckets.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        write!(f, "{} tweets by age", self.total())?;
+        for (label, count) in &buckets {
+            let width = (count * AgeHistogram::BAR_WIDTH)
+                .checked_div(max)
+                .unwrap_or(0);
+            write!(f, "\n{:>12} | {:<40} {}", label, "#".repeat(width), count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AgeHistogram;
+    use chrono::prelude::*;
+    use chrono::Duration;
+
+    #[test]
+    fn counts_tweets_by_age() {
+        let now = Utc::now();
+        let mut histogram = AgeHistogram::default();
+        for days in &[0, 29, 30, 89, 90, 364, 365, 2000] {
+            histogram.record(now - Duration::days(*days), now);
+        }
+
+        let expected = AgeHistogram {
+            up_to_30_days: 2,
+            up_to_90_days: 2,
+            up_to_365_days: 2,
+            older: 2,
+        };
+        assert_eq!(histogram, expected);
+        assert_eq!(histogram.total(), 8);
+    }
+
+    #[test]
+    fn displays_bars_relative_to_the_largest_bucket() {
+        let histogram = AgeHistogram {
+            up_to_30_days: 4,
+            up_to_90_days: 0,
+            up_to_365_days: 2,
+            older: 1,
+        };
+
+        let lines: Vec<String> = histogram.to_string().lines().map(String::from).collect();
+
+        let bar = |width| format!("{:<40}", "#".repeat(width));
+        let expected = vec![
+            String::from("7 tweets by age"),
+            format!("   0-30 days | {} 4", bar(40)),
+            format!("  30-90 days | {} 0", bar(0)),
+            format!(" 90-365 days | {} 2", bar(20)),
+            format!("     1 year+ | {} 1", bar(10)),
+        ];
+        assert_eq!(lines, expected);
+    }
+}
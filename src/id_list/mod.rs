@@ -0,0 +1,49 @@
+use std::io::BufRead;
+
+/// Reads the ids of tweets from `reader`, one per line. Anything after a `#` is a comment. Blank
+/// lines are ignored, and lines that aren't an id are logged and skipped
+pub fn read_ids<R: BufRead>(reader: R) -> Vec<u64> {
+    let mut ids = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Skipping line {} of the list of tweets: {}", number + 1, e);
+                continue;
+            }
+        };
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.parse() {
+            Ok(id) => ids.push(id),
+            Err(e) => warn!(
+                "Skipping line {} of the list of tweets, {:?} is not a tweet id: {}",
+                number + 1,
+                line,
+                e
+            ),
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_ids;
+
+    #[test]
+    fn skips_blank_and_invalid_lines() {
+        let input = "1234\n\n  5678  \nnot an id\n-1\n9012";
+
+        assert_eq!(read_ids(input.as_bytes()), vec![1234, 5678, 9012]);
+    }
+
+    #[test]
+    fn ignores_comments() {
+        let input = "# failed in the last run\n1234 # Rate limit exceeded\n5678#";
+
+        assert_eq!(read_ids(input.as_bytes()), vec![1234, 5678]);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::api::{APIError, AccountInfo, DirectMessage, EraseOutcome, TwitterAPI};
+use crate::pacing::RateLimitBudget;
+use chrono_tz::Tz;
+use egg_mode::tweet::Tweet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+/// Formats a deleted tweet as an entry of the journal: a heading with the date it was posted, in
+/// the given time zone, its text quoted and a line with its likes and retweets
+pub fn entry(tweet: &Tweet, timezone: Tz) -> String {
+    let quoted: Vec<String> = tweet
+        .text
+        .lines()
+        .map(|line| format!("> {}", line).trim_end().to_string())
+        .collect();
+    format!(
+        "## {}\n\n{}\n\n{} likes, {} retweets, tweet #{}\n\n",
+        tweet
+            .created_at
+            .with_timezone(&timezone)
+            .format("%Y-%m-%d %H:%M"),
+        quoted.join("\n"),
+        tweet.favorite_count,
+        tweet.retweet_count,
+        tweet.id
+    )
+}
+
+/// Wrapper over a `TwitterAPI` that appends every tweet it deletes to a journal, in Markdown, as a
+/// keepsake we can read later. Failing to write the journal doesn't stop the run.
+pub struct JournalAPI<'r> {
+    api: &'r mut dyn TwitterAPI,
+    path: String,
+    file: File,
+    timezone: Tz,
+}
+
+impl<'r> JournalAPI<'r> {
+    /// Opens the journal at `path`, appending to it if it exists
+    pub fn open(
+        api: &'r mut dyn TwitterAPI,
+        path: &str,
+        timezone: Tz,
+    ) -> Result<JournalAPI<'r>, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Couldn't open journal {}: {}", path, e))?;
+        Ok(JournalAPI {
+            api,
+            path: String::from(path),
+            file,
+            timezone,
+        })
+    }
+
+    fn record(&mut self, tweet: &Tweet) {
+        if let Err(e) = write!(self.file, "{}", entry(tweet, self.timezone)) {
+            warn!("Couldn't write to journal {}: {}", self.path, e);
+        }
+    }
+}
+
+impl<'r> TwitterAPI for JournalAPI<'r> {
+    fn validate_token(&mut self) -> Result<(), APIError> {
+        self.api.validate_token()
+    }
+
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError> {
+        self.api.get_user_id(screen_name)
+    }
+
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        self.api.get_account_info(screen_name)
+    }
+
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.api.get_statuses_count()
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        self.api.get_tweet(id)
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        self.api.rate_limit_status()
+    }
+
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        self.api.reply_count(tweet)
+    }
+
+    fn api_calls(&self) -> usize {
+        self.api.api_calls()
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.api.token_user_id()
+    }
+
+    fn tweet_count(&self) -> Option<u64> {
+        self.api.tweet_count()
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.api.pinned_tweet_id()
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        self.api.user_id()
+    }
+
+    fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.user_timeline_next_page()
+    }
+
+    fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.likes_timeline_next_page()
+    }
+
+    fn unlike_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        self.api.unlike_tweet(tweet)
+    }
+
+    fn unretweet_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        self.api.unretweet_tweet(tweet)
+    }
+
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
+        let result = self.api.erase_tweet(tweet);
+        if result == Ok(EraseOutcome::Deleted) {
+            self.record(tweet);
+        }
+        result
+    }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        self.api.direct_messages_next_page()
+    }
+
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError> {
+        self.api.erase_dm(dm)
+    }
+
+    fn maintain_tweets(
+        &mut self,
+        tweets: &[&Tweet],
+        concurrency: usize,
+    ) -> Vec<Result<EraseOutcome, APIError>> {
+        let results = self.api.maintain_tweets(tweets, concurrency);
+        for (tweet, result) in tweets.iter().zip(&results) {
+            if *result == Ok(EraseOutcome::Deleted) {
+                self.record(tweet);
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JournalAPI;
+    use crate::api::{EraseOutcome, TestAPI, TwitterAPI};
+    use crate::tests::sample_tweet;
+    use chrono::{TimeZone, Utc};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn appends_deleted_tweets_as_markdown() {
+        let path =
+            env::temp_dir().join(format!("twitter_privacy_journal_{}.md", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut first = sample_tweet(1);
+        first.id = 1;
+        first.created_at = Utc.ymd(2020, 5, 17).and_hms(10, 30, 0);
+        first.text = String::from("Good morning\n\nand good night");
+        first.favorite_count = 12;
+        first.retweet_count = 3;
+        let mut second = sample_tweet(1);
+        second.id = 2;
+        second.created_at = Utc.ymd(2020, 5, 18).and_hms(23, 5, 0);
+        second.text = String::from("Bye");
+        second.favorite_count = 0;
+        second.retweet_count = 0;
+        let mut api = TestAPI {
+            ..Default::default()
+        };
+
+        {
+            let mut journal = JournalAPI::open(&mut api, path, chrono_tz::Europe::Madrid).unwrap();
+            journal.erase_tweet(&first).unwrap();
+            journal.maintain_tweets(&[&second], 2);
+        }
+        let mut api = TestAPI {
+            erase_tweet_answer: Ok(EraseOutcome::Skipped(String::from("not owned"))),
+            ..Default::default()
+        };
+        JournalAPI::open(&mut api, path, chrono_tz::Europe::Madrid)
+            .unwrap()
+            .erase_tweet(&first)
+            .unwrap();
+        let journal = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let expected = "\
+## 2020-05-17 12:30
+
+> Good morning
+>
+> and good night
+
+12 likes, 3 retweets, tweet #1
+
+## 2020-05-19 01:05
+
+> Bye
+
+0 likes, 0 retweets, tweet #2
+
+";
+        assert_eq!(journal, expected);
+    }
+}
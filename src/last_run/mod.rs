@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io::ErrorKind;
+
+/// Reads the time written by `write_to` from the given file. A missing file means there was no
+/// previous run
+pub fn read_from(path: &str) -> Result<Option<DateTime<Utc>>, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => DateTime::parse_from_rfc3339(text.trim())
+            .map(|at| Some(at.with_timezone(&Utc)))
+            .map_err(|e| format!("Couldn't read the last run from {}: {}", path, e)),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Couldn't open {}: {}", path, e)),
+    }
+}
+
+/// Writes the given time to the file, in RFC 3339 format, replacing its contents
+pub fn write_to(path: &str, at: DateTime<Utc>) -> Result<(), String> {
+    fs::write(path, at.to_rfc3339())
+        .map_err(|e| format!("Couldn't write the last run to {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_from, write_to};
+    use chrono::{TimeZone, Utc};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn writes_and_reads_the_time_of_the_last_run() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_last_run_{}.txt",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let at = Utc.ymd(2020, 5, 17).and_hms(10, 30, 0);
+
+        write_to(path, at).unwrap();
+        let text = fs::read_to_string(path).unwrap();
+        let read = read_from(path);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(text, "2020-05-17T10:30:00+00:00");
+        assert_eq!(read, Ok(Some(at)));
+    }
+
+    #[test]
+    fn has_no_last_run_without_a_file() {
+        assert_eq!(read_from("/non/existent/last_run.txt"), Ok(None));
+    }
+}
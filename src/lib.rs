@@ -1,15 +1,73 @@
 #[macro_use]
 extern crate log;
 
+mod action_log;
 mod api;
+mod archive;
+mod args;
+mod backup;
+mod cancellation;
 mod config;
-
-use api::{APIError, RealAPI, TwitterAPI};
+mod cursor;
+mod dry_run;
+mod estimate;
+mod export;
+mod filters;
+mod histogram;
+mod id_list;
+mod journal;
+mod last_run;
+mod media;
+mod pacing;
+mod predicate;
+mod reconcile;
+mod retry;
+mod retry_queue;
+mod review;
+mod run;
+mod store;
+mod summary;
+mod threads;
+
+pub use action_log::{ActionLogAPI, ActionLogEntry};
+pub use api::{
+    APIError, AccountInfo, DirectMessage, EggModeTransport, EraseOutcome, RealAPI, Transport,
+    TransportFuture, TwitterAPI,
+};
+pub use archive::{read_archive, ArchivedTweet};
+pub use args::Args;
+pub use backup::{BackupAPI, BackupEntry};
+pub use cancellation::Cancellation;
 use chrono::prelude::*;
 use chrono::Duration;
-use config::EnvValues;
+use chrono_tz::Tz;
+pub use config::{
+    ActionOrder, ClearOptions, DateWindow, EnvValues, Mode, Profile, Settings, SubAction,
+    TimelineKind, TimelineRules,
+};
+pub use cursor::Cursors;
+pub use dry_run::DryRunAPI;
 use egg_mode::tweet::Tweet;
+pub use estimate::{Estimate, Progress};
+pub use export::{CsvExport, ExportRow};
+pub use filters::{Filters, Patterns};
+pub use histogram::AgeHistogram;
+pub use id_list::read_ids;
+pub use journal::JournalAPI;
+pub use media::{media_urls, Downloader, HttpDownloader, MediaAPI};
+pub use pacing::{AdaptiveDelay, Cooldown, Pacing, RandomSource, RateLimitBudget, ThreadRandom};
+pub use predicate::{Comparison, Counter, Feature, Predicate};
+pub use reconcile::Reconciliation;
+pub use retry::{Backoff, Clock, SystemClock};
+pub use run::{Outcome, Run, TweetAction, TweetObserver};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashSet, VecDeque};
 use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+pub use store::{latest_entries, JsonLinesStore, StoreAPI, StoredTweet, TweetStore};
+use summary::RecordingAPI;
+pub use summary::RunSummary;
 
 /// Defines errors we can get when executing the methods of the library
 #[derive(Debug, Clone, PartialEq)]
@@ -29,6 +87,31 @@ impl fmt::Display for Errors {
     }
 }
 
+/// Outcome of erasing old tweets for a set of accounts
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountsSummary {
+    pub succeeded: Vec<(String, RunSummary)>,
+    pub failed: Vec<(String, Errors)>,
+}
+
+impl fmt::Display for AccountsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Processed {} account(s), {} failed",
+            self.succeeded.len() + self.failed.len(),
+            self.failed.len()
+        )?;
+        for (handle, summary) in &self.succeeded {
+            write!(f, "\n - @{}: {}", handle, summary)?;
+        }
+        for (handle, e) in &self.failed {
+            write!(f, "\n - @{}: {}", handle, e)?;
+        }
+        Ok(())
+    }
+}
+
 /// Tries to erase old tweets for a user account
 ///
 /// This method will load configuration from environment variables as described in the Readme file
@@ -45,22 +128,332 @@ impl fmt::Display for Errors {
 /// # Errors
 ///
 /// - Configuration can't be loaded properly
+/// - The user didn't confirm the deletion via `TP_CONFIRM_DELETE`
 /// - Errors while interacting with Twitter API
-pub fn clear_old_tweets() -> Result<(), Errors> {
+pub fn clear_old_tweets() -> Result<RunSummary, Errors> {
     info!("Retrieve environment values");
     let env_values = EnvValues::load().map_err(Errors::EnvValueErrors)?;
-    let preserve_days = env_values.preserve_days;
     // dbg!(&env_values);
 
+    clear_old_tweets_with(env_values, RealAPI::new)
+}
+
+/// Tries to erase old tweets for a user account, using the given `EnvValues` and building the
+/// `TwitterAPI` implementation with `build_api`.
+///
+/// The user must have confirmed the deletion in `EnvValues`, otherwise we abort before building
+/// the API, so no request is sent to Twitter. In `Mode::Scan`, `Mode::Review` and `Mode::Reconcile`
+/// no tweet is modified, so no confirmation is needed, and neither is it for dry runs. In `Mode::EraseIds` the ids of the tweets to erase are
+/// read from the files of the archive in `archive_paths`, or from `delete_ids_file`, or from the standard input if neither is set. If `EnvValues` has a `summary_path`, the summary of
+/// the run is also written to that file, and likewise its counters as Prometheus metrics if it
+/// has a `metrics_path`.
+///
+/// # Impure
+///
+/// - Multiple requests to Twitter API, depending on `build_api`
+/// - Writes a file if `summary_path` or `metrics_path` are set
+/// - Reads the standard input in `Mode::EraseIds` if `archive_paths` and `delete_ids_file` are not set
+///
+/// # Errors
+///
+/// - The user didn't confirm the deletion
+/// - The file in `TP_DELETE_IDS_FILE` can't be opened
+/// - Errors while interacting with Twitter API
+/// - The summary can't be written to `TP_SUMMARY_PATH` or `TP_METRICS_PATH`
+pub fn clear_old_tweets_with<A, F>(
+    env_values: EnvValues,
+    build_api: F,
+) -> Result<RunSummary, Errors>
+where
+    A: TwitterAPI,
+    F: FnOnce(EnvValues) -> Result<A, APIError>,
+{
+    let mode = env_values.mode;
+    if (mode == Mode::Erase || mode == Mode::EraseIds) && !env_values.dry_run {
+        check_confirmation(&env_values)?;
+    }
+    let listed_ids = if mode == Mode::EraseIds {
+        listed_ids(&env_values)?
+    } else {
+        Vec::new()
+    };
+    let run = Run::from_env(&env_values);
+    let summary_path = env_values.summary_path.clone();
+    let metrics_path = env_values.metrics_path.clone();
+    let review_html = env_values.review_html.clone();
+    let started_at = Utc::now();
+
     info!("Set up API trait for connecting to Twitter");
-    let mut api = RealAPI::new(env_values).map_err(Errors::APIErrors)?;
+    let mut api = build_api(env_values).map_err(Errors::APIErrors)?;
+    if let Some(tweets) = api.tweet_count() {
+        let page_size = api::PAGE_SIZE as u64;
+        info!(
+            "Expected work: {}",
+            Estimate::new(tweets, page_size, run.settings().concurrency)
+        );
+    }
+
+    let mut summary = match mode {
+        Mode::Erase => {
+            info!("Erase old Tweets for user");
+            run.execute(&mut api)?
+        }
+        Mode::Scan => {
+            info!("Scan timelines of the user, without modifying any tweet");
+            let (summary, scanned) = run.scan(&mut api)?;
+            let seconds = (Utc::now() - started_at).num_milliseconds() as f64 / 1000.0;
+            info!(
+                "Scanned {} tweets in {:.1} seconds, {:.1} tweets per second",
+                scanned,
+                seconds,
+                scanned as f64 / seconds.max(0.001)
+            );
+            summary
+        }
+        Mode::Review => {
+            info!("Review the tweets to erase, without modifying them");
+            let (summary, tweets) = run.review(&mut api)?;
+            info!("Write {} tweets to erase to {}", tweets.len(), review_html);
+            review::write_html(&review_html, &tweets).map_err(Errors::LibErrors)?;
+            summary
+        }
+        Mode::EraseIds => {
+            info!("Erase {} listed tweets", listed_ids.len());
+            run.erase_ids(&mut api, &listed_ids)?
+        }
+        Mode::Reconcile => {
+            info!("Compare the tweets in the user timeline with those posted by the user");
+            let (summary, _) = run.reconcile(&mut api)?;
+            summary
+        }
+    };
+    // include the requests done while building the API, like validating the token
+    summary.api_calls = api.api_calls();
+    summary.started_at = Some(started_at);
+    summary.finished_at = Some(Utc::now());
+
+    if let Some(path) = summary_path {
+        info!("Write summary of the run to {}", path);
+        summary.write_to(&path).map_err(Errors::LibErrors)?;
+    }
+    if let Some(path) = metrics_path {
+        info!("Write metrics of the run to {}", path);
+        summary.write_metrics_to(&path).map_err(Errors::LibErrors)?;
+    }
+    Ok(summary)
+}
+
+/// Checks the credentials in the environment variables are valid, and returns the details of the
+/// account in `TP_USER_HANDLE`, without processing any timeline
+///
+/// # Impure
+///
+/// - Loads values from environment variables
+/// - A few requests to Twitter API
+///
+/// # Errors
+///
+/// - Configuration can't be loaded properly
+/// - The credentials are not valid, or the account can't be found
+pub fn check_credentials() -> Result<AccountInfo, Errors> {
+    let env_values = EnvValues::load().map_err(Errors::EnvValueErrors)?;
+    let mut api = RealAPI::new(env_values.clone()).map_err(Errors::APIErrors)?;
+    check_credentials_with(&env_values, &mut api)
+}
+
+/// Validates the token used by `api` and returns the details of the account in `env_values`
+///
+/// # Impure
+///
+/// - Requests to Twitter API, depending on `api`
+///
+/// # Errors
+///
+/// - The token is not valid, or the account can't be found
+/// - The account is not the one the token belongs to
+pub fn check_credentials_with(
+    env_values: &EnvValues,
+    api: &mut dyn TwitterAPI,
+) -> Result<AccountInfo, Errors> {
+    api.validate_token().map_err(Errors::APIErrors)?;
+    let account = api
+        .get_account_info(&env_values.user_handle)
+        .map_err(Errors::APIErrors)?;
+    api::check_token_user(api, &account.screen_name, account.user_id).map_err(Errors::APIErrors)?;
+    info!("Credentials are valid for {}", account);
+    Ok(account)
+}
+
+/// Tries to erase old tweets for all the accounts configured in environment variables
+///
+/// See `EnvValues::load_accounts` for the format of the variables. A failure processing an account
+/// doesn't stop us from processing the rest, check `AccountsSummary` for the outcome of each account.
+/// If the run is cancelled via `Cancellation::global`, the remaining accounts are not processed.
+///
+/// # Impure
+///
+/// - Loads values from environment variables
+/// - Multiple requests to Twitter API
+///
+/// # Errors
+///
+/// - Configuration can't be loaded properly
+pub fn clear_old_tweets_all_accounts() -> Result<AccountsSummary, Errors> {
+    clear_old_tweets_for(&Args::default())
+}
+
+/// Like `clear_old_tweets_all_accounts`, but as a dry run for every account: the tweets that
+/// would be unliked, unretweeted or erased are logged, and none is modified
+///
+/// # Impure
+///
+/// - Loads values from environment variables
+/// - Multiple requests to Twitter API, none modifying a tweet
+///
+/// # Errors
+///
+/// - Configuration can't be loaded properly
+pub fn preview_old_tweets_all_accounts() -> Result<AccountsSummary, Errors> {
+    clear_old_tweets_for(&Args {
+        dry_run: true,
+        ..Default::default()
+    })
+}
+
+/// Like `clear_old_tweets_all_accounts`, for the accounts of the profiles selected in `args`, and
+/// as a dry run for every account if `args` asks for it regardless of `TP_DRY_RUN`
+///
+/// # Impure
+///
+/// - Loads values from environment variables
+/// - Multiple requests to Twitter API
+///
+/// # Errors
+///
+/// - Configuration can't be loaded properly, or the selected profile isn't defined
+pub fn clear_old_tweets_for(args: &Args) -> Result<AccountsSummary, Errors> {
+    info!("Retrieve environment values for all accounts");
+    let mut accounts = EnvValues::load_profiles(&args.profile).map_err(Errors::EnvValueErrors)?;
+    if args.dry_run {
+        accounts
+            .iter_mut()
+            .for_each(|env_values| env_values.dry_run = true);
+    }
+    if args.resume {
+        accounts
+            .iter_mut()
+            .for_each(|env_values| env_values.resume = true);
+    }
+
+    Ok(clear_accounts_with(accounts, RealAPI::new))
+}
+
+/// Runs `clear_old_tweets_with` for each one of the given accounts, collecting the outcome of each
+/// one in an `AccountsSummary`.
+///
+/// # Impure
+///
+/// - Multiple requests to Twitter API, depending on `build_api`
+pub fn clear_accounts_with<A, F>(accounts: Vec<EnvValues>, mut build_api: F) -> AccountsSummary
+where
+    A: TwitterAPI,
+    F: FnMut(EnvValues) -> Result<A, APIError>,
+{
+    let mut summary = AccountsSummary::default();
+
+    for env_values in accounts {
+        let handle = env_values.user_handle.clone();
+        info!("Processing account @{}", handle);
+
+        match clear_old_tweets_with(env_values, &mut build_api) {
+            Ok(run_summary) if run_summary.cancelled => {
+                warn!("Run cancelled, skipping any remaining account");
+                summary.succeeded.push((handle, run_summary));
+                break;
+            }
+            Ok(run_summary) => summary.succeeded.push((handle, run_summary)),
+            Err(e) => {
+                error!("Failed to process account @{}: {}", handle, e);
+                summary.failed.push((handle, e));
+            }
+        }
+    }
+
+    summary
+}
+
+/// Reads the ids of the tweets to erase from the archive in `archive_paths` if set, or from
+/// `delete_ids_file` if set, or the standard input otherwise
+fn listed_ids(env_values: &EnvValues) -> Result<Vec<u64>, Errors> {
+    if !env_values.archive_paths.is_empty() {
+        return archived_ids(env_values, Utc::now());
+    }
+    match &env_values.delete_ids_file {
+        Some(path) => {
+            info!("Read ids of the tweets to erase from {}", path);
+            let file = File::open(path)
+                .map_err(|e| Errors::LibErrors(format!("Couldn't open {}: {}", path, e)))?;
+            Ok(read_ids(BufReader::new(file)))
+        }
+        None => {
+            info!("Read ids of the tweets to erase from the standard input");
+            Ok(read_ids(io::stdin().lock()))
+        }
+    }
+}
+
+/// Reads the ids of the tweets in the files of the archive of the account, in `archive_paths`. Only
+/// the tweets older than `preserve_days` at `now` are selected, unless `delete_all` is set, as the
+/// archive goes back further than the timelines and the listed tweets are erased whatever their age.
+/// A `preserve_days` reaching further back than the dates we can represent selects nothing
+fn archived_ids(env_values: &EnvValues, now: DateTime<Utc>) -> Result<Vec<u64>, Errors> {
+    let cutoff = now.checked_sub_signed(env_values.preserve_days);
+    let mut ids = Vec::new();
+    for path in &env_values.archive_paths {
+        let file = File::open(path)
+            .map_err(|e| Errors::LibErrors(format!("Couldn't open {}: {}", path, e)))?;
+        let tweets = read_archive(BufReader::new(file))
+            .map_err(|e| Errors::LibErrors(format!("Couldn't read archive {}: {}", path, e)))?;
+        let total = tweets.len();
+        ids.extend(
+            tweets
+                .into_iter()
+                .filter(|tweet| {
+                    env_values.delete_all || cutoff.is_some_and(|cutoff| tweet.created_at < cutoff)
+                })
+                .map(|tweet| tweet.id),
+        );
+        info!("Read {} tweets from the archive {}", total, path);
+    }
+    info!("Selected {} archived tweets to erase", ids.len());
+    Ok(ids)
+}
 
-    info!("Erase old Tweets for user");
-    clear_user_timelines(&mut api, preserve_days)
+/// Returns an error unless the user explicitly acknowledged that tweets will be erased, and that
+/// tweets of any age will be erased if `TP_DELETE_ALL` is set
+fn check_confirmation(env_values: &EnvValues) -> Result<(), Errors> {
+    if !env_values.confirm_delete {
+        return Err(Errors::LibErrors(format!(
+            "This tool erases tweets permanently. Set TP_CONFIRM_DELETE=\"{}\" to confirm you want to proceed",
+            EnvValues::CONFIRM_DELETE_VALUE
+        )));
+    }
+    if env_values.delete_all {
+        if !env_values.delete_all_acknowledged {
+            return Err(Errors::LibErrors(format!(
+                "TP_DELETE_ALL erases all your tweets, including the most recent ones. Set TP_DELETE_ALL_ACKNOWLEDGED=\"{}\" to confirm you want to proceed",
+                EnvValues::DELETE_ALL_ACKNOWLEDGED_VALUE
+            )));
+        }
+        warn!("TP_DELETE_ALL is set: erasing tweets of any age, ignoring TP_PRESERVE_DAYS and TP_MINIMUM_AGE_DAYS");
+    }
+    Ok(())
 }
 
-/// Processes a series of timelines for the given user to erase old tweets. The `Config` struct
-/// contains the threshold for tweet deletion.
+/// Processes the timelines selected in `settings` for the given user to erase old tweets, with the
+/// rules of each timeline if it has any. If given, `action` is applied to each selected tweet instead
+/// of the default unlike, unretweet and erase, and `observer` is told the outcome of each tweet
+/// processed by the default.
 ///
 /// # Impure
 ///
@@ -70,35 +463,560 @@ pub fn clear_old_tweets() -> Result<(), Errors> {
 ///
 /// - Errors while removing elements from the timelines
 /// - Other errors when interacting with Twitter API
-fn clear_user_timelines(api: &mut dyn TwitterAPI, preserve_days: i64) -> Result<(), Errors> {
-    info!("Processing User timeline");
-    let user_tl = |c_api: &mut dyn TwitterAPI| c_api.user_timeline_next_page();
-    process_timeline(
-        "User Timeline",
-        preserve_days,
-        api,
-        user_tl,
-        default_maintenance_action,
-    )?;
-
-    info!("Processing Likes timeline");
-    let likes_tl = |c_api: &mut dyn TwitterAPI| c_api.likes_timeline_next_page();
-    process_timeline(
-        "Likes Timeline",
-        preserve_days,
-        api,
-        likes_tl,
-        default_maintenance_action,
-    )?;
-
-    info!("Processed all timelines. Exiting.");
-    Ok(())
+fn clear_user_timelines(
+    api: &mut dyn TwitterAPI,
+    settings: &Settings,
+    mut action: Option<&mut TweetAction>,
+    mut observer: Option<&mut TweetObserver>,
+) -> Result<RunSummary, Errors> {
+    let mut summary = RunSummary::default();
+    let progress = Progress::new(api.tweet_count(), settings.progress_every)
+        .log_deleted_every(settings.log_every);
+
+    if settings.oldest_first {
+        summary.merge(&clear_timelines_oldest_first(
+            api, settings, action, observer, &progress,
+        )?);
+    } else {
+        for timeline in &settings.timelines {
+            info!("Processing {}", timeline);
+            let name = timeline.to_string();
+            let timeline_settings = settings.for_timeline(*timeline);
+            let tl_summary = clear_timeline(
+                &name,
+                &timeline_settings,
+                api,
+                timeline_pages(*timeline),
+                action.as_deref_mut(),
+                observer.as_deref_mut(),
+                &progress,
+            )?;
+            summary.add_timeline(&name, &tl_summary);
+        }
+    }
+
+    if settings.delete_dms {
+        info!("Processing Direct Messages");
+        let dms = |c_api: &mut dyn TwitterAPI| c_api.direct_messages_next_page();
+        summary.merge(&process_dms(settings, api, dms)?);
+    }
+
+    info!("Processed all timelines: {}", summary);
+    Ok(summary)
+}
+
+/// Erases the tweets with the given ids, whatever their age or the filters in `settings`. Each
+/// tweet is retrieved first, so we know if the user liked, retweeted or posted it. Tweets we can't
+/// retrieve, like those already deleted, are logged and counted as failed without stopping. If
+/// given, `action` is applied to each tweet instead of the default unlike, unretweet and erase, and
+/// `observer` is told the outcome of each tweet processed by the default.
+///
+/// # Impure
+///
+/// - Multiple requests to Twitter API
+///
+/// # Errors
+///
+/// - Errors while erasing the tweets
+fn erase_tweets_by_id(
+    api: &mut dyn TwitterAPI,
+    ids: &[u64],
+    settings: &Settings,
+    mut action: Option<&mut TweetAction>,
+    mut observer: Option<&mut TweetObserver>,
+) -> Result<RunSummary, Errors> {
+    let cancellation = &settings.cancellation;
+    let user_id = api.user_id();
+    let api_calls_before = api.api_calls();
+    let mut recording_api = RecordingAPI::new(api);
+    for id in ids {
+        if cancellation.is_cancelled() {
+            warn!("Stopping erasure of listed tweets as requested");
+            break;
+        }
+        if settings.kept_ids.contains(id) {
+            info!("Skipping tweet #{} as it is listed to keep", id);
+            recording_api.summary.skipped += 1;
+            continue;
+        }
+        let tweet = match recording_api.get_tweet(*id) {
+            Ok(tweet) => tweet,
+            Err(e) => {
+                warn!("Skipping listed tweet: {}", e);
+                recording_api.summary.failed += 1;
+                continue;
+            }
+        };
+        let result = maintenance_action(
+            &mut recording_api,
+            &tweet,
+            action.as_deref_mut(),
+            observer.as_deref_mut(),
+            settings,
+            user_id,
+        );
+        tolerate_failure(result, settings)?;
+    }
+
+    let api_calls = recording_api.api_calls() - api_calls_before;
+    let mut summary = recording_api.summary;
+    summary.api_calls = api_calls;
+    summary.cancelled = cancellation.is_cancelled();
+    info!("Processed listed tweets: {}", summary);
+    Ok(summary)
+}
+
+/// Returns the function that retrieves the next page of the given timeline
+fn timeline_pages(
+    timeline: TimelineKind,
+) -> fn(&mut dyn TwitterAPI) -> Result<Vec<Tweet>, APIError> {
+    match timeline {
+        TimelineKind::User => |c_api| c_api.user_timeline_next_page(),
+        TimelineKind::Likes => |c_api| c_api.likes_timeline_next_page(),
+    }
+}
+
+/// Same as processing each timeline with `clear_timeline`, but the tweets selected in all the
+/// timelines are erased together, from the oldest to the newest, until `max_deletions` tweets are
+/// deleted. We fetch the whole timelines before erasing anything.
+///
+/// # Impure
+///
+/// - Multiple requests to Twitter API
+///
+/// # Errors
+///
+/// - Errors while retrieving the timelines or erasing the tweets
+fn clear_timelines_oldest_first(
+    api: &mut dyn TwitterAPI,
+    settings: &Settings,
+    mut action: Option<&mut TweetAction>,
+    mut observer: Option<&mut TweetObserver>,
+    progress: &Progress,
+) -> Result<RunSummary, Errors> {
+    let mut summary = RunSummary::default();
+    let mut selected: Vec<Tweet> = Vec::new();
+    for timeline in &settings.timelines {
+        info!("Collecting tweets to erase from {}", timeline);
+        let mut timeline_settings = settings.for_timeline(*timeline);
+        timeline_settings.max_deletions = None;
+        let mut collect = |_: &mut dyn TwitterAPI, tweet: &Tweet| {
+            selected.push(tweet.clone());
+            Ok(())
+        };
+        summary.merge(&clear_timeline(
+            &timeline.to_string(),
+            &timeline_settings,
+            api,
+            timeline_pages(*timeline),
+            Some(&mut collect),
+            None,
+            progress,
+        )?);
+    }
+
+    // a tweet we posted and liked is in both timelines, we only need to process it once
+    selected.sort_by_key(|tweet| (tweet.created_at, tweet.id));
+    selected.dedup_by_key(|tweet| tweet.id);
+    info!(
+        "Erasing {} tweets from the oldest to the newest",
+        selected.len()
+    );
+
+    let cancellation = &settings.cancellation;
+    let user_id = api.user_id();
+    let api_calls_before = api.api_calls();
+    let mut recording_api = RecordingAPI::with_progress(api, progress);
+    for tweet in &selected {
+        if cancellation.is_cancelled() {
+            break;
+        }
+        if max_deletions_reached(settings, progress) {
+            summary.stopped_early = true;
+            break;
+        }
+        let result = maintenance_action(
+            &mut recording_api,
+            tweet,
+            action.as_deref_mut(),
+            observer.as_deref_mut(),
+            settings,
+            user_id,
+        );
+        tolerate_failure(result, settings)?;
+    }
+
+    let api_calls = recording_api.api_calls() - api_calls_before;
+    summary.merge(&recording_api.summary);
+    summary.api_calls += api_calls;
+    summary.cancelled = cancellation.is_cancelled();
+    Ok(summary)
+}
+
+/// Erases the tweets of a timeline that are older than `preserve_days` (or that the filters force
+/// to erase regardless of age) and allowed by the filters in `settings`. Tweets are erased one by one, unless `concurrency` is more than 1, in which case
+/// each page is erased in batches. If given, `action` is applied to each selected tweet, one by one,
+/// instead of the default unlike, unretweet and erase. The tweets retrieved and deleted are counted in `progress`.
+///
+/// If `preserve_active_threads` is set, the whole timeline is fetched before erasing anything, and
+/// tweets in a thread with some tweet that can't be erased yet are preserved. Likewise with
+/// `preserve_self_threads`, for threads of replies of the user to their own tweets where some tweet
+/// can't be erased yet or is preserved by the filters, the pinned tweet or `kept_ids`.
+///
+/// The `cancellation` flag in `settings` is checked before each tweet (or batch) and page. Once set,
+/// we stop as if we had reached the end of the timeline and return what we processed so far.
+///
+/// # Impure
+///
+/// - Multiple requests to Twitter API
+///
+/// # Errors
+///
+/// - Errors while removing elements from the timelines
+/// - Other errors when interacting with Twitter API
+fn clear_timeline<F>(
+    name: &str,
+    settings: &Settings,
+    api: &mut dyn TwitterAPI,
+    mut tl_iterator: F,
+    mut action: Option<&mut TweetAction>,
+    mut observer: Option<&mut TweetObserver>,
+    progress: &Progress,
+) -> Result<RunSummary, Errors>
+where
+    F: FnMut(&mut dyn TwitterAPI) -> Result<Vec<Tweet>, APIError>,
+{
+    let window = settings.erasure_window();
+    let minimum_age_days = settings.minimum_age_days;
+    // tweets older than this were already old enough when the last run went through them. With a
+    // retention longer than the dates we can represent, no tweet was old enough back then
+    let previous_cutoff = match window {
+        _ if settings.delete_all => None,
+        DateWindow::OlderThan(preserve) => settings
+            .last_run
+            .and_then(|last_run| last_run.checked_sub_signed(preserve)),
+        DateWindow::Between(..) => None,
+    };
+    let handled_by_last_run =
+        |tweet: &Tweet| previous_cutoff.is_some_and(|cutoff| tweet.created_at < cutoff);
+    let is_old = |tweet: &Tweet| {
+        settings.delete_all
+            || (window.contains(tweet.created_at, minimum_age_days) && !handled_by_last_run(tweet))
+    };
+
+    let filters = &settings.filters;
+    let pinned_tweet_id = if settings.preserve_pinned {
+        api.pinned_tweet_id()
+    } else {
+        None
+    };
+    let user_id = api.user_id();
+    // a tweet the rules keep makes its whole self-thread stay
+    let is_protected = |tweet: &Tweet| {
+        settings.kept_ids.contains(&tweet.id)
+            || pinned_tweet_id == Some(tweet.id)
+            || !filters.allows(tweet)
+    };
+
+    // to preserve active threads we need to see all their tweets before erasing any of them, so
+    // we fetch the whole timeline first and then serve the pages from memory
+    let active_threads = RefCell::new(HashSet::new());
+    let mut buffered_pages: Option<VecDeque<Vec<Tweet>>> = None;
+    let mut next_page = |c_api: &mut dyn TwitterAPI| -> Result<Vec<Tweet>, APIError> {
+        if !settings.preserve_active_threads && !settings.preserve_self_threads {
+            return tl_iterator(c_api);
+        }
+        if buffered_pages.is_none() {
+            let mut pages = VecDeque::new();
+            loop {
+                let page = tl_iterator(c_api)?;
+                if page.is_empty() {
+                    break;
+                }
+                pages.push_back(page);
+            }
+            let mut preserved_ids = HashSet::new();
+            if settings.preserve_active_threads {
+                preserved_ids.extend(threads::active_thread_ids(pages.iter().flatten(), is_old));
+            }
+            if settings.preserve_self_threads {
+                // only linking the tweets of the user, replies to other users start a new thread
+                let own = pages.iter().flatten().filter(|t| is_posted_by(t, user_id));
+                preserved_ids.extend(threads::active_thread_ids(own, |t| {
+                    is_old(t) && !is_protected(t)
+                }));
+            }
+            *active_threads.borrow_mut() = preserved_ids;
+            buffered_pages = Some(pages);
+        }
+        Ok(buffered_pages
+            .as_mut()
+            .and_then(|pages| pages.pop_front())
+            .unwrap_or_default())
+    };
+
+    let cancellation = &settings.cancellation;
+    let empty_pages = Cell::new(0);
+    let scanned = Cell::new(0);
+    let reached_last_run = Cell::new(false);
+    let stopped_early = Cell::new(false);
+    let latest_tweets = RefCell::new(HashSet::new());
+    let cancellable_tl = |c_api: &mut dyn TwitterAPI| {
+        if cancellation.is_cancelled() {
+            warn!("Stopping processing of {} timeline as requested", name);
+            return Ok(Vec::new());
+        }
+        if reached_last_run.get() {
+            info!(
+                "Stopping processing of {} timeline as the last run handled older tweets",
+                name
+            );
+            return Ok(Vec::new());
+        }
+        if max_deletions_reached(settings, progress) {
+            info!(
+                "Stopping processing of {} timeline as we reached the maximum deletions",
+                name
+            );
+            stopped_early.set(true);
+            return Ok(Vec::new());
+        }
+        let page = next_page(c_api)?;
+        progress.record(page.len());
+        scanned.set(scanned.get() + page.len());
+
+        // newest tweets come first, so the pages after one reaching the last run are all older
+        if !is_oldest_first(&page) && page.last().is_some_and(&handled_by_last_run) {
+            reached_last_run.set(true);
+        }
+
+        // timelines return the newest tweets first, so the latest tweets are the first we retrieve
+        let mut latest = latest_tweets.borrow_mut();
+        let missing = settings.keep_latest.saturating_sub(latest.len());
+        latest.extend(page.iter().take(missing).map(|t| t.id));
+
+        // if the oldest tweets come first, once we find pages without old tweets all the pages that
+        // follow are newer, so there is nothing left to erase
+        if let Some(max_empty_pages) = settings.max_empty_pages {
+            if is_oldest_first(&page) && !page.iter().any(&is_old) {
+                empty_pages.set(empty_pages.get() + 1);
+            } else {
+                empty_pages.set(0);
+            }
+            if empty_pages.get() > max_empty_pages {
+                info!(
+                    "Stopping processing of {} timeline after {} pages without old tweets",
+                    name, max_empty_pages
+                );
+                stopped_early.set(true);
+                return Ok(Vec::new());
+            }
+        }
+        Ok(page)
+    };
+
+    let preserved = Cell::new(0);
+    let allowed = |tweet: &Tweet| {
+        if settings.kept_ids.contains(&tweet.id) {
+            info!("Preserving tweet #{} as it is listed to keep", tweet.id);
+            preserved.set(preserved.get() + 1);
+            return false;
+        }
+        if settings.skipped_ids.contains(&tweet.id) {
+            info!("Skipping tweet #{} as a previous run handled it", tweet.id);
+            preserved.set(preserved.get() + 1);
+            return false;
+        }
+        if settings.keep_self_likes && is_posted_by(tweet, user_id) {
+            info!("Preserving tweet #{} as it is a self-like", tweet.id);
+            preserved.set(preserved.get() + 1);
+            return false;
+        }
+        let allowed = filters.allows(tweet) && pinned_tweet_id != Some(tweet.id);
+        if !allowed {
+            info!("Preserving tweet #{} as per configured filters", tweet.id);
+            preserved.set(preserved.get() + 1);
+        }
+        allowed
+    };
+
+    // counting replies takes a request, so we only do it for tweets the rest of rules let us erase
+    let has_many_replies = |c_api: &mut dyn TwitterAPI, tweet: &Tweet| {
+        let min_replies = match settings.min_replies {
+            Some(min_replies) => min_replies,
+            None => return false,
+        };
+        let many = match c_api.reply_count(tweet) {
+            Ok(replies) => replies >= min_replies,
+            Err(e) => {
+                warn!(
+                    "Preserving tweet #{} as we couldn't count its replies: {}",
+                    tweet.id, e
+                );
+                true
+            }
+        };
+        if many {
+            info!("Preserving tweet #{} as it has many replies", tweet.id);
+            preserved.set(preserved.get() + 1);
+        }
+        many
+    };
+
+    let is_candidate = |tweet: &Tweet| {
+        let forced = filters.forces_erasure(tweet)
+            && DateWindow::OlderThan(Duration::zero()).contains(tweet.created_at, minimum_age_days);
+        let active_thread = active_threads.borrow().contains(&tweet.id);
+        if active_thread {
+            info!(
+                "Preserving tweet #{} as its thread has recent or preserved tweets",
+                tweet.id
+            );
+        }
+        let latest = latest_tweets.borrow().contains(&tweet.id);
+        if latest {
+            info!("Preserving tweet #{} as one of the latest tweets", tweet.id);
+        }
+        (forced || is_old(tweet)) && !active_thread && !latest
+    };
+
+    let api_calls_before = api.api_calls();
+    let mut recording_api = RecordingAPI::with_progress(api, progress);
+    // batches unlike, unretweet and erase in the default order, so a custom order, keeping likes,
+    // checking who posted each tweet or ignoring some errors means erasing tweets one at a time
+    let batchable = settings.action_order == ActionOrder::default()
+        && !settings.keep_likes
+        && !settings.check_ownership
+        && !settings.ignore_unlike_errors
+        && !settings.ignore_unretweet_errors;
+    if settings.concurrency > 1 && action.is_none() && !batchable {
+        info!(
+            "Processing {} one tweet at a time to follow the configured actions",
+            name
+        );
+    }
+    if settings.concurrency > 1 && action.is_none() && batchable {
+        let concurrency = settings.concurrency;
+        let batch_action = |c_api: &mut dyn TwitterAPI, tweets: Vec<&Tweet>| {
+            if cancellation.is_cancelled() {
+                return Ok(());
+            }
+            let mut tweets: Vec<&Tweet> = tweets
+                .into_iter()
+                .filter(|t| allowed(t) && !has_many_replies(c_api, t))
+                .collect();
+            if let Some(max_deletions) = settings.max_deletions {
+                tweets.truncate(max_deletions.saturating_sub(progress.deleted()));
+            }
+            let result = concurrent_maintenance_action(
+                c_api,
+                &tweets,
+                concurrency,
+                observer.as_deref_mut(),
+                settings.timezone,
+            );
+            tolerate_failure(result, settings)
+        };
+        process_pages(
+            name,
+            &is_candidate,
+            &mut recording_api,
+            cancellable_tl,
+            batch_action,
+        )?;
+    } else {
+        let filtered_action = |c_api: &mut dyn TwitterAPI, tweet: &Tweet| {
+            if cancellation.is_cancelled()
+                || max_deletions_reached(settings, progress)
+                || !allowed(tweet)
+                || has_many_replies(c_api, tweet)
+            {
+                Ok(())
+            } else {
+                let result = maintenance_action(
+                    c_api,
+                    tweet,
+                    action.as_deref_mut(),
+                    observer.as_deref_mut(),
+                    settings,
+                    user_id,
+                );
+                tolerate_failure(result, settings)
+            }
+        };
+        process_timeline(
+            name,
+            &is_candidate,
+            &mut recording_api,
+            cancellable_tl,
+            filtered_action,
+        )?;
+    }
+
+    let api_calls = recording_api.api_calls() - api_calls_before;
+    let mut summary = recording_api.summary;
+    summary.api_calls = api_calls;
+    summary.scanned = scanned.get();
+    summary.skipped += preserved.get();
+    summary.cancelled = cancellation.is_cancelled();
+    summary.stopped_early = stopped_early.get();
+    Ok(summary)
+}
+
+/// Erases the direct messages older than `preserve_dm_days` in `settings`, requesting pages with
+/// `dm_iterator` until it returns an empty page. Like `clear_timeline`, it stops early if the
+/// `cancellation` flag in `settings` is set.
+///
+/// # Impure
+///
+/// - Multiple requests to Twitter API
+///
+/// # Errors
+///
+/// - Errors while retrieving or erasing direct messages
+fn process_dms<F>(
+    settings: &Settings,
+    api: &mut dyn TwitterAPI,
+    mut dm_iterator: F,
+) -> Result<RunSummary, Errors>
+where
+    F: FnMut(&mut dyn TwitterAPI) -> Result<Vec<DirectMessage>, APIError>,
+{
+    let cancellation = &settings.cancellation;
+    let is_candidate = |dm: &&DirectMessage| {
+        DateWindow::OlderThan(settings.preserve_dm_days)
+            .contains(dm.created_at, settings.minimum_age_days)
+    };
+
+    let api_calls_before = api.api_calls();
+    let mut recording_api = RecordingAPI::new(api);
+    while !cancellation.is_cancelled() {
+        let page = dm_iterator(&mut recording_api).map_err(Errors::APIErrors)?;
+        if page.is_empty() {
+            break;
+        }
+        for dm in page.iter().filter(is_candidate) {
+            if cancellation.is_cancelled() {
+                warn!("Stopping processing of direct messages as requested");
+                break;
+            }
+            recording_api.erase_dm(dm).map_err(Errors::APIErrors)?;
+            info!("Erased direct message #{}", dm.id);
+        }
+    }
+
+    let api_calls = recording_api.api_calls() - api_calls_before;
+    let mut summary = recording_api.summary;
+    summary.api_calls = api_calls;
+    summary.cancelled = cancellation.is_cancelled();
+    Ok(summary)
 }
 
 /// Given a function that returns a `Vector` of `Tweet`, it keeps calling the function and operation over
 /// the elements returned until it reaches the end or an error is raised.
 ///
-/// The default operation is that for any item older in days than the provided `preserve_days`, it will erase that element from the timeline.
+/// The operation is only called for the items for which `is_candidate` returns true. By default that
+/// is any item older in days than the configured `preserve_days`, which the operation erases from the timeline.
 ///
 /// # Impure
 ///
@@ -108,171 +1026,1763 @@ fn clear_user_timelines(api: &mut dyn TwitterAPI, preserve_days: i64) -> Result<
 ///
 /// - Errors while removing elements from the timelines
 /// - Other errors when interacting with Twitter API
-fn process_timeline<'a, F, G>(
+fn process_timeline<'a, F, G, P>(
     name: &str,
-    preserve_days: i64,
+    is_candidate: &P,
     api: &mut dyn TwitterAPI,
-    mut tl_iterator: F,
+    tl_iterator: F,
     mut action: G,
 ) -> Result<(), Errors>
 where
     F: FnMut(&mut dyn TwitterAPI) -> Result<Vec<Tweet>, APIError>,
     G: FnMut(&mut dyn TwitterAPI, &Tweet) -> Result<(), Errors> + 'a,
+    P: Fn(&Tweet) -> bool,
 {
-    let feed = tl_iterator(api).map_err(Errors::APIErrors)?;
-
-    if feed.is_empty() {
-        info!("We got to the end of the {} timeline", name);
+    let page_action = |c_api: &mut dyn TwitterAPI, tweets: Vec<&Tweet>| {
+        for tweet in tweets {
+            action(c_api, tweet)?;
+        }
         Ok(())
-    } else {
+    };
+    process_pages(name, is_candidate, api, tl_iterator, page_action)
+}
+
+/// Same as `process_timeline`, but the operation receives at once all the tweets of a page for which
+/// `is_candidate` returns true
+fn process_pages<'a, F, G, P>(
+    name: &str,
+    is_candidate: &P,
+    api: &mut dyn TwitterAPI,
+    mut tl_iterator: F,
+    mut page_action: G,
+) -> Result<(), Errors>
+where
+    F: FnMut(&mut dyn TwitterAPI) -> Result<Vec<Tweet>, APIError>,
+    G: FnMut(&mut dyn TwitterAPI, Vec<&Tweet>) -> Result<(), Errors> + 'a,
+    P: Fn(&Tweet) -> bool,
+{
+    // a loop rather than a call per page, so the stack doesn't grow with the size of the timeline
+    loop {
+        let feed = tl_iterator(api).map_err(Errors::APIErrors)?;
+
+        if feed.is_empty() {
+            info!("We got to the end of the {} timeline", name);
+            return Ok(());
+        }
+
         info!("Processing next page of {} timeline", name);
-        for tweet in &feed {
-            if is_erasable(tweet.created_at, preserve_days) {
-                action(api, tweet)?;
-            }
+        let erasable = feed.iter().filter(|tweet| is_candidate(tweet)).collect();
+        page_action(api, erasable)?;
+    }
+}
+
+/// Erases a batch of tweets, with up to `concurrency` tweets being erased at the same time.
+/// All the tweets in the batch are processed, and then the first error found (if any) is returned.
+fn concurrent_maintenance_action(
+    api: &mut dyn TwitterAPI,
+    tweets: &[&Tweet],
+    concurrency: usize,
+    mut observer: Option<&mut TweetObserver>,
+    timezone: Tz,
+) -> Result<(), Errors> {
+    for tweet in tweets {
+        log_erasure(tweet, timezone);
+    }
+
+    let mut first_error = None;
+    for (tweet, result) in tweets.iter().zip(api.maintain_tweets(tweets, concurrency)) {
+        if let Some(observer) = observer.as_deref_mut() {
+            let tweet_result = result
+                .clone()
+                .map_err(|e| Errors::APIErrors(e.for_tweet(tweet.id)));
+            observer(tweet, outcome_of(&tweet_result));
+        }
+        match result {
+            Ok(EraseOutcome::Deleted) => info!("Erased tweet #{}", tweet.id),
+            Ok(EraseOutcome::Skipped(reason)) => {
+                info!("Skipped erasing tweet #{}: {}", tweet.id, reason)
+            }
+            Err(e) => {
+                error!("Failed to erase tweet #{}: {}", tweet.id, e);
+                first_error.get_or_insert(Errors::APIErrors(e));
+            }
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Applies `action` to the tweet if given, or the default unlike, unretweet and erase otherwise,
+/// telling `observer` the outcome of the latter
+fn maintenance_action(
+    api: &mut dyn TwitterAPI,
+    tweet: &Tweet,
+    action: Option<&mut TweetAction>,
+    observer: Option<&mut TweetObserver>,
+    settings: &Settings,
+    user_id: Option<u64>,
+) -> Result<(), Errors> {
+    if let Some(action) = action {
+        return action(api, tweet);
+    }
+
+    log_erasure(tweet, settings.timezone);
+    let result = if settings.check_ownership {
+        ownership_aware_maintenance_action(api, tweet, user_id, settings)
+    } else {
+        default_maintenance_action(api, tweet, settings)
+    };
+    if let Some(observer) = observer {
+        observer(tweet, outcome_of(&result));
+    }
+    result.map(|_| ())
+}
+
+/// Logs the failure to process a tweet and returns Ok if `settings` says to continue on errors, so
+/// the rest of tweets are still processed. The failure is still counted in the summary
+fn tolerate_failure(result: Result<(), Errors>, settings: &Settings) -> Result<(), Errors> {
+    match result {
+        Err(Errors::APIErrors(e)) if settings.continue_on_error => {
+            warn!("Continuing after a failure: {}", e);
+            Ok(())
+        }
+        other => other,
+    }
+}
+
+/// Describes the result of erasing a tweet for a `TweetObserver`
+fn outcome_of(result: &Result<EraseOutcome, Errors>) -> Outcome {
+    match result {
+        Ok(EraseOutcome::Deleted) => Outcome::Deleted,
+        Ok(EraseOutcome::Skipped(reason)) => Outcome::Skipped(reason.clone()),
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+/// Logs the tweet we are about to erase, with its date in the given time zone
+fn log_erasure(tweet: &Tweet, timezone: Tz) {
+    warn!(
+        "Erasing tweet created at: [{}] - F:{}|RT:{} -- {}",
+        tweet.created_at.with_timezone(&timezone),
+        tweet.favorited.unwrap_or(false),
+        tweet.retweeted.unwrap_or(false),
+        tweet.text
+    );
+}
+
+/// Returns true if we already deleted the maximum number of tweets set in `settings`
+fn max_deletions_reached(settings: &Settings, progress: &Progress) -> bool {
+    settings
+        .max_deletions
+        .is_some_and(|max_deletions| progress.deleted() >= max_deletions)
+}
+
+/// Unlikes, unretweets and erases the tweet, as needed, in the order set in `settings`. If
+/// `settings` says to keep likes, tweets we liked are only unretweeted, as erasing them would also
+/// remove the like
+fn default_maintenance_action(
+    api: &mut dyn TwitterAPI,
+    tweet: &Tweet,
+    settings: &Settings,
+) -> Result<EraseOutcome, Errors> {
+    if settings.keep_likes && tweet.favorited.unwrap_or(false) {
+        info!("Tweet #{} is liked, undoing retweet only", tweet.id);
+        undo(api, tweet, SubAction::Unretweet, settings)?;
+        return Ok(EraseOutcome::Skipped(String::from(
+            "liked, only unretweeted to keep the like",
+        )));
+    }
+
+    let tweet_error = |e: APIError| Errors::APIErrors(e.for_tweet(tweet.id));
+    let mut outcome = EraseOutcome::Deleted;
+    for step in settings.action_order.steps() {
+        match step {
+            SubAction::Erase => outcome = api.erase_tweet(tweet).map_err(tweet_error)?,
+            engagement => undo(api, tweet, *engagement, settings)?,
+        }
+    }
+    match &outcome {
+        EraseOutcome::Deleted => info!("Erased tweet #{}", tweet.id),
+        EraseOutcome::Skipped(reason) => info!("Skipped erasing tweet #{}: {}", tweet.id, reason),
+    }
+    Ok(outcome)
+}
+
+/// Returns true if the first tweet of the page is older than the last one. Timelines from Twitter API
+/// return the newest tweets first, so this is false for them
+fn is_oldest_first(page: &[Tweet]) -> bool {
+    match (page.first(), page.last()) {
+        (Some(first), Some(last)) => first.created_at < last.created_at,
+        _ => false,
+    }
+}
+
+/// Returns true if we know the tweet was posted by `user_id`
+fn is_posted_by(tweet: &Tweet, user_id: Option<u64>) -> bool {
+    match (&tweet.user, user_id) {
+        (Some(author), Some(user_id)) => author.id == user_id,
+        _ => false,
+    }
+}
+
+/// Like `default_maintenance_action`, but checks who posted the tweet first: tweets posted by
+/// someone other than `user_id` are only unliked and unretweeted, without trying to erase them.
+/// Tweets are treated as posted by the user if we don't know the author or the user
+fn ownership_aware_maintenance_action(
+    api: &mut dyn TwitterAPI,
+    tweet: &Tweet,
+    user_id: Option<u64>,
+    settings: &Settings,
+) -> Result<EraseOutcome, Errors> {
+    let posted_by_other =
+        tweet.user.is_some() && user_id.is_some() && !is_posted_by(tweet, user_id);
+    if !posted_by_other {
+        return default_maintenance_action(api, tweet, settings);
+    }
+
+    info!(
+        "Tweet #{} posted by another user, undoing like and retweet only",
+        tweet.id
+    );
+    undo_engagement(api, tweet, settings)?;
+    Ok(EraseOutcome::Skipped(String::from(
+        "posted by another user, only unliked and unretweeted",
+    )))
+}
+
+/// Unlikes and unretweets the tweet, as needed, in the order set in `settings`
+fn undo_engagement(
+    api: &mut dyn TwitterAPI,
+    tweet: &Tweet,
+    settings: &Settings,
+) -> Result<(), Errors> {
+    for step in settings.action_order.steps() {
+        if *step != SubAction::Erase {
+            undo(api, tweet, *step, settings)?;
+        }
+    }
+    Ok(())
+}
+
+/// Sends the unlike or unretweet `step` if we liked or retweeted the tweet. Failures are returned,
+/// unless `settings` says to ignore them, in which case they are only logged
+fn undo(
+    api: &mut dyn TwitterAPI,
+    tweet: &Tweet,
+    step: SubAction,
+    settings: &Settings,
+) -> Result<(), Errors> {
+    let (result, request, ignore_errors) = match step {
+        SubAction::Unlike if tweet.favorited.unwrap_or(false) && !settings.keep_likes => (
+            api.unlike_tweet(tweet),
+            "unlike",
+            settings.ignore_unlike_errors,
+        ),
+        SubAction::Unretweet if tweet.retweeted.unwrap_or(false) => (
+            api.unretweet_tweet(tweet),
+            "unretweet",
+            settings.ignore_unretweet_errors,
+        ),
+        _ => return Ok(()),
+    };
+    match result {
+        Err(e) if ignore_errors => {
+            warn!("Ignoring failed {} of tweet #{}: {}", request, tweet.id, e);
+            Ok(())
+        }
+        other => other.map_err(|e| Errors::APIErrors(e.for_tweet(tweet.id))),
+    }
+}
+
+#[cfg(test)]
+#[macro_use]
+extern crate pretty_assertions;
+
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
+
+#[cfg(test)]
+mod tests {
+    use crate::config::tests::{lookup_from, required_values};
+    use crate::EnvValues;
+    use chrono::prelude::*;
+    use egg_mode::tweet::{Tweet, TweetEntities, TweetSource};
+    use egg_mode::user::{TwitterUser, UserEntities, UserEntityDetail};
+
+    pub fn sample_tweet(days_ago: i64) -> Tweet {
+        let now = Utc::now().timestamp();
+        let seconds_past = days_ago * 24 * 60 * 60;
+        let dt = NaiveDateTime::from_timestamp(now - seconds_past, 0);
+        let date = DateTime::from_utc(dt, Utc);
+        Tweet {
+            coordinates: None,
+            created_at: date,
+            current_user_retweet: None,
+            display_text_range: None,
+            entities: TweetEntities {
+                hashtags: Vec::new(),
+                symbols: Vec::new(),
+                urls: Vec::new(),
+                user_mentions: Vec::new(),
+                media: None,
+            },
+            extended_entities: None,
+            favorite_count: 20,
+            favorited: None,
+            filter_level: None,
+            id: 1,
+            in_reply_to_user_id: None,
+            in_reply_to_screen_name: None,
+            in_reply_to_status_id: None,
+            lang: Some(String::from("und")),
+            place: None,
+            possibly_sensitive: None,
+            quoted_status_id: None,
+            quoted_status: None,
+            retweet_count: 10,
+            retweeted: None,
+            retweeted_status: None,
+            source: TweetSource {
+                name: String::from("source name"),
+                url: String::from("source url"),
+            },
+            text: String::from("a sample tweet"),
+            truncated: false,
+            user: None,
+            withheld_copyright: false,
+            withheld_in_countries: None,
+            withheld_scope: None,
+        }
+    }
+
+    pub fn sample_user(id: u64) -> TwitterUser {
+        TwitterUser {
+            contributors_enabled: false,
+            created_at: Utc::now(),
+            default_profile: true,
+            default_profile_image: true,
+            description: None,
+            entities: UserEntities {
+                description: UserEntityDetail { urls: Vec::new() },
+                url: None,
+            },
+            favourites_count: 0,
+            follow_request_sent: None,
+            followers_count: 0,
+            friends_count: 0,
+            geo_enabled: false,
+            id,
+            is_translator: false,
+            lang: None,
+            listed_count: 0,
+            location: None,
+            name: String::from("user name"),
+            profile_background_color: String::from("FFFFFF"),
+            profile_background_image_url: None,
+            profile_background_image_url_https: None,
+            profile_background_tile: None,
+            profile_banner_url: None,
+            profile_image_url: String::from("image url"),
+            profile_image_url_https: String::from("image url"),
+            profile_link_color: String::from("000000"),
+            profile_sidebar_border_color: String::from("000000"),
+            profile_sidebar_fill_color: String::from("000000"),
+            profile_text_color: String::from("000000"),
+            profile_use_background_image: false,
+            protected: false,
+            screen_name: String::from("handle"),
+            show_all_inline_media: None,
+            status: None,
+            statuses_count: 0,
+            time_zone: None,
+            url: None,
+            utc_offset: None,
+            verified: false,
+            withheld_in_countries: None,
+            withheld_scope: None,
+        }
+    }
+
+    /// Builds a valid `EnvValues` with the given extra values on top of the required ones
+    pub fn sample_env(extra: Vec<(&str, &str)>) -> EnvValues {
+        let mut values = required_values();
+        values.extend(extra);
+        EnvValues::load_from(lookup_from(values)).unwrap()
+    }
+
+    mod clear_old_tweets_with {
+        use super::{sample_env, sample_tweet};
+        use crate::api::{APIError, TestAPI};
+        use crate::clear_old_tweets_with;
+        use crate::{Errors, RunSummary};
+
+        #[test]
+        fn refuses_to_run_without_confirmation() {
+            let mut api_built = false;
+            let result = clear_old_tweets_with(sample_env(vec![]), |_env| {
+                api_built = true;
+                Ok(TestAPI {
+                    ..Default::default()
+                })
+            });
+
+            match result {
+                Err(Errors::LibErrors(msg)) => assert!(msg.contains("TP_CONFIRM_DELETE")),
+                other => panic!("Unexpected result {:?}", other),
+            }
+            assert_eq!(api_built, false);
+        }
+
+        #[test]
+        fn refuses_to_delete_all_without_acknowledgement() {
+            let env = sample_env(vec![
+                ("TP_CONFIRM_DELETE", "yes-delete-my-tweets"),
+                ("TP_DELETE_ALL", "true"),
+            ]);
+            let mut api_built = false;
+            let result = clear_old_tweets_with(env, |_env| {
+                api_built = true;
+                Ok(TestAPI {
+                    ..Default::default()
+                })
+            });
+
+            match result {
+                Err(Errors::LibErrors(msg)) => {
+                    assert!(msg.contains("TP_DELETE_ALL_ACKNOWLEDGED"))
+                }
+                other => panic!("Unexpected result {:?}", other),
+            }
+            assert_eq!(api_built, false);
+        }
+
+        #[test]
+        fn deletes_recent_tweets_if_delete_all_is_acknowledged() {
+            let env = sample_env(vec![
+                ("TP_CONFIRM_DELETE", "yes-delete-my-tweets"),
+                ("TP_DELETE_ALL", "true"),
+                ("TP_DELETE_ALL_ACKNOWLEDGED", "yes-delete-all-my-tweets"),
+            ]);
+            let result = clear_old_tweets_with(env, |_env| {
+                Ok(TestAPI {
+                    user_timeline_pages: vec![Ok(vec![sample_tweet(0), sample_tweet(400)])]
+                        .into_iter()
+                        .collect(),
+                    ..Default::default()
+                })
+            });
+
+            assert_eq!(result.unwrap().deleted, 2);
+        }
+
+        #[test]
+        fn dry_run_needs_no_confirmation() {
+            let env = sample_env(vec![("TP_DRY_RUN", "true")]);
+            let result = clear_old_tweets_with(env, |_env| {
+                Ok(TestAPI {
+                    user_timeline_pages: vec![Ok(vec![sample_tweet(400)])].into_iter().collect(),
+                    erase_tweet_answer: Err(APIError::ErasureError(String::from("modified"))),
+                    ..Default::default()
+                })
+            });
+
+            // the erasure is only logged, so it succeeds
+            assert_eq!(result.map(|s| s.deleted), Ok(1));
+        }
+
+        #[test]
+        fn runs_when_confirmed() {
+            let env = sample_env(vec![("TP_CONFIRM_DELETE", "yes-delete-my-tweets")]);
+            let result = clear_old_tweets_with(env, |_env| {
+                Ok(TestAPI {
+                    ..Default::default()
+                })
+            });
+
+            // one request for each timeline, both empty
+            let empty = RunSummary {
+                api_calls: 1,
+                ..Default::default()
+            };
+            let mut expected = RunSummary::default();
+            expected.add_timeline("User Timeline", &empty);
+            expected.add_timeline("Likes Timeline", &empty);
+            let summary = result.unwrap();
+            assert!(summary.started_at <= summary.finished_at);
+            assert!(summary.started_at.is_some());
+            let counts = RunSummary {
+                started_at: None,
+                finished_at: None,
+                ..summary
+            };
+            assert_eq!(counts, expected);
+        }
+
+        #[test]
+        fn writes_summary_if_requested() {
+            let path = std::env::temp_dir()
+                .join(format!("twitter_privacy_run_{}.json", std::process::id()));
+            let path = path.to_str().unwrap();
+            let env = sample_env(vec![
+                ("TP_CONFIRM_DELETE", "yes-delete-my-tweets"),
+                ("TP_SUMMARY_PATH", path),
+            ]);
+            let summary = clear_old_tweets_with(env, |_env| {
+                Ok(TestAPI {
+                    ..Default::default()
+                })
+            })
+            .unwrap();
+
+            let written = RunSummary::read_from(path);
+            std::fs::remove_file(path).unwrap();
+            assert_eq!(written, Ok(summary));
+        }
+    }
+
+    mod check_credentials_with {
+        use super::sample_env;
+        use crate::api::{APIError, TestAPI};
+        use crate::{check_credentials_with, AccountInfo, Errors};
+
+        #[test]
+        fn returns_account_info() {
+            let env = sample_env(vec![]);
+            let mut api = TestAPI {
+                get_user_id_answer: Ok(42),
+                ..Default::default()
+            };
+
+            let expected = AccountInfo {
+                user_id: 42,
+                screen_name: String::from("handle"),
+                statuses_count: 0,
+            };
+            assert_eq!(check_credentials_with(&env, &mut api), Ok(expected));
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["validate_token", "get_account_info"]
+            );
+        }
+
+        #[test]
+        fn fails_on_invalid_token() {
+            let env = sample_env(vec![]);
+            let mut api = TestAPI {
+                validate_token_answer: Err(APIError::InvalidToken),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                check_credentials_with(&env, &mut api),
+                Err(Errors::APIErrors(APIError::InvalidToken))
+            );
+            assert_eq!(api.methods_called_in_order, vec!["validate_token"]);
+        }
+    }
+
+    mod clear_accounts_with {
+        use super::sample_env;
+        use crate::api::{APIError, TestAPI};
+        use crate::{clear_accounts_with, Errors};
+        use std::collections::VecDeque;
+
+        #[test]
+        fn processes_all_accounts_despite_failures() {
+            let confirm = ("TP_CONFIRM_DELETE", "yes-delete-my-tweets");
+            let mut first = sample_env(vec![confirm]);
+            first.user_handle = String::from("first");
+            let mut second = sample_env(vec![confirm]);
+            second.user_handle = String::from("second");
+            let mut third = sample_env(vec![confirm]);
+            third.user_handle = String::from("third");
+
+            let err = APIError::TimelineError(String::from("bad answer"));
+            let mut handles_built = Vec::new();
+            let summary = clear_accounts_with(vec![first, second, third], |env| {
+                handles_built.push(env.user_handle.clone());
+                if env.user_handle == "second" {
+                    Ok(TestAPI {
+                        user_timeline_pages: VecDeque::from(vec![Err(err.clone())]),
+                        ..Default::default()
+                    })
+                } else {
+                    Ok(TestAPI {
+                        ..Default::default()
+                    })
+                }
+            });
+
+            assert_eq!(handles_built, vec!["first", "second", "third"]);
+            let succeeded: Vec<&str> = summary.succeeded.iter().map(|s| s.0.as_str()).collect();
+            assert_eq!(succeeded, vec!["first", "third"]);
+            assert_eq!(
+                summary.failed,
+                vec![(String::from("second"), Errors::APIErrors(err))]
+            );
+        }
+    }
+
+    mod clear_user_timeline {
+        use super::sample_tweet;
+        use crate::api::{APIError, TestAPI};
+        use crate::clear_user_timelines;
+        use crate::Errors;
+        use crate::{Filters, RunSummary, Settings, TimelineKind, TimelineRules};
+        use egg_mode::tweet::Tweet;
+        use std::collections::VecDeque;
+
+        fn settings(preserve_days: i64) -> Settings {
+            Settings {
+                preserve_days: chrono::Duration::days(preserve_days),
+                ..Default::default()
+            }
+        }
+
+        fn tweet_with_id(id: u64, days_ago: i64) -> Tweet {
+            let mut tweet = sample_tweet(days_ago);
+            tweet.id = id;
+            tweet
+        }
+
+        #[test]
+        fn stops_after_max_deletions() {
+            let mut api = TestAPI {
+                user_timeline_pages: VecDeque::from(vec![
+                    Ok(vec![tweet_with_id(1, 20), tweet_with_id(2, 50)]),
+                    Ok(vec![tweet_with_id(3, 60)]),
+                ]),
+                likes_timeline_pages: VecDeque::from(vec![Ok(vec![tweet_with_id(4, 70)])]),
+                ..Default::default()
+            };
+            let settings = Settings {
+                max_deletions: Some(2),
+                ..settings(10)
+            };
+
+            let summary = clear_user_timelines(&mut api, &settings, None, None).unwrap();
+
+            assert_eq!(summary.deleted, 2);
+            assert_eq!(api.erased_tweet_ids, vec![1, 2]);
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["user_timeline_next_page", "erase_tweet", "erase_tweet"]
+            );
+        }
+
+        #[test]
+        fn erases_oldest_tweets_of_all_timelines_first() {
+            let mut api = TestAPI {
+                user_timeline_pages: VecDeque::from(vec![
+                    Ok(vec![tweet_with_id(1, 20), tweet_with_id(2, 50)]),
+                    Ok(vec![tweet_with_id(3, 80), tweet_with_id(4, 5)]),
+                ]),
+                likes_timeline_pages: VecDeque::from(vec![Ok(vec![
+                    tweet_with_id(5, 30),
+                    tweet_with_id(6, 60),
+                ])]),
+                ..Default::default()
+            };
+            let settings = Settings {
+                oldest_first: true,
+                max_deletions: Some(3),
+                ..settings(10)
+            };
+
+            let summary = clear_user_timelines(&mut api, &settings, None, None).unwrap();
+
+            assert_eq!(summary.deleted, 3);
+            assert_eq!(summary.api_calls, 8);
+            assert_eq!(api.erased_tweet_ids, vec![3, 6, 2]);
+            assert!(api.methods_called_in_order[..5]
+                .iter()
+                .all(|m| m.ends_with("_next_page")));
+        }
+
+        #[test]
+        fn erases_tweets_in_both_timelines_once_if_oldest_first() {
+            let mut api = TestAPI {
+                user_timeline_pages: VecDeque::from(vec![Ok(vec![tweet_with_id(1, 20)])]),
+                likes_timeline_pages: VecDeque::from(vec![Ok(vec![tweet_with_id(1, 20)])]),
+                ..Default::default()
+            };
+            let settings = Settings {
+                oldest_first: true,
+                ..settings(10)
+            };
+
+            let summary = clear_user_timelines(&mut api, &settings, None, None).unwrap();
+
+            assert_eq!(summary.deleted, 1);
+            assert_eq!(api.erased_tweet_ids, vec![1]);
+        }
+
+        #[test]
+        fn propagates_errors_from_user_tl() {
+            let err = APIError::TimelineError(String::from("bad answer"));
+            let mut api = TestAPI {
+                user_timeline_pages: VecDeque::from(vec![Err(err.clone())]),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                clear_user_timelines(&mut api, &settings(10), None, None),
+                Err(Errors::APIErrors(err))
+            )
+        }
+
+        #[test]
+        fn propagates_errors_from_likes_tl() {
+            let err = APIError::TimelineError(String::from("bad answer"));
+            let mut api = TestAPI {
+                likes_timeline_pages: VecDeque::from(vec![Err(err.clone())]),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                clear_user_timelines(&mut api, &settings(10), None, None),
+                Err(Errors::APIErrors(err))
+            )
+        }
+
+        #[test]
+        fn calls_expected_methods_in_api() {
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+
+            clear_user_timelines(&mut api, &settings(10), None, None).unwrap();
+
+            let expected_calls = vec!["user_timeline_next_page", "likes_timeline_next_page"];
+
+            assert_eq!(api.methods_called_in_order, expected_calls)
+        }
+
+        #[test]
+        fn processes_every_page_of_each_timeline() {
+            let mut liked = sample_tweet(20);
+            liked.favorited = Some(true);
+            let mut api = TestAPI {
+                user_timeline_pages: VecDeque::from(vec![
+                    Ok(vec![sample_tweet(20); 2]),
+                    Ok(vec![sample_tweet(1)]),
+                ]),
+                likes_timeline_pages: VecDeque::from(vec![Ok(vec![liked])]),
+                ..Default::default()
+            };
+
+            let summary = clear_user_timelines(&mut api, &settings(10), None, None).unwrap();
+
+            let expected_calls = vec![
+                "user_timeline_next_page",
+                "erase_tweet",
+                "erase_tweet",
+                "user_timeline_next_page",
+                "user_timeline_next_page",
+                "likes_timeline_next_page",
+                "unlike_tweet",
+                "erase_tweet",
+                "likes_timeline_next_page",
+            ];
+            assert_eq!(api.methods_called_in_order, expected_calls);
+            let user = RunSummary {
+                scanned: 3,
+                deleted: 2,
+                api_calls: 5,
+                ..Default::default()
+            };
+            let likes = RunSummary {
+                scanned: 1,
+                deleted: 1,
+                unliked: 1,
+                api_calls: 4,
+                ..Default::default()
+            };
+            let mut expected = RunSummary::default();
+            expected.add_timeline("User Timeline", &user);
+            expected.add_timeline("Likes Timeline", &likes);
+            assert_eq!(summary, expected);
+        }
+
+        #[test]
+        fn unlikes_and_unretweets_tweets_in_likes_timeline() {
+            let mut tweet = sample_tweet(20);
+            tweet.favorited = Some(true);
+            tweet.retweeted = Some(true);
+            let mut api = TestAPI {
+                likes_timeline_pages: VecDeque::from(vec![Ok(vec![tweet])]),
+                ..Default::default()
+            };
+            let settings = Settings {
+                timelines: vec![TimelineKind::Likes],
+                ..settings(10)
+            };
+
+            let summary = clear_user_timelines(&mut api, &settings, None, None).unwrap();
+
+            let expected_calls = vec![
+                "likes_timeline_next_page",
+                "unlike_tweet",
+                "unretweet_tweet",
+                "erase_tweet",
+                "likes_timeline_next_page",
+            ];
+            assert_eq!(api.methods_called_in_order, expected_calls);
+            assert_eq!((summary.unliked, summary.unretweeted), (1, 1));
+        }
+
+        #[test]
+        fn applies_the_rules_of_each_timeline() {
+            let mut api = TestAPI {
+                user_timeline_pages: VecDeque::from(vec![Ok(vec![sample_tweet(20); 2])]),
+                likes_timeline_pages: VecDeque::from(vec![Ok(vec![sample_tweet(20); 3])]),
+                ..Default::default()
+            };
+            let mut settings = settings(10);
+            settings.timeline_rules.insert(
+                TimelineKind::User,
+                TimelineRules {
+                    preserve_days: chrono::Duration::days(90),
+                    filters: Filters::default(),
+                },
+            );
+            settings.timeline_rules.insert(
+                TimelineKind::Likes,
+                TimelineRules {
+                    preserve_days: chrono::Duration::days(7),
+                    filters: Filters {
+                        preserve_sources: vec![String::from("other source")],
+                        ..Default::default()
+                    },
+                },
+            );
+
+            let summary = clear_user_timelines(&mut api, &settings, None, None).unwrap();
+
+            assert_eq!(summary.deleted, 3);
+            assert_eq!(summary.skipped, 0);
+        }
+
+        #[test]
+        fn processes_direct_messages_if_enabled() {
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let settings = Settings {
+                delete_dms: true,
+                ..Default::default()
+            };
+
+            clear_user_timelines(&mut api, &settings, None, None).unwrap();
+
+            let expected_calls = vec![
+                "user_timeline_next_page",
+                "likes_timeline_next_page",
+                "direct_messages_next_page",
+            ];
+
+            assert_eq!(api.methods_called_in_order, expected_calls)
+        }
+    }
+    mod clear_timeline {
+        use super::{sample_tweet, sample_user};
+        use crate::api::{APIError, TestAPI, TwitterAPI};
+        use crate::clear_timeline;
+        use crate::{Cancellation, DateWindow, Progress, RunSummary, Settings};
+        use chrono::Utc;
+        use egg_mode::tweet::Tweet;
+
+        fn erase_calls(api: &TestAPI) -> usize {
+            api.methods_called_in_order
+                .iter()
+                .filter(|m| *m == "erase_tweet")
+                .count()
+        }
+
+        quickcheck! {
+            fn erases_each_tweet_once_with_concurrency(pages: Vec<u8>, concurrency: u8) -> bool {
+                let settings = Settings {
+                    preserve_days: chrono::Duration::days(1),
+                    concurrency: usize::from(concurrency % 8) + 2,
+                    ..Default::default()
+                };
+                let mut api = TestAPI{..Default::default()};
+                let expected: usize = pages.iter().map(|p| usize::from(p % 10)).sum();
+                let mut pages: Vec<_> = pages
+                    .iter()
+                    .filter(|p| *p % 10 > 0)
+                    .map(|p| vec![sample_tweet(5); usize::from(p % 10)])
+                    .collect();
+
+                let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+                clear_timeline("name", &settings, &mut api, dataset, None, None, &Progress::default()).unwrap();
+
+                erase_calls(&api) == expected
+            }
+        }
+
+        #[test]
+        fn preserves_filtered_tweets_with_concurrency() {
+            let mut settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                concurrency: 4,
+                ..Default::default()
+            };
+            settings.filters.preserve_sources = vec![String::from("source name")];
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut pages = vec![vec![sample_tweet(5); 3]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(erase_calls(&api), 0);
+        }
+
+        #[test]
+        fn checks_ownership_with_concurrency() {
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                concurrency: 4,
+                check_ownership: true,
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                user_id_answer: Some(7),
+                ..Default::default()
+            };
+            let mut own = sample_tweet(5);
+            own.id = 1;
+            own.user = Some(Box::new(sample_user(7)));
+            let mut other = sample_tweet(5);
+            other.id = 2;
+            other.user = Some(Box::new(sample_user(8)));
+            other.favorited = Some(true);
+            let mut pages = vec![vec![own, other]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(api.erased_tweet_ids, vec![1]);
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["erase_tweet", "unlike_tweet"]
+            );
+        }
+
+        #[test]
+        fn ignores_unlike_errors_with_concurrency() {
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                concurrency: 4,
+                ignore_unlike_errors: true,
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                unlike_tweet_answer: Err(APIError::ErasureError(String::from("error"))),
+                ..Default::default()
+            };
+            let mut tweet = sample_tweet(5);
+            tweet.favorited = Some(true);
+            let mut pages = vec![vec![tweet]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["unlike_tweet", "erase_tweet"]
+            );
+            assert_eq!(summary.deleted, 1);
+        }
+
+        #[test]
+        fn counts_processed_tweets() {
+            let mut settings = Settings {
+                preserve_days: chrono::Duration::days(3),
+                ..Default::default()
+            };
+            settings.filters.preserve_sources = vec![String::from("other source")];
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut preserved = sample_tweet(5);
+            preserved.source.name = String::from("other source");
+            let mut liked = sample_tweet(5);
+            liked.favorited = Some(true);
+            let mut pages = vec![vec![sample_tweet(1), liked, preserved, sample_tweet(5)]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            let expected = RunSummary {
+                scanned: 4,
+                deleted: 2,
+                skipped: 1,
+                unliked: 1,
+                api_calls: 3,
+                ..Default::default()
+            };
+            assert_eq!(summary, expected);
+        }
+
+        #[test]
+        fn erases_withheld_tweets_regardless_of_age() {
+            let mut settings = Settings {
+                preserve_days: chrono::Duration::days(30),
+                ..Default::default()
+            };
+            settings.filters.delete_withheld = true;
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut withheld = sample_tweet(1);
+            withheld.withheld_in_countries = Some(vec![String::from("DE")]);
+            let mut pages = vec![vec![sample_tweet(1), withheld]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(erase_calls(&api), 1);
+        }
+
+        #[test]
+        fn erases_tweets_matching_delete_patterns_regardless_of_age() {
+            let mut settings = Settings {
+                preserve_days: chrono::Duration::days(30),
+                ..Default::default()
+            };
+            settings.filters.delete_patterns = "@old_handle".parse().unwrap();
+            settings.filters.preserve_patterns = "keep".parse().unwrap();
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut matching = sample_tweet(2);
+            matching.text = String::from("hi @old_handle");
+            let mut preserved = sample_tweet(2);
+            preserved.text = String::from("keep @old_handle");
+            let mut pages = vec![vec![sample_tweet(2), matching, preserved, sample_tweet(40)]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            // the old tweet and the recent one matching, as preserving patterns still apply
+            assert_eq!(erase_calls(&api), 2);
+        }
+
+        #[test]
+        fn continues_after_failures_if_enabled() {
+            let err = APIError::ErasureError(String::from("Unexpected error"));
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                continue_on_error: true,
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                erase_tweet_answer: Err(err.clone()),
+                ..Default::default()
+            };
+            let mut first = sample_tweet(5);
+            first.id = 1;
+            let mut second = sample_tweet(5);
+            second.id = 2;
+            let mut pages = vec![vec![first, second]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(erase_calls(&api), 2);
+            assert_eq!(summary.failed, 2);
+            let failed_ids: Vec<u64> = summary.failures.keys().cloned().collect();
+            assert_eq!(failed_ids, vec![1, 2]);
+            assert_eq!(summary.failures[&1], err.to_string());
+        }
+
+        #[test]
+        fn preserves_quote_tweets() {
+            let mut settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                ..Default::default()
+            };
+            settings.filters.preserve_quotes = true;
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut quote = sample_tweet(5);
+            quote.quoted_status_id = Some(2);
+            let mut pages = vec![vec![sample_tweet(5), quote]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(erase_calls(&api), 1);
+            assert_eq!(summary.deleted, 1);
+            assert_eq!(summary.skipped, 1);
+        }
+
+        #[test]
+        fn only_erases_old_tweets_without_engagement() {
+            let mut settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                ..Default::default()
+            };
+            settings.filters.delete_zero_engagement_only = true;
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut ignored = sample_tweet(5);
+            ignored.favorite_count = 0;
+            ignored.retweet_count = 0;
+            let mut ignored_recent = ignored.clone();
+            ignored_recent.created_at = chrono::Utc::now();
+            let engaged = sample_tweet(5);
+            let mut pages = vec![vec![ignored, engaged, ignored_recent]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(erase_calls(&api), 1);
+            assert_eq!(summary.deleted, 1);
+        }
+
+        #[test]
+        fn only_erases_tweets_in_range_ignoring_preserve_days() {
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                date_window: Some(DateWindow::Between(
+                    Utc::now() - chrono::Duration::days(20),
+                    Utc::now() - chrono::Duration::days(10),
+                )),
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut pages = vec![vec![sample_tweet(5), sample_tweet(15), sample_tweet(25)]];
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(erase_calls(&api), 1);
+            assert_eq!(summary.deleted, 1);
+        }
+
+        #[test]
+        fn applies_custom_action_instead_of_erasing() {
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                concurrency: 4,
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut pages = vec![vec![sample_tweet(5), sample_tweet(0), sample_tweet(5)]];
+            let mut selected = 0;
+            let mut action = |_a: &mut dyn TwitterAPI, _t: &Tweet| {
+                selected += 1;
+                Ok(())
+            };
+
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                Some(&mut action),
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(selected, 2);
+            assert_eq!(erase_calls(&api), 0);
+        }
+
+        #[test]
+        fn preserves_pinned_tweet_if_enabled() {
+            for (preserve_pinned, expected_erasures) in &[(true, 1), (false, 2)] {
+                let settings = Settings {
+                    preserve_days: chrono::Duration::days(1),
+                    preserve_pinned: *preserve_pinned,
+                    ..Default::default()
+                };
+                let mut api = TestAPI {
+                    pinned_tweet_id_answer: Some(42),
+                    ..Default::default()
+                };
+                let mut pinned = sample_tweet(5);
+                pinned.id = 42;
+                let mut pages = vec![vec![sample_tweet(5), pinned]];
+
+                let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+                clear_timeline(
+                    "name",
+                    &settings,
+                    &mut api,
+                    dataset,
+                    None,
+                    None,
+                    &Progress::default(),
+                )
+                .unwrap();
+
+                assert_eq!(erase_calls(&api), *expected_erasures);
+            }
+        }
+
+        #[test]
+        fn preserves_self_threads_with_recent_or_protected_tweets_if_enabled() {
+            // a thread of old tweets with a preserved quote, another one with a recent tweet, an
+            // old reply to another user, and an old standalone tweet
+            let tweets: Vec<Tweet> = vec![
+                (1, 10, None, 7),
+                (2, 9, Some(1), 7),
+                (3, 8, Some(2), 7),
+                (4, 10, None, 7),
+                (5, 1, Some(4), 7),
+                (6, 10, None, 8),
+                (7, 9, Some(6), 7),
+                (8, 10, None, 7),
+            ]
+            .into_iter()
+            .map(|(id, days_ago, parent, author)| {
+                let mut tweet = sample_tweet(days_ago);
+                tweet.id = id;
+                tweet.in_reply_to_status_id = parent;
+                tweet.user = Some(Box::new(sample_user(author)));
+                tweet
+            })
+            .map(|mut tweet| {
+                if tweet.id == 2 {
+                    tweet.quoted_status_id = Some(99);
+                }
+                tweet
+            })
+            .collect();
+
+            for (preserve_self_threads, expected) in
+                &[(true, vec![6, 7, 8]), (false, vec![1, 3, 4, 6, 7, 8])]
+            {
+                let mut settings = Settings {
+                    preserve_days: chrono::Duration::days(3),
+                    preserve_self_threads: *preserve_self_threads,
+                    ..Default::default()
+                };
+                settings.filters.preserve_quotes = true;
+                let mut api = TestAPI {
+                    user_id_answer: Some(7),
+                    ..Default::default()
+                };
+                let mut pages = vec![tweets.clone()];
+
+                let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+                clear_timeline(
+                    "name",
+                    &settings,
+                    &mut api,
+                    dataset,
+                    None,
+                    None,
+                    &Progress::default(),
+                )
+                .unwrap();
+
+                assert_eq!(&api.erased_tweet_ids, expected);
+            }
+        }
+
+        #[test]
+        fn preserves_threads_with_recent_tweets_if_enabled() {
+            for (preserve_active_threads, expected_erasures) in &[(true, 1), (false, 3)] {
+                let settings = Settings {
+                    preserve_days: chrono::Duration::days(3),
+                    preserve_active_threads: *preserve_active_threads,
+                    ..Default::default()
+                };
+                let mut api = TestAPI {
+                    ..Default::default()
+                };
+                let thread: Vec<Tweet> = vec![(1, 10, None), (2, 5, Some(1)), (3, 1, Some(2))]
+                    .into_iter()
+                    .map(|(id, days_ago, parent)| {
+                        let mut tweet = sample_tweet(days_ago);
+                        tweet.id = id;
+                        tweet.in_reply_to_status_id = parent;
+                        tweet
+                    })
+                    .collect();
+                let mut old = sample_tweet(5);
+                old.id = 4;
+                // the timeline is fetched newest first, so the thread is split across pages
+                let mut pages = vec![
+                    vec![thread[1].clone(), thread[0].clone(), old],
+                    vec![thread[2].clone()],
+                ];
+
+                let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+                clear_timeline(
+                    "name",
+                    &settings,
+                    &mut api,
+                    dataset,
+                    None,
+                    None,
+                    &Progress::default(),
+                )
+                .unwrap();
+
+                assert_eq!(erase_calls(&api), *expected_erasures);
+            }
+        }
+
+        #[test]
+        fn stops_after_max_empty_pages_if_oldest_first() {
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(3),
+                max_empty_pages: Some(2),
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let recent_page = || vec![sample_tweet(2), sample_tweet(1)];
+            let mut pages = vec![
+                vec![sample_tweet(6), sample_tweet(5)],
+                recent_page(),
+                recent_page(),
+                recent_page(),
+                vec![sample_tweet(10), sample_tweet(5)],
+            ];
+            pages.reverse();
+            let mut requested = 0;
+
+            let dataset = |_a: &mut dyn TwitterAPI| {
+                requested += 1;
+                Ok(pages.pop().unwrap_or_default())
+            };
+            clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(requested, 4);
+            assert_eq!(erase_calls(&api), 2);
+        }
+
+        #[test]
+        fn keeps_self_likes_if_enabled() {
+            for keep_self_likes in &[false, true] {
+                let settings = Settings {
+                    preserve_days: chrono::Duration::days(1),
+                    keep_self_likes: *keep_self_likes,
+                    ..Default::default()
+                };
+                let mut own_tweet = sample_tweet(40);
+                own_tweet.user = Some(Box::new(sample_user(7)));
+                own_tweet.favorited = Some(true);
+                let mut api = TestAPI {
+                    user_id_answer: Some(7),
+                    ..Default::default()
+                };
+
+                let mut pages = vec![vec![own_tweet]];
+                let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+                let summary = clear_timeline(
+                    "name",
+                    &settings,
+                    &mut api,
+                    dataset,
+                    None,
+                    None,
+                    &Progress::default(),
+                )
+                .unwrap();
+
+                let unliked = api
+                    .methods_called_in_order
+                    .iter()
+                    .any(|m| m == "unlike_tweet");
+                assert_eq!(unliked, !keep_self_likes);
+                assert_eq!(summary.skipped, if *keep_self_likes { 1 } else { 0 });
+            }
+        }
+
+        #[test]
+        fn keeps_tweets_with_many_replies_if_enabled() {
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                min_replies: Some(5),
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                reply_counts: vec![(1, 10), (2, 4)].into_iter().collect(),
+                ..Default::default()
+            };
+
+            let tweets: Vec<Tweet> = (1..=3)
+                .map(|id| {
+                    let mut tweet = sample_tweet(2);
+                    tweet.id = id;
+                    tweet
+                })
+                .collect();
+            let mut pages = vec![tweets];
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(api.erased_tweet_ids, vec![2, 3]);
+            assert_eq!(summary.skipped, 1);
+        }
+
+        #[test]
+        fn only_erases_tweets_that_became_old_since_the_last_run() {
+            // tweets older than 32 days were already older than 30 days two days ago
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(30),
+                last_run: Some(Utc::now() - chrono::Duration::days(2)),
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let tweet = |id: u64, days_ago: i64| {
+                let mut tweet = sample_tweet(days_ago);
+                tweet.id = id;
+                tweet
+            };
+
+            let mut pages = vec![
+                vec![tweet(4, 50)],
+                vec![tweet(3, 40)],
+                vec![tweet(1, 10), tweet(2, 31)],
+            ];
+            let mut requested_pages = 0;
+            let dataset = |_a: &mut dyn TwitterAPI| {
+                requested_pages += 1;
+                Ok(pages.pop().unwrap_or_default())
+            };
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            assert_eq!(api.erased_tweet_ids, vec![2]);
+            assert_eq!(summary.deleted, 1);
+            assert_eq!(requested_pages, 2);
         }
 
-        process_timeline(name, preserve_days, api, tl_iterator, action)
-    }
-}
+        #[test]
+        fn handles_a_last_run_cutoff_beyond_any_date() {
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(100_000_000),
+                last_run: Some(Utc::now()),
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                ..Default::default()
+            };
 
-fn default_maintenance_action(api: &mut dyn TwitterAPI, tweet: &Tweet) -> Result<(), Errors> {
-    warn!(
-        "Erasing tweet created at: [{}] - F:{}|RT:{} -- {}",
-        tweet.created_at,
-        tweet.favorited.unwrap_or(false),
-        tweet.retweeted.unwrap_or(false),
-        tweet.text
-    );
+            let mut pages = vec![vec![sample_tweet(40)]];
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = clear_timeline(
+                "name",
+                &settings,
+                &mut api,
+                dataset,
+                None,
+                None,
+                &Progress::default(),
+            )
+            .unwrap();
 
-    if tweet.favorited.unwrap_or(false) {
-        api.unlike_tweet(&tweet).map_err(Errors::APIErrors)?;
-    }
-    if tweet.retweeted.unwrap_or(false) {
-        api.unretweet_tweet(&tweet).map_err(Errors::APIErrors)?;
-    }
+            assert_eq!(api.erased_tweet_ids, Vec::<u64>::new());
+            assert_eq!(summary.deleted, 0);
+        }
 
-    api.erase_tweet(&tweet).map_err(Errors::APIErrors)
-}
+        #[test]
+        fn keeps_latest_tweets_regardless_of_age() {
+            for (keep_latest, expected_erasures) in &[(0, 5), (3, 2), (10, 0)] {
+                let settings = Settings {
+                    preserve_days: chrono::Duration::days(1),
+                    keep_latest: *keep_latest,
+                    ..Default::default()
+                };
+                let mut api = TestAPI {
+                    ..Default::default()
+                };
+                let tweets: Vec<Tweet> = (0..5)
+                    .map(|id| {
+                        let mut tweet = sample_tweet(40 + id);
+                        tweet.id = id as u64;
+                        tweet
+                    })
+                    .collect();
+                let mut pages = vec![
+                    tweets[4..].to_vec(),
+                    tweets[2..4].to_vec(),
+                    tweets[..2].to_vec(),
+                ];
+                let mut erased = Vec::new();
+                let mut action = |_: &mut dyn TwitterAPI, tweet: &Tweet| {
+                    erased.push(tweet.id);
+                    Ok(())
+                };
 
-/// Returns true if the given date is older (exclusively older!) in days than the value of `preserve_days`
-fn is_erasable(created_at: DateTime<Utc>, preserve_days: i64) -> bool {
-    let utc: DateTime<Utc> = Utc::now();
-    utc.signed_duration_since(created_at) > Duration::days(preserve_days)
-}
+                let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+                clear_timeline(
+                    "name",
+                    &settings,
+                    &mut api,
+                    dataset,
+                    Some(&mut action),
+                    None,
+                    &Progress::default(),
+                )
+                .unwrap();
+
+                assert_eq!(erased.len(), *expected_erasures);
+                assert!(erased.iter().all(|id| *id >= *keep_latest as u64));
+            }
+        }
 
-#[cfg(test)]
-#[macro_use]
-extern crate pretty_assertions;
+        #[test]
+        fn counts_progress_across_pages() {
+            let settings = Settings {
+                preserve_days: chrono::Duration::days(1),
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut pages = vec![vec![sample_tweet(5); 3], vec![sample_tweet(0); 25]];
+            let progress = Progress::new(Some(100), 10);
 
-#[cfg(test)]
-#[macro_use]
-extern crate quickcheck;
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            clear_timeline("name", &settings, &mut api, dataset, None, None, &progress).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use chrono::prelude::*;
-    use egg_mode::tweet::{Tweet, TweetEntities, TweetSource};
+            assert_eq!(progress.processed(), 28);
+            assert_eq!(progress.deleted(), 3);
+        }
 
-    pub fn sample_tweet(days_ago: i64) -> Tweet {
-        let now = Utc::now().timestamp();
-        let seconds_past = days_ago * 24 * 60 * 60;
-        let dt = NaiveDateTime::from_timestamp(now - seconds_past, 0);
-        let date = DateTime::from_utc(dt, Utc);
-        Tweet {
-            coordinates: None,
-            created_at: date,
-            current_user_retweet: None,
-            display_text_range: None,
-            entities: TweetEntities {
-                hashtags: Vec::new(),
-                symbols: Vec::new(),
-                urls: Vec::new(),
-                user_mentions: Vec::new(),
-                media: None,
-            },
-            extended_entities: None,
-            favorite_count: 20,
-            favorited: None,
-            filter_level: None,
-            id: 1,
-            in_reply_to_user_id: None,
-            in_reply_to_screen_name: None,
-            in_reply_to_status_id: None,
-            lang: Some(String::from("und")),
-            place: None,
-            possibly_sensitive: None,
-            quoted_status_id: None,
-            quoted_status: None,
-            retweet_count: 10,
-            retweeted: None,
-            retweeted_status: None,
-            source: TweetSource {
-                name: String::from("source name"),
-                url: String::from("source url"),
-            },
-            text: String::from("a sample tweet"),
-            truncated: false,
-            user: None,
-            withheld_copyright: false,
-            withheld_in_countries: None,
-            withheld_scope: None,
+        #[test]
+        fn stops_at_tweet_boundary_when_cancelled() {
+            for concurrency in &[1, 4] {
+                let settings = Settings {
+                    preserve_days: chrono::Duration::days(1),
+                    concurrency: *concurrency,
+                    cancellation: Cancellation::default(),
+                    ..Default::default()
+                };
+                let mut api = TestAPI {
+                    ..Default::default()
+                };
+                let mut pages = vec![vec![sample_tweet(5)]; 5];
+                let mut pages_served = 0;
+
+                // simulates a Ctrl-C arriving while the third page is being processed
+                let dataset = |_a: &mut dyn TwitterAPI| {
+                    pages_served += 1;
+                    if pages_served == 3 {
+                        settings.cancellation.cancel();
+                    }
+                    Ok(pages.pop().unwrap_or_default())
+                };
+                let summary = clear_timeline(
+                    "name",
+                    &settings,
+                    &mut api,
+                    dataset,
+                    None,
+                    None,
+                    &Progress::default(),
+                )
+                .unwrap();
+
+                assert_eq!(erase_calls(&api), 2);
+                assert_eq!(summary.deleted, 2);
+                assert!(summary.cancelled);
+            }
         }
     }
 
-    mod clear_user_timeline {
-        use crate::api::{APIError, TestAPI};
-        use crate::clear_user_timelines;
-        use crate::Errors;
+    mod process_dms {
+        use crate::api::{APIError, DirectMessage, TestAPI, TwitterAPI};
+        use crate::{process_dms, Errors, RunSummary, Settings};
+        use chrono::prelude::*;
+
+        fn dm(id: u64, days_ago: i64) -> DirectMessage {
+            DirectMessage {
+                id,
+                created_at: Utc::now() - chrono::Duration::days(days_ago),
+            }
+        }
 
         #[test]
-        fn propagates_errors_from_user_tl() {
-            let err = APIError::TimelineError(String::from("bad answer"));
+        fn erases_old_direct_messages() {
+            let settings = Settings {
+                preserve_dm_days: chrono::Duration::days(7),
+                ..Default::default()
+            };
             let mut api = TestAPI {
-                user_timeline_next_page_answer: Err(err.clone()),
                 ..Default::default()
             };
+            let mut pages = vec![vec![dm(3, 30)], vec![dm(1, 2), dm(2, 10)]];
 
-            assert_eq!(
-                clear_user_timelines(&mut api, 10),
-                Err(Errors::APIErrors(err))
-            )
-        }
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let summary = process_dms(&settings, &mut api, dataset).unwrap();
 
-        #[test]
-        fn propagates_errors_from_likes_tl() {
-            let err = APIError::TimelineError(String::from("bad answer"));
-            let mut api = TestAPI {
-                likes_timeline_next_page_answer: Err(err.clone()),
+            let expected = RunSummary {
+                deleted_dms: 2,
+                api_calls: 2,
                 ..Default::default()
             };
-
-            assert_eq!(
-                clear_user_timelines(&mut api, 10),
-                Err(Errors::APIErrors(err))
-            )
+            assert_eq!(summary, expected);
+            assert_eq!(api.methods_called_in_order, vec!["erase_dm", "erase_dm"]);
         }
 
         #[test]
-        fn calls_expected_methods_in_api() {
+        fn propagates_erasure_errors() {
+            let err = APIError::ErasureError(String::from("Unexpected error"));
             let mut api = TestAPI {
+                erase_dm_answer: Err(err.clone()),
                 ..Default::default()
             };
+            let mut pages = vec![vec![dm(1, 60)]];
 
-            clear_user_timelines(&mut api, 10).unwrap();
-
-            let expected_calls = vec!["user_timeline_next_page", "likes_timeline_next_page"];
+            let dataset = |_a: &mut dyn TwitterAPI| Ok(pages.pop().unwrap_or_default());
+            let result = process_dms(&Settings::default(), &mut api, dataset);
 
-            assert_eq!(api.methods_called_in_order, expected_calls)
+            assert_eq!(result, Err(Errors::APIErrors(err)));
         }
     }
+
     mod process_timeline {
         use super::sample_tweet;
         use crate::api::{APIError, TestAPI, TwitterAPI};
-        use crate::process_timeline;
         use crate::Errors;
+        use crate::{process_timeline, DateWindow};
         use egg_mode::tweet::Tweet;
 
+        fn older_than(preserve_days: i64) -> impl Fn(&Tweet) -> bool {
+            let window = DateWindow::OlderThan(chrono::Duration::days(preserve_days));
+            move |tweet| window.contains(tweet.created_at, 0)
+        }
+
         #[test]
         fn propagates_dataset_errors() {
             let mut api = TestAPI {
@@ -283,7 +2793,7 @@ mod tests {
             let action = |_a: &mut dyn TwitterAPI, _t: &Tweet| Ok(());
 
             assert_eq!(
-                process_timeline("name", 1, &mut api, dataset, action),
+                process_timeline("name", &older_than(1), &mut api, dataset, action),
                 Err(Errors::APIErrors(err))
             );
         }
@@ -300,7 +2810,7 @@ mod tests {
             let action = |_a: &mut dyn TwitterAPI, _t: &Tweet| Err(err.clone());
 
             assert_eq!(
-                process_timeline("name", 1, &mut api, dataset, action),
+                process_timeline("name", &older_than(1), &mut api, dataset, action),
                 Err(err)
             );
         }
@@ -314,7 +2824,7 @@ mod tests {
             let action = |_a: &mut dyn TwitterAPI, _t: &Tweet| Ok(());
 
             assert_eq!(
-                process_timeline("name", 1, &mut api, dataset, action),
+                process_timeline("name", &older_than(1), &mut api, dataset, action),
                 Ok(())
             );
         }
@@ -336,7 +2846,7 @@ mod tests {
                     Ok(())
                 };
 
-                process_timeline("name", 1, &mut api, dataset,  action).unwrap();
+                process_timeline("name", &older_than(1), &mut api, dataset,  action).unwrap();
 
                 calls_made == sz
             }
@@ -358,7 +2868,7 @@ mod tests {
                     Ok(())
                 };
 
-                process_timeline("name", 4, &mut api, dataset, action).unwrap();
+                process_timeline("name", &older_than(4), &mut api, dataset, action).unwrap();
 
                 calls_made == oldsz
             }
@@ -367,9 +2877,9 @@ mod tests {
 
     mod default_maintenance_action {
         use super::sample_tweet;
-        use crate::api::{APIError, TestAPI};
+        use crate::api::{APIError, EraseOutcome, TestAPI};
         use crate::default_maintenance_action;
-        use crate::Errors;
+        use crate::{Errors, Settings};
 
         #[test]
         fn propagates_unlike_api_errors() {
@@ -383,8 +2893,8 @@ mod tests {
             tweet.favorited = Some(true);
 
             assert_eq!(
-                default_maintenance_action(&mut api, &tweet),
-                Err(Errors::APIErrors(err))
+                default_maintenance_action(&mut api, &tweet, &Settings::default()),
+                Err(Errors::APIErrors(err.for_tweet(1)))
             );
         }
 
@@ -400,8 +2910,8 @@ mod tests {
             tweet.retweeted = Some(true);
 
             assert_eq!(
-                default_maintenance_action(&mut api, &tweet),
-                Err(Errors::APIErrors(err))
+                default_maintenance_action(&mut api, &tweet, &Settings::default()),
+                Err(Errors::APIErrors(err.for_tweet(1)))
             );
         }
 
@@ -414,9 +2924,23 @@ mod tests {
             };
 
             assert_eq!(
-                default_maintenance_action(&mut api, &sample_tweet(1)),
-                Err(Errors::APIErrors(err))
+                default_maintenance_action(&mut api, &sample_tweet(1), &Settings::default()),
+                Err(Errors::APIErrors(err.for_tweet(1)))
+            );
+        }
+
+        #[test]
+        fn accepts_skipped_erasures() {
+            let mut api = TestAPI {
+                erase_tweet_answer: Ok(EraseOutcome::Skipped(String::from("not owned"))),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                default_maintenance_action(&mut api, &sample_tweet(1), &Settings::default()),
+                Ok(EraseOutcome::Skipped(String::from("not owned")))
             );
+            assert_eq!(api.methods_called_in_order, vec!["erase_tweet"]);
         }
 
         #[test]
@@ -428,7 +2952,7 @@ mod tests {
             let mut tweet = sample_tweet(1);
             tweet.favorited = Some(true);
 
-            default_maintenance_action(&mut api, &tweet).unwrap();
+            default_maintenance_action(&mut api, &tweet, &Settings::default()).unwrap();
 
             let expected = vec!["unlike_tweet", "erase_tweet"];
             assert_eq!(api.methods_called_in_order, expected);
@@ -443,7 +2967,7 @@ mod tests {
             let mut tweet = sample_tweet(1);
             tweet.retweeted = Some(true);
 
-            default_maintenance_action(&mut api, &tweet).unwrap();
+            default_maintenance_action(&mut api, &tweet, &Settings::default()).unwrap();
 
             let expected = vec!["unretweet_tweet", "erase_tweet"];
             assert_eq!(api.methods_called_in_order, expected);
@@ -459,40 +2983,243 @@ mod tests {
             tweet.favorited = Some(true);
             tweet.retweeted = Some(true);
 
-            default_maintenance_action(&mut api, &tweet).unwrap();
+            default_maintenance_action(&mut api, &tweet, &Settings::default()).unwrap();
 
             let expected = vec!["unlike_tweet", "unretweet_tweet", "erase_tweet"];
             assert_eq!(api.methods_called_in_order, expected);
         }
-    }
-    mod is_erasable {
-        use crate::is_erasable;
-        use chrono::prelude::*;
 
-        quickcheck! {
-            fn work_on_dates_as_expected(days_past: u32) -> bool {
-                let now = Utc::now().timestamp();
-                // not more than 10 years ago for testing purposes
-                let bounded = i64::from(days_past % (365 * 10));
-                let seconds_past = bounded * 24 * 60 * 60;
-
-                let dt = NaiveDateTime::from_timestamp(now - seconds_past, 0);
-                let date = DateTime::from_utc(dt, Utc);
-
-                // check the full range of date differences
-                let mut boundary_after_date = true;
-                for i in 0..bounded {
-                    boundary_after_date = boundary_after_date && is_erasable(date, i);
-                }
+        #[test]
+        fn only_unretweets_liked_tweets_if_keeping_likes() {
+            let settings = Settings {
+                keep_likes: true,
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut tweet = sample_tweet(1);
+            tweet.favorited = Some(true);
+            tweet.retweeted = Some(true);
 
-                let boundary_on_date = is_erasable(date, bounded);
+            let outcome = default_maintenance_action(&mut api, &tweet, &settings);
 
-                let mut boundary_before_date = false;
-                for i in (bounded+1)..(bounded + 365) {
-                    boundary_before_date = boundary_before_date && is_erasable(date, i);
-                }
-                boundary_after_date && boundary_on_date && !boundary_before_date
+            assert!(matches!(outcome, Ok(EraseOutcome::Skipped(_))));
+            assert_eq!(api.methods_called_in_order, vec!["unretweet_tweet"]);
+
+            // tweets we didn't like are erased as usual
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            tweet.favorited = Some(false);
+            default_maintenance_action(&mut api, &tweet, &settings).unwrap();
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["unretweet_tweet", "erase_tweet"]
+            );
+        }
+
+        #[test]
+        fn calls_expected_methods_in_custom_order() {
+            let cases = vec![
+                (
+                    "erase,unlike,unretweet",
+                    vec!["erase_tweet", "unlike_tweet", "unretweet_tweet"],
+                ),
+                (
+                    "unretweet,unlike,erase",
+                    vec!["unretweet_tweet", "unlike_tweet", "erase_tweet"],
+                ),
+            ];
+
+            for (order, expected) in cases {
+                let settings = Settings {
+                    action_order: order.parse().unwrap(),
+                    ..Default::default()
+                };
+                let mut api = TestAPI {
+                    ..Default::default()
+                };
+                let mut tweet = sample_tweet(1);
+                tweet.favorited = Some(true);
+                tweet.retweeted = Some(true);
+
+                default_maintenance_action(&mut api, &tweet, &settings).unwrap();
+
+                assert_eq!(api.methods_called_in_order, expected);
             }
         }
+
+        #[test]
+        fn erases_tweet_if_unlike_errors_are_ignored() {
+            let err = APIError::ErasureError(String::from("Unexpected error"));
+            let settings = Settings {
+                ignore_unlike_errors: true,
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                unlike_tweet_answer: Err(err.clone()),
+                unretweet_tweet_answer: Err(err.clone()),
+                ..Default::default()
+            };
+            let mut tweet = sample_tweet(1);
+            tweet.favorited = Some(true);
+
+            assert_eq!(
+                default_maintenance_action(&mut api, &tweet, &settings),
+                Ok(EraseOutcome::Deleted)
+            );
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["unlike_tweet", "erase_tweet"]
+            );
+
+            // unretweet errors are still fatal
+            tweet.retweeted = Some(true);
+            assert_eq!(
+                default_maintenance_action(&mut api, &tweet, &settings),
+                Err(Errors::APIErrors(err.for_tweet(1)))
+            );
+        }
+
+        #[test]
+        fn erases_tweet_if_unretweet_errors_are_ignored() {
+            let err = APIError::ErasureError(String::from("Unexpected error"));
+            let settings = Settings {
+                ignore_unretweet_errors: true,
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                unretweet_tweet_answer: Err(err.clone()),
+                ..Default::default()
+            };
+            let mut tweet = sample_tweet(1);
+            tweet.retweeted = Some(true);
+
+            assert_eq!(
+                default_maintenance_action(&mut api, &tweet, &settings),
+                Ok(EraseOutcome::Deleted)
+            );
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["unretweet_tweet", "erase_tweet"]
+            );
+        }
+
+        #[test]
+        fn propagates_erase_errors_even_if_other_errors_are_ignored() {
+            let err = APIError::ErasureError(String::from("Unexpected error"));
+            let settings = Settings {
+                ignore_unlike_errors: true,
+                ignore_unretweet_errors: true,
+                ..Default::default()
+            };
+            let mut api = TestAPI {
+                erase_tweet_answer: Err(err.clone()),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                default_maintenance_action(&mut api, &sample_tweet(1), &settings),
+                Err(Errors::APIErrors(err.for_tweet(1)))
+            );
+        }
+    }
+    mod ownership_aware_maintenance_action {
+        use super::{sample_tweet, sample_user};
+        use crate::api::TestAPI;
+        use crate::ownership_aware_maintenance_action;
+        use crate::Settings;
+
+        #[test]
+        fn erases_own_tweets() {
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut tweet = sample_tweet(1);
+            tweet.user = Some(Box::new(sample_user(7)));
+            tweet.favorited = Some(true);
+
+            ownership_aware_maintenance_action(&mut api, &tweet, Some(7), &Settings::default())
+                .unwrap();
+
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["unlike_tweet", "erase_tweet"]
+            );
+        }
+
+        #[test]
+        fn only_unlikes_and_unretweets_tweets_of_others() {
+            let mut api = TestAPI {
+                ..Default::default()
+            };
+            let mut tweet = sample_tweet(1);
+            tweet.user = Some(Box::new(sample_user(8)));
+            tweet.favorited = Some(true);
+            tweet.retweeted = Some(true);
+
+            ownership_aware_maintenance_action(&mut api, &tweet, Some(7), &Settings::default())
+                .unwrap();
+
+            assert_eq!(
+                api.methods_called_in_order,
+                vec!["unlike_tweet", "unretweet_tweet"]
+            );
+        }
+    }
+
+    mod archived_ids {
+        use super::sample_env;
+        use crate::archived_ids;
+        use chrono::prelude::*;
+        use std::env;
+        use std::fs;
+
+        #[test]
+        fn selects_archived_tweets_older_than_preserve_days() {
+            let path =
+                env::temp_dir().join(format!("twitter_privacy_archive_{}.js", std::process::id()));
+            let archive = r#"window.YTD.tweets.part0 = [
+  { "tweet" : { "id_str" : "1", "created_at" : "Mon Jan 10 10:00:00 +0000 2022" } },
+  { "tweet" : { "id_str" : "2", "created_at" : "Sat Jan 01 10:00:00 +0000 2022" } }
+]"#;
+            fs::write(&path, archive).unwrap();
+            let path = path.to_str().unwrap();
+            let now = Utc.ymd(2022, 1, 15).and_hms(10, 0, 0);
+
+            let env_values = sample_env(vec![("TP_ARCHIVE_PATH", path), ("TP_PRESERVE_DAYS", "7")]);
+            let old_ids = archived_ids(&env_values, now);
+            let mut env_values = sample_env(vec![("TP_ARCHIVE_PATH", path)]);
+            env_values.delete_all = true;
+            let all_ids = archived_ids(&env_values, now);
+            fs::remove_file(path).unwrap();
+
+            assert_eq!(old_ids.unwrap(), vec![2]);
+            assert_eq!(all_ids.unwrap(), vec![1, 2]);
+        }
+
+        #[test]
+        fn selects_nothing_when_preserve_days_is_beyond_any_date() {
+            let path = env::temp_dir().join(format!(
+                "twitter_privacy_archive_long_{}.js",
+                std::process::id()
+            ));
+            let archive = r#"window.YTD.tweets.part0 = [
+  { "tweet" : { "id_str" : "1", "created_at" : "Sat Jan 01 10:00:00 +0000 2022" } }
+]"#;
+            fs::write(&path, archive).unwrap();
+            let path = path.to_str().unwrap();
+            let now = Utc.ymd(2022, 1, 15).and_hms(10, 0, 0);
+
+            let env_values = sample_env(vec![
+                ("TP_ARCHIVE_PATH", path),
+                ("TP_PRESERVE_DAYS", "100000000"),
+            ]);
+            let ids = archived_ids(&env_values, now);
+            fs::remove_file(path).unwrap();
+
+            assert_eq!(ids.unwrap(), Vec::<u64>::new());
+        }
     }
 }
@@ -0,0 +1,374 @@
+use crate::api::{APIError, AccountInfo, DirectMessage, EraseOutcome, TwitterAPI};
+use crate::pacing::RateLimitBudget;
+use egg_mode::entities::MediaEntity;
+use egg_mode::tweet::Tweet;
+use futures::{Future, Stream};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Uri};
+use hyper_tls::HttpsConnector;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::runtime::current_thread::block_on_all;
+
+/// Fetches the files attached to tweets. Lets tests run without network access
+pub trait Downloader {
+    /// Returns the contents of the file at `url`
+    fn download(&self, url: &str) -> Result<Vec<u8>, String>;
+}
+
+/// `Downloader` that sends a GET request for each file, over HTTPS
+pub struct HttpDownloader {
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+}
+
+impl HttpDownloader {
+    /// Builds the HTTPS client used for every download
+    pub fn new() -> Result<HttpDownloader, String> {
+        let https = HttpsConnector::new(1).map_err(|e| e.to_string())?;
+        Ok(HttpDownloader {
+            client: Client::builder().build(https),
+        })
+    }
+}
+
+impl Downloader for HttpDownloader {
+    fn download(&self, url: &str) -> Result<Vec<u8>, String> {
+        let uri: Uri = url
+            .parse()
+            .map_err(|e| format!("Invalid url {}: {}", url, e))?;
+        let request = self.client.get(uri).and_then(|response| {
+            let status = response.status();
+            response
+                .into_body()
+                .concat2()
+                .map(move |body| (status, body))
+        });
+        match block_on_all(request) {
+            Ok((status, body)) if status.is_success() => Ok(body.to_vec()),
+            Ok((status, _)) => Err(format!("Couldn't download {}: {}", url, status)),
+            Err(e) => Err(format!("Couldn't download {}: {}", url, e)),
+        }
+    }
+}
+
+/// Returns the addresses of the photos, videos and animated GIFs attached to the tweet. For videos
+/// we pick the MP4 with the highest bitrate
+pub fn media_urls(tweet: &Tweet) -> Vec<String> {
+    let media = match (&tweet.extended_entities, &tweet.entities.media) {
+        (Some(extended), _) => extended.media.as_slice(),
+        (None, Some(media)) => media.as_slice(),
+        (None, None) => &[],
+    };
+    media.iter().map(best_url).collect()
+}
+
+fn best_url(media: &MediaEntity) -> String {
+    let best_video = media.video_info.as_ref().and_then(|video| {
+        video
+            .variants
+            .iter()
+            .filter(|variant| variant.content_type == "video/mp4")
+            .max_by_key(|variant| variant.bitrate.unwrap_or(0))
+    });
+    match best_video {
+        Some(variant) => variant.url.clone(),
+        None => media.media_url_https.clone(),
+    }
+}
+
+// name of the file in the url, without the query
+fn file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Wrapper over a `TwitterAPI` that saves the media attached to a tweet before requesting its
+/// erasure, in a directory named after the id of the tweet. If any file can't be saved the tweet is
+/// not erased, so we keep the originals of the pictures and videos we post.
+pub struct MediaAPI<'r> {
+    api: &'r mut dyn TwitterAPI,
+    dir: PathBuf,
+    downloader: Box<dyn Downloader>,
+}
+
+impl<'r> MediaAPI<'r> {
+    /// Saves the media under `dir`, fetching the files with `downloader`
+    pub fn new(
+        api: &'r mut dyn TwitterAPI,
+        dir: &str,
+        downloader: Box<dyn Downloader>,
+    ) -> MediaAPI<'r> {
+        MediaAPI {
+            api,
+            dir: PathBuf::from(dir),
+            downloader,
+        }
+    }
+
+    fn save_media(&self, tweet: &Tweet) -> Result<(), APIError> {
+        let urls = media_urls(tweet);
+        if urls.is_empty() {
+            return Ok(());
+        }
+        let tweet_dir = self.dir.join(tweet.id.to_string());
+        let saved = fs::create_dir_all(&tweet_dir)
+            .map_err(|e| format!("Couldn't create {}: {}", tweet_dir.display(), e))
+            .and_then(|_| {
+                urls.iter()
+                    .try_for_each(|url| self.save_file(url, &tweet_dir.join(file_name(url))))
+            });
+        saved.map_err(|e| {
+            APIError::ErasureError(format!("Couldn't save media: {}", e)).for_tweet(tweet.id)
+        })
+    }
+
+    // files saved by a previous attempt to erase the tweet are not downloaded again
+    fn save_file(&self, url: &str, path: &Path) -> Result<(), String> {
+        if path.exists() {
+            return Ok(());
+        }
+        let contents = self.downloader.download(url)?;
+        fs::write(path, contents).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+    }
+}
+
+impl<'r> TwitterAPI for MediaAPI<'r> {
+    fn validate_token(&mut self) -> Result<(), APIError> {
+        self.api.validate_token()
+    }
+
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError> {
+        self.api.get_user_id(screen_name)
+    }
+
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        self.api.get_account_info(screen_name)
+    }
+
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.api.get_statuses_count()
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        self.api.get_tweet(id)
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        self.api.rate_limit_status()
+    }
+
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        self.api.reply_count(tweet)
+    }
+
+    fn api_calls(&self) -> usize {
+        self.api.api_calls()
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.api.token_user_id()
+    }
+
+    fn tweet_count(&self) -> Option<u64> {
+        self.api.tweet_count()
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.api.pinned_tweet_id()
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        self.api.user_id()
+    }
+
+    fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.user_timeline_next_page()
+    }
+
+    fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.likes_timeline_next_page()
+    }
+
+    fn unlike_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        self.api.unlike_tweet(tweet)
+    }
+
+    fn unretweet_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        self.api.unretweet_tweet(tweet)
+    }
+
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
+        self.save_media(tweet)?;
+        self.api.erase_tweet(tweet)
+    }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        self.api.direct_messages_next_page()
+    }
+
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError> {
+        self.api.erase_dm(dm)
+    }
+
+    fn maintain_tweets(
+        &mut self,
+        tweets: &[&Tweet],
+        concurrency: usize,
+    ) -> Vec<Result<EraseOutcome, APIError>> {
+        let saved: Vec<Result<(), APIError>> =
+            tweets.iter().map(|tweet| self.save_media(tweet)).collect();
+        let with_media_saved: Vec<&Tweet> = tweets
+            .iter()
+            .zip(&saved)
+            .filter(|(_, saved)| saved.is_ok())
+            .map(|(tweet, _)| *tweet)
+            .collect();
+        let mut results = self
+            .api
+            .maintain_tweets(&with_media_saved, concurrency)
+            .into_iter();
+        saved
+            .into_iter()
+            .map(|saved| match saved {
+                Ok(()) => results
+                    .next()
+                    .unwrap_or_else(|| Err(APIError::ErasureError(String::from("Missing result")))),
+                Err(e) => Err(e),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_name, media_urls, Downloader, MediaAPI};
+    use crate::api::{TestAPI, TwitterAPI};
+    use crate::tests::sample_tweet;
+    use egg_mode::entities::{MediaEntity, MediaSize, MediaSizes, MediaType, ResizeMode};
+    use egg_mode::tweet::{ExtendedTweetEntities, Tweet};
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs;
+    use std::rc::Rc;
+
+    struct FakeDownloader {
+        requested: Rc<RefCell<Vec<String>>>,
+        fail: bool,
+    }
+
+    impl Downloader for FakeDownloader {
+        fn download(&self, url: &str) -> Result<Vec<u8>, String> {
+            self.requested.borrow_mut().push(String::from(url));
+            if self.fail {
+                Err(String::from("Not found"))
+            } else {
+                Ok(url.as_bytes().to_vec())
+            }
+        }
+    }
+
+    fn photo(name: &str) -> MediaEntity {
+        let size = || MediaSize {
+            w: 100,
+            h: 100,
+            resize: ResizeMode::Fit,
+        };
+        MediaEntity {
+            display_url: String::from("pic.twitter.com/abcdef"),
+            expanded_url: String::from("https://twitter.com/user/status/1/photo/1"),
+            id: 1,
+            range: (0, 23),
+            media_url: format!("http://pbs.twimg.com/media/{}", name),
+            media_url_https: format!("https://pbs.twimg.com/media/{}", name),
+            sizes: MediaSizes {
+                thumb: size(),
+                small: size(),
+                medium: size(),
+                large: size(),
+            },
+            source_status_id: None,
+            media_type: MediaType::Photo,
+            url: String::from("https://t.co/abcdef"),
+            video_info: None,
+            ext_alt_text: None,
+        }
+    }
+
+    fn tweet_with_photos(id: u64, names: &[&str]) -> Tweet {
+        let mut tweet = sample_tweet(1);
+        tweet.id = id;
+        tweet.extended_entities = Some(ExtendedTweetEntities {
+            media: names.iter().map(|name| photo(name)).collect(),
+        });
+        tweet
+    }
+
+    #[test]
+    fn saves_media_before_erasing_tweets() {
+        let dir = env::temp_dir().join(format!("twitter_privacy_media_{}", std::process::id()));
+        let requested = Rc::new(RefCell::new(Vec::new()));
+        let downloader = FakeDownloader {
+            requested: requested.clone(),
+            fail: false,
+        };
+        let mut api = TestAPI {
+            ..Default::default()
+        };
+        let with_photos = tweet_with_photos(1, &["first.jpg", "second.png"]);
+        let text_only = sample_tweet(1);
+
+        {
+            let mut media_api =
+                MediaAPI::new(&mut api, dir.to_str().unwrap(), Box::new(downloader));
+            media_api.erase_tweet(&with_photos).unwrap();
+            media_api.maintain_tweets(&[&text_only, &with_photos], 2);
+        }
+        let first = fs::read_to_string(dir.join("1").join("first.jpg"));
+        let second = fs::read_to_string(dir.join("1").join("second.png"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first.unwrap(), "https://pbs.twimg.com/media/first.jpg");
+        assert_eq!(second.unwrap(), "https://pbs.twimg.com/media/second.png");
+        // the second time the files are already there
+        assert_eq!(requested.borrow().len(), 2);
+        assert_eq!(
+            api.methods_called_in_order,
+            vec!["erase_tweet", "erase_tweet", "erase_tweet"]
+        );
+    }
+
+    #[test]
+    fn keeps_tweets_whose_media_fails_to_download() {
+        let dir = env::temp_dir().join(format!(
+            "twitter_privacy_media_failed_{}",
+            std::process::id()
+        ));
+        let downloader = FakeDownloader {
+            requested: Rc::new(RefCell::new(Vec::new())),
+            fail: true,
+        };
+        let mut api = TestAPI {
+            ..Default::default()
+        };
+
+        let result = MediaAPI::new(&mut api, dir.to_str().unwrap(), Box::new(downloader))
+            .erase_tweet(&tweet_with_photos(1, &["first.jpg"]));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+        assert!(api.methods_called_in_order.is_empty());
+    }
+
+    #[test]
+    fn names_files_after_the_url() {
+        assert_eq!(
+            media_urls(&tweet_with_photos(1, &["abc.jpg"])),
+            vec!["https://pbs.twimg.com/media/abc.jpg"]
+        );
+        assert!(media_urls(&sample_tweet(1)).is_empty());
+        assert_eq!(
+            file_name("https://video.twimg.com/ext_tw_video/1/pu/vid/720x1280/abc.mp4?tag=10"),
+            "abc.mp4"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use crate::retry::{Clock, SystemClock};
+use rand::Rng;
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Source of the random numbers used to spread the delays. Lets tests use fixed values
+pub trait RandomSource {
+    /// Returns a number between 0 (included) and `upper` (excluded), or 0 if `upper` is 0
+    fn below(&self, upper: u64) -> u64;
+}
+
+/// `RandomSource` backed by the thread-local generator of `rand`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadRandom;
+
+impl RandomSource for ThreadRandom {
+    fn below(&self, upper: u64) -> u64 {
+        if upper == 0 {
+            0
+        } else {
+            rand::thread_rng().gen_range(0, upper)
+        }
+    }
+}
+
+/// Requests we can still send in the current rate limit window, out of `limit`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitBudget {
+    pub remaining: u32,
+    pub limit: u32,
+}
+
+/// Extra wait that grows in proportion to the rate limit budget already used, from nothing with
+/// the whole budget left to `max_delay` once it is spent. We slow down as we get close to the
+/// limit, and speed up again once the window resets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveDelay {
+    pub max_delay: Duration,
+}
+
+impl AdaptiveDelay {
+    /// Returns the extra wait with `remaining` requests left out of `limit`
+    pub fn delay(&self, remaining: u32, limit: u32) -> Duration {
+        if limit == 0 {
+            return Duration::from_millis(0);
+        }
+        let used = limit - remaining.min(limit);
+        self.max_delay
+            .checked_mul(used)
+            .map_or(self.max_delay, |delay| delay / limit)
+    }
+}
+
+/// Longer pause after every `every` deletions, so the run doesn't keep a steady pace for hours
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cooldown {
+    pub every: usize,
+    pub pause: Duration,
+}
+
+/// Wait before each request that erases a tweet, so we don't send them in bursts. A random `jitter`
+/// is added to each wait, so several runs started at the same time don't stay aligned. If
+/// `adaptive` is set, the wait also grows as the last rate limit budget recorded shrinks, and if
+/// `cooldown` is set we pause for longer after every few deletions
+#[derive(Clone)]
+pub struct Pacing {
+    /// Time to wait before every request
+    pub delay: Duration,
+    /// Maximum time added at random to `delay`
+    pub jitter: Duration,
+    pub adaptive: Option<AdaptiveDelay>,
+    pub cooldown: Option<Cooldown>,
+    budget: Cell<Option<RateLimitBudget>>,
+    deletions: Cell<usize>,
+    clock: Rc<dyn Clock>,
+    random: Rc<dyn RandomSource>,
+}
+
+impl Pacing {
+    pub fn new(delay: Duration, jitter: Duration) -> Pacing {
+        Pacing {
+            delay,
+            jitter,
+            adaptive: None,
+            cooldown: None,
+            budget: Cell::new(None),
+            deletions: Cell::new(0),
+            clock: Rc::new(SystemClock),
+            random: Rc::new(ThreadRandom),
+        }
+    }
+
+    /// Returns a copy of this `Pacing` that waits using the given clock
+    pub fn with_clock(self, clock: Rc<dyn Clock>) -> Pacing {
+        Pacing { clock, ..self }
+    }
+
+    /// Returns a copy of this `Pacing` that draws the jitter from the given source
+    pub fn with_random(self, random: Rc<dyn RandomSource>) -> Pacing {
+        Pacing { random, ..self }
+    }
+
+    /// Returns a copy of this `Pacing` that also waits as the rate limit budget shrinks
+    pub fn with_adaptive(self, adaptive: AdaptiveDelay) -> Pacing {
+        Pacing {
+            adaptive: Some(adaptive),
+            ..self
+        }
+    }
+
+    /// Returns a copy of this `Pacing` that also pauses after every few deletions
+    pub fn with_cooldown(self, cooldown: Cooldown) -> Pacing {
+        Pacing {
+            cooldown: Some(cooldown),
+            ..self
+        }
+    }
+
+    /// Counts `deleted` more deletions, and pauses for the cooldown if they reach a multiple of its
+    /// `every`. A batch that goes past several multiples pauses only once
+    pub fn record_deletions(&self, deleted: usize) {
+        let before = self.deletions.get();
+        let after = before + deleted;
+        self.deletions.set(after);
+        if let Some(cooldown) = self.cooldown {
+            if cooldown.every > 0 && after / cooldown.every > before / cooldown.every {
+                info!(
+                    "Pausing for {} seconds after {} deletions",
+                    cooldown.pause.as_secs(),
+                    after
+                );
+                self.clock.sleep(cooldown.pause);
+            }
+        }
+    }
+
+    /// Records the rate limit budget the following waits adapt to
+    pub fn record_budget(&self, budget: RateLimitBudget) {
+        self.budget.set(Some(budget));
+    }
+
+    /// Returns the time to wait before the next request, `delay` plus a random part of `jitter`,
+    /// plus the adaptive delay for the last budget recorded, if any
+    pub fn next_delay(&self) -> Duration {
+        let jitter = u64::try_from(self.jitter.as_millis()).unwrap_or(u64::MAX);
+        let adaptive = match (self.adaptive, self.budget.get()) {
+            (Some(adaptive), Some(budget)) => adaptive.delay(budget.remaining, budget.limit),
+            _ => Duration::from_millis(0),
+        };
+        self.delay + Duration::from_millis(self.random.below(jitter)) + adaptive
+    }
+
+    /// Waits before the next request, if needed
+    pub fn wait(&self) {
+        let delay = self.next_delay();
+        if delay > Duration::from_millis(0) {
+            self.clock.sleep(delay);
+        }
+    }
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing::new(Duration::from_millis(0), Duration::from_millis(0))
+    }
+}
+
+impl fmt::Debug for Pacing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pacing")
+            .field("delay", &self.delay)
+            .field("jitter", &self.jitter)
+            .field("adaptive", &self.adaptive)
+            .field("cooldown", &self.cooldown)
+            .field("budget", &self.budget.get())
+            .field("deletions", &self.deletions.get())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveDelay, Cooldown, Pacing, RandomSource, RateLimitBudget};
+    use crate::retry::tests::FakeClock;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    /// `RandomSource` that always returns the largest value allowed, or the smallest if `max` is false
+    struct FixedRandom {
+        max: bool,
+    }
+
+    impl RandomSource for FixedRandom {
+        fn below(&self, upper: u64) -> u64 {
+            if self.max {
+                upper.saturating_sub(1)
+            } else {
+                0
+            }
+        }
+    }
+
+    fn pacing(max: bool) -> Pacing {
+        Pacing::new(Duration::from_millis(500), Duration::from_millis(200))
+            .with_random(Rc::new(FixedRandom { max }))
+    }
+
+    #[test]
+    fn adds_jitter_to_the_delay() {
+        assert_eq!(pacing(false).next_delay(), Duration::from_millis(500));
+        assert_eq!(pacing(true).next_delay(), Duration::from_millis(699));
+    }
+
+    #[test]
+    fn random_jitter_stays_in_range() {
+        let pacing = Pacing::new(Duration::from_millis(500), Duration::from_millis(200));
+
+        for _ in 0..100 {
+            let delay = pacing.next_delay();
+            assert!(delay >= Duration::from_millis(500) && delay < Duration::from_millis(700));
+        }
+    }
+
+    #[test]
+    fn adaptive_delay_grows_as_the_budget_shrinks() {
+        let adaptive = AdaptiveDelay {
+            max_delay: Duration::from_millis(1000),
+        };
+
+        assert_eq!(adaptive.delay(900, 900), Duration::from_millis(0));
+        assert_eq!(adaptive.delay(675, 900), Duration::from_millis(250));
+        assert_eq!(adaptive.delay(450, 900), Duration::from_millis(500));
+        assert_eq!(adaptive.delay(90, 900), Duration::from_millis(900));
+        assert_eq!(adaptive.delay(0, 900), Duration::from_millis(1000));
+        // we don't trust budgets out of range
+        assert_eq!(adaptive.delay(1000, 900), Duration::from_millis(0));
+        assert_eq!(adaptive.delay(0, 0), Duration::from_millis(0));
+    }
+
+    #[test]
+    fn adds_adaptive_delay_for_the_last_budget_recorded() {
+        let pacing = pacing(false).with_adaptive(AdaptiveDelay {
+            max_delay: Duration::from_millis(1000),
+        });
+        assert_eq!(pacing.next_delay(), Duration::from_millis(500));
+
+        pacing.record_budget(RateLimitBudget {
+            remaining: 45,
+            limit: 180,
+        });
+        assert_eq!(pacing.next_delay(), Duration::from_millis(1250));
+
+        // the window was reset
+        pacing.record_budget(RateLimitBudget {
+            remaining: 180,
+            limit: 180,
+        });
+        assert_eq!(pacing.next_delay(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn cools_down_after_every_few_deletions() {
+        let clock = Rc::new(FakeClock::default());
+        let pacing = Pacing::default()
+            .with_clock(clock.clone())
+            .with_cooldown(Cooldown {
+                every: 3,
+                pause: Duration::from_secs(60),
+            });
+
+        let mut pauses_after = Vec::new();
+        for deletion in 1..=10 {
+            pacing.record_deletions(1);
+            if clock.sleeps.borrow().len() > pauses_after.len() {
+                pauses_after.push(deletion);
+            }
+        }
+        // a batch that reaches the next multiple pauses once
+        pacing.record_deletions(5);
+
+        assert_eq!(pauses_after, vec![3, 6, 9]);
+        assert_eq!(*clock.sleeps.borrow(), vec![Duration::from_secs(60); 4]);
+    }
+
+    #[test]
+    fn never_cools_down_by_default() {
+        let clock = Rc::new(FakeClock::default());
+        let pacing = Pacing::default().with_clock(clock.clone());
+
+        pacing.record_deletions(1000);
+        assert!(clock.sleeps.borrow().is_empty());
+    }
+
+    #[test]
+    fn waits_only_if_there_is_a_delay() {
+        let clock = Rc::new(FakeClock::default());
+
+        Pacing::default().with_clock(clock.clone()).wait();
+        assert!(clock.sleeps.borrow().is_empty());
+
+        pacing(true).with_clock(clock.clone()).wait();
+        assert_eq!(*clock.sleeps.borrow(), vec![Duration::from_millis(699)]);
+    }
+}
@@ -0,0 +1,442 @@
+use egg_mode::tweet::Tweet;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Condition on the fields of a tweet, parsed from expressions like
+/// `favorites<10 AND (lang=en OR has:media)`. `AND` binds tighter than `OR`, and parentheses group
+/// conditions. The available conditions are:
+///
+/// - `favorites` and `retweets`, compared with a number using `<`, `<=`, `>`, `>=`, `=` or `!=`
+/// - `lang`, compared with a language code using `=` or `!=`
+/// - `has:media` and `has:reply`, true if the tweet has pictures or videos, or is a reply
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Count(Counter, Comparison, i64),
+    Lang(Comparison, String),
+    Has(Feature),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+/// Counts of a tweet we can compare
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Counter {
+    Favorites,
+    Retweets,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+/// Parts a tweet may have, checked with `has:`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    Media,
+    Reply,
+}
+
+impl Predicate {
+    /// Returns true if the given tweet meets the condition
+    pub fn matches(&self, tweet: &Tweet) -> bool {
+        match self {
+            Predicate::Count(counter, comparison, value) => {
+                let count = match counter {
+                    Counter::Favorites => tweet.favorite_count,
+                    Counter::Retweets => tweet.retweet_count,
+                };
+                comparison.holds(i64::from(count).cmp(value))
+            }
+            Predicate::Lang(comparison, lang) => {
+                let same = tweet
+                    .lang
+                    .as_ref()
+                    .is_some_and(|l| l.eq_ignore_ascii_case(lang));
+                comparison.holds(if same {
+                    Ordering::Equal
+                } else {
+                    Ordering::Less
+                })
+            }
+            Predicate::Has(Feature::Media) => has_media(tweet),
+            Predicate::Has(Feature::Reply) => tweet.in_reply_to_status_id.is_some(),
+            Predicate::And(left, right) => left.matches(tweet) && right.matches(tweet),
+            Predicate::Or(left, right) => left.matches(tweet) || right.matches(tweet),
+        }
+    }
+}
+
+impl Comparison {
+    // true if a value that compares to the expected one as `ordering` meets the comparison
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::NotEqual => ordering != Ordering::Equal,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Equal => "=",
+            Comparison::NotEqual => "!=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl FromStr for Predicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            next: 0,
+        };
+        let predicate = parser.or()?;
+        match parser.advance() {
+            None => Ok(predicate),
+            found => Err(unexpected(found, "AND, OR or the end of the filter")),
+        }
+    }
+}
+
+// pictures and videos are listed in the entities, or in the extended entities if there are several
+pub(crate) fn has_media(tweet: &Tweet) -> bool {
+    let in_entities = tweet
+        .entities
+        .media
+        .as_ref()
+        .is_some_and(|media| !media.is_empty());
+    let in_extended = tweet
+        .extended_entities
+        .as_ref()
+        .is_some_and(|extended| !extended.media.is_empty());
+    in_entities || in_extended
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Comparison(Comparison),
+    Word(String),
+}
+
+// splits the expression into tokens, along with the position where each starts, counting
+// characters from 1 so errors can point at them
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let is_symbol = |c: char| c.is_whitespace() || "()<>=!".contains(c);
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let position = i + 1;
+        let followed_by_equal = chars.get(i + 1) == Some(&'=');
+        let (token, length) = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '<' if followed_by_equal => (Token::Comparison(Comparison::LessOrEqual), 2),
+            '<' => (Token::Comparison(Comparison::Less), 1),
+            '>' if followed_by_equal => (Token::Comparison(Comparison::GreaterOrEqual), 2),
+            '>' => (Token::Comparison(Comparison::Greater), 1),
+            '=' if followed_by_equal => (Token::Comparison(Comparison::Equal), 2),
+            '=' => (Token::Comparison(Comparison::Equal), 1),
+            '!' if followed_by_equal => (Token::Comparison(Comparison::NotEqual), 2),
+            '!' => {
+                return Err(format!(
+                    "unexpected '!' at position {}, expected '!='",
+                    position
+                ))
+            }
+            _ => {
+                let word: String = chars[i..].iter().take_while(|c| !is_symbol(**c)).collect();
+                let length = word.chars().count();
+                (Token::Word(word), length)
+            }
+        };
+        tokens.push((position, token));
+        i += length;
+    }
+    Ok(tokens)
+}
+
+// recursive descent parser over the tokens, `next` is the index of the next token to read
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn advance(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    // consumes the next token if it is the given keyword, in any case
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.next) {
+            Some((_, Token::Word(word))) if word.eq_ignore_ascii_case(keyword) => {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Predicate, String> {
+        let mut predicate = self.and()?;
+        while self.keyword("OR") {
+            predicate = Predicate::Or(Box::new(predicate), Box::new(self.and()?));
+        }
+        Ok(predicate)
+    }
+
+    fn and(&mut self) -> Result<Predicate, String> {
+        let mut predicate = self.term()?;
+        while self.keyword("AND") {
+            predicate = Predicate::And(Box::new(predicate), Box::new(self.term()?));
+        }
+        Ok(predicate)
+    }
+
+    fn term(&mut self) -> Result<Predicate, String> {
+        match self.advance() {
+            Some((_, Token::Open)) => {
+                let predicate = self.or()?;
+                match self.advance() {
+                    Some((_, Token::Close)) => Ok(predicate),
+                    found => Err(unexpected(found, "')'")),
+                }
+            }
+            Some((position, Token::Word(word))) => self.condition(position, &word),
+            found => Err(unexpected(found, "a condition")),
+        }
+    }
+
+    fn condition(&mut self, position: usize, word: &str) -> Result<Predicate, String> {
+        let word = word.to_lowercase();
+        if let Some(feature) = word.strip_prefix("has:") {
+            return match feature {
+                "media" => Ok(Predicate::Has(Feature::Media)),
+                "reply" => Ok(Predicate::Has(Feature::Reply)),
+                _ => Err(format!(
+                    "unknown {:?} at position {}, expected has:media or has:reply",
+                    word, position
+                )),
+            };
+        }
+        let counter = match word.as_str() {
+            "favorites" => Some(Counter::Favorites),
+            "retweets" => Some(Counter::Retweets),
+            "lang" => None,
+            _ => {
+                return Err(format!(
+                    "unknown field {:?} at position {}, expected favorites, retweets, lang or has:",
+                    word, position
+                ))
+            }
+        };
+        let (comparison_position, comparison) = match self.advance() {
+            Some((position, Token::Comparison(comparison))) => (position, comparison),
+            found => return Err(unexpected(found, "a comparison like '<' or '='")),
+        };
+        let (position, value) = match self.advance() {
+            Some((position, Token::Word(value))) => (position, value),
+            found => return Err(unexpected(found, "a value")),
+        };
+
+        match counter {
+            Some(counter) => value
+                .parse()
+                .map(|value| Predicate::Count(counter, comparison, value))
+                .map_err(|_| {
+                    format!(
+                        "unexpected {:?} at position {}, expected a number",
+                        value, position
+                    )
+                }),
+            None if comparison == Comparison::Equal || comparison == Comparison::NotEqual => {
+                Ok(Predicate::Lang(comparison, value))
+            }
+            None => Err(format!(
+                "unexpected '{}' at position {}, lang can only be compared with '=' or '!='",
+                comparison, comparison_position
+            )),
+        }
+    }
+}
+
+// describes the token we found when we expected something else
+fn unexpected(found: Option<(usize, Token)>, expected: &str) -> String {
+    let (position, token) = match found {
+        Some(found) => found,
+        None => return format!("unexpected end of the filter, expected {}", expected),
+    };
+    let token = match token {
+        Token::Open => String::from("'('"),
+        Token::Close => String::from("')'"),
+        Token::Comparison(comparison) => format!("'{}'", comparison),
+        Token::Word(word) => format!("{:?}", word),
+    };
+    format!(
+        "unexpected {} at position {}, expected {}",
+        token, position, expected
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, Counter, Feature, Predicate};
+    use crate::tests::sample_tweet;
+
+    fn parse(s: &str) -> Result<Predicate, String> {
+        s.parse()
+    }
+
+    #[test]
+    fn parses_conditions() {
+        assert_eq!(
+            parse("favorites<10"),
+            Ok(Predicate::Count(Counter::Favorites, Comparison::Less, 10))
+        );
+        assert_eq!(
+            parse("retweets >= 2"),
+            Ok(Predicate::Count(
+                Counter::Retweets,
+                Comparison::GreaterOrEqual,
+                2
+            ))
+        );
+        assert_eq!(
+            parse("lang!=en"),
+            Ok(Predicate::Lang(Comparison::NotEqual, String::from("en")))
+        );
+        assert_eq!(parse("has:MEDIA"), Ok(Predicate::Has(Feature::Media)));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let reply = || Box::new(Predicate::Has(Feature::Reply));
+        let media = || Box::new(Predicate::Has(Feature::Media));
+        let english = || Box::new(Predicate::Lang(Comparison::Equal, String::from("en")));
+
+        assert_eq!(
+            parse("has:reply OR has:media and lang=en"),
+            Ok(Predicate::Or(
+                reply(),
+                Box::new(Predicate::And(media(), english()))
+            ))
+        );
+        assert_eq!(
+            parse("(has:reply OR has:media) AND lang=en"),
+            Ok(Predicate::And(
+                Box::new(Predicate::Or(reply(), media())),
+                english()
+            ))
+        );
+    }
+
+    #[test]
+    fn points_at_the_offending_token() {
+        let cases = vec![
+            (
+                "favorites<10 AND lang=en has:media",
+                "unexpected \"has:media\" at position 26, expected AND, OR or the end of the filter",
+            ),
+            (
+                "favourites<10",
+                "unknown field \"favourites\" at position 1, expected favorites, retweets, lang or has:",
+            ),
+            (
+                "favorites<ten",
+                "unexpected \"ten\" at position 11, expected a number",
+            ),
+            (
+                "lang<en",
+                "unexpected '<' at position 5, lang can only be compared with '=' or '!='",
+            ),
+            (
+                "(has:reply OR has:media",
+                "unexpected end of the filter, expected ')'",
+            ),
+            (
+                "has:reply AND",
+                "unexpected end of the filter, expected a condition",
+            ),
+            (
+                "retweets 5",
+                "unexpected \"5\" at position 10, expected a comparison like '<' or '='",
+            ),
+            ("", "unexpected end of the filter, expected a condition"),
+            ("lang ! en", "unexpected '!' at position 6, expected '!='"),
+        ];
+
+        for (expression, expected) in cases {
+            assert_eq!(
+                parse(expression),
+                Err(String::from(expected)),
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn evaluates_expressions_against_tweets() {
+        let mut popular = sample_tweet(1);
+        popular.favorite_count = 50;
+        popular.retweet_count = 5;
+        popular.lang = Some(String::from("en"));
+        let mut reply = sample_tweet(1);
+        reply.favorite_count = 1;
+        reply.retweet_count = 0;
+        reply.lang = Some(String::from("es"));
+        reply.in_reply_to_status_id = Some(7);
+
+        let cases = vec![
+            ("favorites<10", false, true),
+            ("favorites>=50", true, false),
+            ("retweets=0", false, true),
+            ("lang=EN", true, false),
+            ("lang!=en", false, true),
+            ("has:reply", false, true),
+            ("has:media", false, false),
+            ("favorites<10 AND lang=en", false, false),
+            ("favorites<10 OR lang=en", true, true),
+            ("has:media OR (retweets>1 AND lang=en)", true, false),
+        ];
+
+        for (expression, matches_popular, matches_reply) in cases {
+            let predicate = parse(expression).unwrap();
+            assert_eq!(
+                predicate.matches(&popular),
+                matches_popular,
+                "{}",
+                expression
+            );
+            assert_eq!(predicate.matches(&reply), matches_reply, "{}", expression);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+
+/// Missing tweets we tolerate before warning, as `statuses_count` may include tweets the timeline
+/// never returns, like those withheld in our country
+const GAP_TOLERANCE: u64 = 100;
+
+/// Tweets we can reach paging through the user timeline, compared with those the account has
+/// posted. The timeline only returns the latest 3,200 tweets, so older ones can only be found in
+/// the archive of the account
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Reconciliation {
+    /// Tweets retrieved from the user timeline
+    pub reachable: u64,
+    /// Tweets posted by the user, as reported by the user details
+    pub posted: u64,
+}
+
+impl Reconciliation {
+    /// Tweets posted that the timeline didn't return
+    pub fn missing(&self) -> u64 {
+        self.posted.saturating_sub(self.reachable)
+    }
+
+    /// Returns the warning to show if the timeline misses too many tweets to erase them all
+    pub fn warning(&self) -> Option<String> {
+        if self.missing() > GAP_TOLERANCE {
+            Some(format!(
+                "{}. Download the archive of your account and erase them with TP_MODE=erase_ids",
+                self
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Reconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Timeline API exposes {} of {} tweets; {} older tweets require archive import",
+            thousands(self.reachable),
+            thousands(self.posted),
+            thousands(self.missing())
+        )
+    }
+}
+
+// formats a number with commas between thousands, as in 18,402
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{thousands, Reconciliation};
+
+    #[test]
+    fn warns_when_the_timeline_misses_many_tweets() {
+        let reconciliation = Reconciliation {
+            reachable: 3200,
+            posted: 18402,
+        };
+
+        assert_eq!(reconciliation.missing(), 15202);
+        assert_eq!(
+            reconciliation.warning(),
+            Some(String::from(
+                "Timeline API exposes 3,200 of 18,402 tweets; 15,202 older tweets require archive \
+                 import. Download the archive of your account and erase them with TP_MODE=erase_ids"
+            ))
+        );
+    }
+
+    #[test]
+    fn tolerates_small_gaps() {
+        let cases = vec![(3200, 3200), (3150, 3200), (500, 450)];
+        for (reachable, posted) in cases {
+            let reconciliation = Reconciliation { reachable, posted };
+            assert_eq!(reconciliation.warning(), None);
+        }
+    }
+
+    #[test]
+    fn separates_thousands() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1000), "1,000");
+        assert_eq!(thousands(1234567), "1,234,567");
+    }
+}
@@ -0,0 +1,377 @@
+use crate::pacing::{RandomSource, ThreadRandom};
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+/// Source of time used to wait between attempts. Lets tests run without really waiting
+pub trait Clock {
+    /// Blocks the current thread for the given duration
+    fn sleep(&self, duration: Duration);
+}
+
+/// `Clock` that uses the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// Retries an operation, waiting exponentially longer between attempts. A random `jitter` is added
+/// to each wait, so several runs failing at the same time don't retry together
+#[derive(Clone)]
+pub struct Backoff {
+    /// Time to wait before the first retry
+    pub base_delay: Duration,
+    /// Factor applied to the delay after every retry
+    pub multiplier: u32,
+    /// Maximum number of times we run the operation, including the first one
+    pub max_attempts: u32,
+    /// Maximum time added at random to each delay
+    pub jitter: Duration,
+    clock: Rc<dyn Clock>,
+    random: Rc<dyn RandomSource>,
+}
+
+impl Backoff {
+    pub fn new(base_delay: Duration, multiplier: u32, max_attempts: u32) -> Backoff {
+        Backoff {
+            base_delay,
+            multiplier,
+            max_attempts,
+            jitter: Duration::from_millis(0),
+            clock: Rc::new(SystemClock),
+            random: Rc::new(ThreadRandom),
+        }
+    }
+
+    /// Returns a copy of this `Backoff` that waits using the given clock
+    pub fn with_clock(self, clock: Rc<dyn Clock>) -> Backoff {
+        Backoff { clock, ..self }
+    }
+
+    /// Returns a copy of this `Backoff` that adds up to `jitter` to each delay
+    pub fn with_jitter(self, jitter: Duration) -> Backoff {
+        Backoff { jitter, ..self }
+    }
+
+    /// Returns a copy of this `Backoff` that draws the jitter from the given source
+    pub fn with_random(self, random: Rc<dyn RandomSource>) -> Backoff {
+        Backoff { random, ..self }
+    }
+
+    /// Waits for the given time using the clock of this `Backoff`
+    pub fn sleep(&self, duration: Duration) {
+        self.clock.sleep(duration)
+    }
+
+    /// Returns the time to wait before the given retry, starting at 1, without the jitter
+    pub fn delay_for(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.saturating_pow(retry.saturating_sub(1));
+        self.base_delay.checked_mul(factor).unwrap_or(Duration::MAX)
+    }
+
+    /// Returns the time to wait before the given retry, `delay_for` plus a random part of `jitter`
+    pub fn jittered_delay_for(&self, retry: u32) -> Duration {
+        let jitter = u64::try_from(self.jitter.as_millis()).unwrap_or(u64::MAX);
+        self.delay_for(retry)
+            .saturating_add(Duration::from_millis(self.random.below(jitter)))
+    }
+
+    /// Runs `operation` until it succeeds, it fails with an error for which `is_retryable` returns
+    /// false, or we reach `max_attempts`. Returns the result of the last attempt.
+    pub fn retry<T, E, F, P>(&self, is_retryable: P, operation: F) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+        P: Fn(&E) -> bool,
+        E: fmt::Display,
+    {
+        self.retry_with(is_retryable, |_| None, operation)
+    }
+
+    /// Like `retry`, but if `wait_for` returns a duration for the error of a failed attempt, we wait
+    /// that long before the next attempt instead of the exponential delay
+    pub fn retry_with<T, E, F, P, W>(
+        &self,
+        is_retryable: P,
+        wait_for: W,
+        mut operation: F,
+    ) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+        P: Fn(&E) -> bool,
+        W: Fn(&E) -> Option<Duration>,
+        E: fmt::Display,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(ref e) if attempt < self.max_attempts && is_retryable(e) => {
+                    let delay = wait_for(e).unwrap_or_else(|| self.jittered_delay_for(attempt));
+                    warn!(
+                        "Attempt {} of {} failed, retrying in {:?}: {}",
+                        attempt, self.max_attempts, delay, e
+                    );
+                    self.clock.sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Like `retry_with`, for an operation over `count` items at once. `operation` runs on the
+    /// indexes of the items still to do, and returns their results in the same order. The items that
+    /// failed with a retryable error run again in the next attempt, after the longest wait any of
+    /// them needs. Returns the last result of each item, or `None` if `operation` didn't return it
+    pub fn retry_each_with<T, E, F, P, W>(
+        &self,
+        count: usize,
+        is_retryable: P,
+        wait_for: W,
+        mut operation: F,
+    ) -> Vec<Option<Result<T, E>>>
+    where
+        F: FnMut(&[usize]) -> Vec<Result<T, E>>,
+        P: Fn(&E) -> bool,
+        W: Fn(&E) -> Option<Duration>,
+        E: fmt::Display,
+    {
+        let mut results: Vec<Option<Result<T, E>>> = (0..count).map(|_| None).collect();
+        let mut pending: Vec<usize> = (0..count).collect();
+        let mut attempt = 1;
+        while !pending.is_empty() {
+            let mut failed = Vec::new();
+            let mut delay = None;
+            for (&index, result) in pending.iter().zip(operation(&pending)) {
+                if let Err(ref e) = result {
+                    if attempt < self.max_attempts && is_retryable(e) {
+                        let wait = wait_for(e).unwrap_or_else(|| self.jittered_delay_for(attempt));
+                        warn!(
+                            "Attempt {} of {} failed for item {}: {}",
+                            attempt,
+                            self.max_attempts,
+                            index + 1,
+                            e
+                        );
+                        delay = delay.max(Some(wait));
+                        failed.push(index);
+                    }
+                }
+                results[index] = Some(result);
+            }
+            if let Some(delay) = delay {
+                warn!("Retrying {} items in {:?}", failed.len(), delay);
+                self.clock.sleep(delay);
+            }
+            pending = failed;
+            attempt += 1;
+        }
+        results
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_secs(1), 2, 3)
+    }
+}
+
+impl fmt::Debug for Backoff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Backoff")
+            .field("base_delay", &self.base_delay)
+            .field("multiplier", &self.multiplier)
+            .field("max_attempts", &self.max_attempts)
+            .field("jitter", &self.jitter)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Backoff, Clock};
+    use crate::pacing::RandomSource;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    /// `Clock` that doesn't wait, but records the requested delays
+    #[derive(Default)]
+    pub struct FakeClock {
+        pub sleeps: RefCell<Vec<Duration>>,
+    }
+
+    impl Clock for FakeClock {
+        fn sleep(&self, duration: Duration) {
+            self.sleeps.borrow_mut().push(duration);
+        }
+    }
+
+    fn backoff(clock: &Rc<FakeClock>) -> Backoff {
+        Backoff::new(Duration::from_millis(100), 3, 4).with_clock(clock.clone())
+    }
+
+    #[test]
+    fn computes_exponential_delays() {
+        let backoff = Backoff::new(Duration::from_millis(100), 3, 4);
+
+        assert_eq!(backoff.delay_for(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay_for(2), Duration::from_millis(300));
+        assert_eq!(backoff.delay_for(3), Duration::from_millis(900));
+    }
+
+    #[test]
+    fn stops_on_success() {
+        let clock = Rc::new(FakeClock::default());
+        let mut attempts = 0;
+
+        let result = backoff(&clock).retry(
+            |_e: &String| true,
+            || {
+                attempts += 1;
+                if attempts < 3 {
+                    Err(String::from("transient"))
+                } else {
+                    Ok(attempts)
+                }
+            },
+        );
+
+        assert_eq!(result, Ok(3));
+        let expected = vec![Duration::from_millis(100), Duration::from_millis(300)];
+        assert_eq!(*clock.sleeps.borrow(), expected);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let clock = Rc::new(FakeClock::default());
+        let mut attempts = 0;
+
+        let result: Result<(), String> = backoff(&clock).retry(
+            |_e| true,
+            || {
+                attempts += 1;
+                Err(format!("failure #{}", attempts))
+            },
+        );
+
+        assert_eq!(result, Err(String::from("failure #4")));
+        assert_eq!(clock.sleeps.borrow().len(), 3);
+    }
+
+    #[test]
+    fn waits_the_given_duration_if_any() {
+        let clock = Rc::new(FakeClock::default());
+        let mut attempts = 0;
+
+        let result: Result<(), String> = backoff(&clock).retry_with(
+            |_e| true,
+            |e: &String| {
+                if e.as_str() == "rate limit" {
+                    Some(Duration::from_secs(42))
+                } else {
+                    None
+                }
+            },
+            || {
+                attempts += 1;
+                if attempts == 1 {
+                    Err(String::from("rate limit"))
+                } else {
+                    Err(String::from("other"))
+                }
+            },
+        );
+
+        assert_eq!(result, Err(String::from("other")));
+        let expected = vec![
+            Duration::from_secs(42),
+            Duration::from_millis(300),
+            Duration::from_millis(900),
+        ];
+        assert_eq!(*clock.sleeps.borrow(), expected);
+    }
+
+    #[test]
+    fn adds_jitter_to_the_delays() {
+        struct MaxRandom;
+        impl RandomSource for MaxRandom {
+            fn below(&self, upper: u64) -> u64 {
+                upper.saturating_sub(1)
+            }
+        }
+        let backoff = Backoff::new(Duration::from_millis(100), 3, 4)
+            .with_jitter(Duration::from_millis(50))
+            .with_random(Rc::new(MaxRandom));
+
+        assert_eq!(backoff.jittered_delay_for(1), Duration::from_millis(149));
+        assert_eq!(backoff.jittered_delay_for(2), Duration::from_millis(349));
+        assert_eq!(backoff.delay_for(2), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn does_not_retry_permanent_errors() {
+        let clock = Rc::new(FakeClock::default());
+        let mut attempts = 0;
+
+        let result: Result<(), String> = backoff(&clock).retry(
+            |e: &String| e.as_str() == "transient",
+            || {
+                attempts += 1;
+                Err(String::from("permanent"))
+            },
+        );
+
+        assert_eq!(result, Err(String::from("permanent")));
+        assert_eq!(attempts, 1);
+        assert!(clock.sleeps.borrow().is_empty());
+    }
+
+    #[test]
+    fn retries_only_the_items_that_failed() {
+        let clock = Rc::new(FakeClock::default());
+        let mut attempts: Vec<Vec<usize>> = vec![];
+
+        let results = backoff(&clock).retry_each_with(
+            4,
+            |e: &String| e.as_str() != "permanent",
+            |_e| None,
+            |pending| {
+                attempts.push(pending.to_vec());
+                pending
+                    .iter()
+                    .map(|&item| match (item, attempts.len()) {
+                        (1, _) => Err(String::from("permanent")),
+                        (2, 1) | (3, _) => Err(String::from("transient")),
+                        _ => Ok(item),
+                    })
+                    .collect()
+            },
+        );
+
+        assert_eq!(
+            results,
+            vec![
+                Some(Ok(0)),
+                Some(Err(String::from("permanent"))),
+                Some(Ok(2)),
+                Some(Err(String::from("transient"))),
+            ]
+        );
+        assert_eq!(
+            attempts,
+            vec![vec![0, 1, 2, 3], vec![2, 3], vec![3], vec![3]]
+        );
+        let expected = vec![
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+            Duration::from_millis(900),
+        ];
+        assert_eq!(*clock.sleeps.borrow(), expected);
+    }
+}
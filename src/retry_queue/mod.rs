@@ -0,0 +1,65 @@
+use crate::id_list::read_ids;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Write};
+
+/// Appends the id of a tweet we failed to erase to the retry queue at `path`, followed by the
+/// reason as a comment, so the file can be passed as it is to the `erase_ids` mode
+pub fn append(path: &str, id: u64, reason: &str) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Couldn't open retry queue {}: {}", path, e))?;
+    let reason = reason.replace('\n', " ");
+    writeln!(file, "{} # {}", id, reason)
+        .map_err(|e| format!("Couldn't write to retry queue {}: {}", path, e))
+}
+
+/// Reads the ids of the tweets in the retry queue at `path`. A missing file is an empty queue
+pub fn read(path: &str) -> Result<Vec<u64>, String> {
+    match File::open(path) {
+        Ok(file) => Ok(read_ids(BufReader::new(file))),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Couldn't open retry queue {}: {}", path, e)),
+    }
+}
+
+/// Empties the retry queue at `path`, once we are about to retry its tweets
+pub fn clear(path: &str) -> Result<(), String> {
+    match fs::remove_file(path) {
+        Err(ref e) if e.kind() != ErrorKind::NotFound => {
+            Err(format!("Couldn't clear retry queue {}: {}", path, e))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append, clear, read};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn queues_failed_ids_with_their_reason() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_retry_queue_{}.txt",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        append(path, 1234, "Rate limit exceeded").unwrap();
+        append(path, 5678, "Error\nover two lines").unwrap();
+        let text = fs::read_to_string(path).unwrap();
+        let ids = read(path);
+        clear(path).unwrap();
+
+        assert_eq!(
+            text,
+            "1234 # Rate limit exceeded\n5678 # Error over two lines\n"
+        );
+        assert_eq!(ids, Ok(vec![1234, 5678]));
+        assert_eq!(read(path), Ok(Vec::new()));
+        assert_eq!(clear(path), Ok(()));
+    }
+}
@@ -0,0 +1,64 @@
+use egg_mode::tweet::Tweet;
+use std::fs;
+
+/// Renders the given tweets as a static HTML page, with a row for each tweet, so they can be
+/// reviewed in a browser before erasing them
+pub fn render_html(tweets: &[Tweet]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Tweets to erase</title>\n</head>\n<body>\n",
+    );
+    html.push_str(&format!("<h1>{} tweets to erase</h1>\n", tweets.len()));
+    html.push_str(
+        "<table>\n<tr><th>Date</th><th>Id</th><th>Text</th><th>Likes</th><th>Retweets</th></tr>\n",
+    );
+    for tweet in tweets {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            tweet.created_at.format("%Y-%m-%d %H:%M"),
+            tweet.id,
+            escape(&tweet.text),
+            tweet.favorite_count,
+            tweet.retweet_count
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Writes the page rendered by `render_html` to the given file
+pub fn write_html(path: &str, tweets: &[Tweet]) -> Result<(), String> {
+    fs::write(path, render_html(tweets))
+        .map_err(|e| format!("Couldn't write review to {}: {}", path, e))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_html;
+    use crate::tests::sample_tweet;
+
+    #[test]
+    fn renders_a_row_per_tweet() {
+        let mut first = sample_tweet(40);
+        first.id = 1234;
+        first.text = String::from("first <tweet> & more");
+        first.favorite_count = 5;
+        let mut second = sample_tweet(50);
+        second.id = 5678;
+        second.text = String::from("second tweet");
+
+        let html = render_html(&[first, second]);
+
+        assert!(html.contains("2 tweets to erase"));
+        assert!(html.contains("<td>1234</td><td>first &lt;tweet&gt; &amp; more</td><td>5</td>"));
+        assert!(html.contains("<td>5678</td><td>second tweet</td>"));
+        assert_eq!(html.matches("<tr><td>").count(), 2);
+    }
+}
@@ -0,0 +1,1057 @@
+use crate::action_log::{self, ActionLogAPI};
+use crate::api::TwitterAPI;
+use crate::backup::BackupAPI;
+use crate::config::{Settings, TimelineKind};
+use crate::dry_run::DryRunAPI;
+use crate::export::CsvExport;
+use crate::histogram::AgeHistogram;
+use crate::journal::JournalAPI;
+use crate::last_run;
+use crate::media::{HttpDownloader, MediaAPI};
+use crate::reconcile::Reconciliation;
+use crate::retry_queue;
+use crate::store::{JsonLinesStore, StoreAPI};
+use crate::{
+    clear_user_timelines, erase_tweets_by_id, read_ids, Cancellation, EnvValues, Errors, Filters,
+    RunSummary,
+};
+use chrono::{Duration, Utc};
+use egg_mode::tweet::Tweet;
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io::BufReader;
+
+/// Operation applied to each tweet selected for erasure
+pub type TweetAction<'a> = dyn FnMut(&mut dyn TwitterAPI, &Tweet) -> Result<(), Errors> + 'a;
+
+/// Function called after each tweet selected for erasure is processed, with what happened to it
+pub type TweetObserver<'a> = dyn FnMut(&Tweet, Outcome) + 'a;
+
+/// What happened to a tweet selected for erasure
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Deleted,
+    /// The tweet wasn't erased, for the given reason. Tweets posted by other users are only unliked
+    /// and unretweeted when checking ownership
+    Skipped(String),
+    /// Processing the tweet failed with the given error
+    Failed(String),
+}
+
+/// Builder to configure and execute a run over the timelines of a user
+///
+/// ```no_run
+/// # use twitter_privacy::{EnvValues, RealAPI, Run, TimelineKind};
+/// let env = EnvValues::load().unwrap();
+/// let mut api = RealAPI::new(env).unwrap();
+/// let summary = Run::new()
+///     .preserve_days(90)
+///     .timelines(vec![TimelineKind::Likes])
+///     .dry_run(true)
+///     .execute(&mut api);
+/// ```
+pub struct Run<'a> {
+    settings: Settings,
+    action: Option<Box<TweetAction<'a>>>,
+    observer: Option<Box<TweetObserver<'a>>>,
+}
+
+impl<'a> Run<'a> {
+    /// Builds a run with the default settings, see `Settings::default`
+    pub fn new() -> Run<'a> {
+        Run {
+            settings: Settings::default(),
+            action: None,
+            observer: None,
+        }
+    }
+
+    /// Builds a run with the settings loaded from the environment
+    pub fn from_env(env_values: &EnvValues) -> Run<'a> {
+        Run {
+            settings: Settings::from_env(env_values),
+            action: None,
+            observer: None,
+        }
+    }
+
+    /// Tweets newer than this amount of days are not erased
+    pub fn preserve_days(self, preserve_days: i64) -> Run<'a> {
+        self.preserve(Duration::days(preserve_days))
+    }
+
+    /// Tweets newer than this are not erased, like `preserve_days` for periods that aren't whole days
+    pub fn preserve(mut self, preserve: Duration) -> Run<'a> {
+        self.settings.preserve_days = preserve;
+        self
+    }
+
+    /// If true, requests to unlike, unretweet or erase tweets are logged instead of sent
+    pub fn dry_run(mut self, dry_run: bool) -> Run<'a> {
+        self.settings.dry_run = dry_run;
+        self
+    }
+
+    /// Timelines to process, in order
+    pub fn timelines(mut self, timelines: Vec<TimelineKind>) -> Run<'a> {
+        self.settings.timelines = timelines;
+        self
+    }
+
+    /// Rules, besides age, that decide which tweets can be erased
+    pub fn filters(mut self, filters: Filters) -> Run<'a> {
+        self.settings.filters = filters;
+        self
+    }
+
+    /// Maximum number of tweets erased at the same time. Ignored if a custom `action` is set
+    pub fn concurrency(mut self, concurrency: usize) -> Run<'a> {
+        self.settings.concurrency = concurrency;
+        self
+    }
+
+    /// Maximum number of tweets erased in the run. Once reached, the timelines are not processed
+    /// further
+    pub fn max_deletions(mut self, max_deletions: usize) -> Run<'a> {
+        self.settings.max_deletions = Some(max_deletions);
+        self
+    }
+
+    /// Flag to stop the run early
+    pub fn cancellation(mut self, cancellation: Cancellation) -> Run<'a> {
+        self.settings.cancellation = cancellation;
+        self
+    }
+
+    /// File where we append every unlike, unretweet and erase that succeeds. If `skip_logged` is
+    /// true, tweets already in the file are not processed again
+    pub fn action_log(mut self, path: &str, skip_logged: bool) -> Run<'a> {
+        self.settings.action_log_path = Some(String::from(path));
+        self.settings.skip_logged = skip_logged;
+        self
+    }
+
+    /// File where we append every tweet we delete, as Markdown, to read them later
+    pub fn journal(mut self, path: &str) -> Run<'a> {
+        self.settings.journal_path = Some(String::from(path));
+        self
+    }
+
+    /// File where we append every tweet, as a line of JSON, before requesting its erasure. Tweets
+    /// that can't be written to it are not erased
+    pub fn backup(mut self, path: &str) -> Run<'a> {
+        self.settings.backup_path = Some(String::from(path));
+        self
+    }
+
+    /// File replaced with a CSV of the tweets processed in the run, with their date, text, likes,
+    /// retweets and what happened to them. Nothing is written in dry runs
+    pub fn export(mut self, path: &str) -> Run<'a> {
+        self.settings.export_path = Some(String::from(path));
+        self
+    }
+
+    /// Directory where we save the photos and videos attached to each tweet before requesting its
+    /// erasure, in a directory named after the id of the tweet. Tweets whose media can't be saved
+    /// are not erased
+    pub fn media(mut self, path: &str) -> Run<'a> {
+        self.settings.media_path = Some(String::from(path));
+        self
+    }
+
+    /// File where we append every tweet retrieved from the timelines, and the outcome of every
+    /// request about it, as lines of JSON, to report on them later
+    pub fn store(mut self, path: &str) -> Run<'a> {
+        self.settings.store_path = Some(String::from(path));
+        self
+    }
+
+    /// File with the ids of the tweets that must never be unliked, unretweeted or erased, one per
+    /// line. It is read once when the run starts
+    pub fn keep_ids_file(mut self, path: &str) -> Run<'a> {
+        self.settings.keep_ids_file = Some(String::from(path));
+        self
+    }
+
+    /// File where we append the id of every tweet we fail to erase, with the error. If
+    /// `retry_first` is true, the tweets in the file are erased before the timelines
+    pub fn retry_queue(mut self, path: &str, retry_first: bool) -> Run<'a> {
+        self.settings.retry_queue_path = Some(String::from(path));
+        self.settings.retry_queue_first = retry_first;
+        self
+    }
+
+    /// Replaces the default unlike, unretweet and erase of each selected tweet
+    pub fn action<F>(mut self, action: F) -> Run<'a>
+    where
+        F: FnMut(&mut dyn TwitterAPI, &Tweet) -> Result<(), Errors> + 'a,
+    {
+        self.action = Some(Box::new(action));
+        self
+    }
+
+    /// Called after each tweet selected for erasure is processed, with its outcome. Tweets handled
+    /// by a custom `action` are not observed, as only the action knows what it did with them
+    pub fn observer<F>(mut self, observer: F) -> Run<'a>
+    where
+        F: FnMut(&Tweet, Outcome) + 'a,
+    {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Settings the run will use
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Processes the timelines of the user behind `api`, returning what was done
+    ///
+    /// # Impure
+    ///
+    /// - Multiple requests to Twitter API
+    ///
+    /// # Errors
+    ///
+    /// - Errors while interacting with Twitter API, or returned by the custom `action`
+    /// - The action log can't be read or opened, or the journal can't be opened
+    /// - The last run file can't be read or written
+    /// - The file of ids to keep can't be opened
+    /// - The retry queue can't be read or cleared
+    pub fn execute(mut self, api: &mut dyn TwitterAPI) -> Result<RunSummary, Errors> {
+        let started_at = Utc::now();
+        let last_run_file = self.settings.last_run_file.clone();
+        if let Some(path) = &last_run_file {
+            self.settings.last_run = last_run::read_from(path).map_err(Errors::LibErrors)?;
+            if let Some(last_run) = self.settings.last_run {
+                info!("Only erasing tweets that became old since {}", last_run);
+            }
+        }
+
+        let dry_run = self.settings.dry_run;
+        let mut queued = Vec::new();
+        if let (Some(path), true) = (
+            &self.settings.retry_queue_path,
+            self.settings.retry_queue_first,
+        ) {
+            queued = retry_queue::read(path).map_err(Errors::LibErrors)?;
+            info!("Retrying {} tweets from {}", queued.len(), path);
+            // tweets failing again are appended back while we retry them
+            if !dry_run {
+                retry_queue::clear(path).map_err(Errors::LibErrors)?;
+            }
+        }
+        let summary = self.run_with(api, |api, settings, mut action, mut observer| {
+            let mut summary = RunSummary::default();
+            if !queued.is_empty() {
+                summary = erase_tweets_by_id(
+                    api,
+                    &queued,
+                    settings,
+                    action.as_deref_mut(),
+                    observer.as_deref_mut(),
+                )?;
+            }
+            let timelines = clear_user_timelines(api, settings, action, observer)?;
+            summary.merge(&timelines);
+            Ok(summary)
+        })?;
+
+        // a run that didn't go through every old tweet can't narrow the next one
+        let complete =
+            !dry_run && !summary.cancelled && !summary.stopped_early && summary.failed == 0;
+        match last_run_file {
+            Some(path) if complete => {
+                info!("Write the start of this run to {}", path);
+                last_run::write_to(&path, started_at).map_err(Errors::LibErrors)?;
+            }
+            Some(path) => info!("Not updating {} as the run wasn't complete", path),
+            None => (),
+        }
+        Ok(summary)
+    }
+
+    /// File where we keep when the last complete run started, so the next one only erases the
+    /// tweets of the user timeline that became old since then. See `Settings::last_run`
+    pub fn last_run_file(mut self, path: &str) -> Run<'a> {
+        self.settings.last_run_file = Some(String::from(path));
+        self
+    }
+
+    /// Erases the tweets with the given ids, whatever their age or the filters, with the dry run,
+    /// action log, custom action and observer of this run. Ids of tweets we can't retrieve are
+    /// counted as failed and skipped
+    ///
+    /// # Impure
+    ///
+    /// - Multiple requests to Twitter API
+    ///
+    /// # Errors
+    ///
+    /// - Errors while erasing the tweets, or returned by the custom `action`
+    /// - The action log can't be read or opened, or the journal can't be opened
+    /// - The file of ids to keep can't be opened
+    pub fn erase_ids(self, api: &mut dyn TwitterAPI, ids: &[u64]) -> Result<RunSummary, Errors> {
+        self.run_with(api, |api, settings, action, observer| {
+            erase_tweets_by_id(api, ids, settings, action, observer)
+        })
+    }
+
+    // applies `process` with the settings, action and observer of this run, on top of the action
+    // log, journal, backup, media, store, export and dry run if enabled
+    fn run_with<F>(mut self, api: &mut dyn TwitterAPI, process: F) -> Result<RunSummary, Errors>
+    where
+        F: FnOnce(
+            &mut dyn TwitterAPI,
+            &Settings,
+            Option<&mut TweetAction>,
+            Option<&mut TweetObserver>,
+        ) -> Result<RunSummary, Errors>,
+    {
+        if let Some(path) = &self.settings.keep_ids_file {
+            let file = File::open(path)
+                .map_err(|e| Errors::LibErrors(format!("Couldn't open {}: {}", path, e)))?;
+            self.settings.kept_ids = read_ids(BufReader::new(file)).into_iter().collect();
+            info!(
+                "Never touching the {} tweets listed in {}",
+                self.settings.kept_ids.len(),
+                path
+            );
+        }
+        let mut log_api;
+        let api: &mut dyn TwitterAPI = match self.settings.action_log_path.clone() {
+            Some(path) => {
+                if self.settings.skip_logged {
+                    self.settings.skipped_ids =
+                        action_log::handled_ids(&path).map_err(Errors::LibErrors)?;
+                    info!(
+                        "Skipping {} tweets handled by previous runs",
+                        self.settings.skipped_ids.len()
+                    );
+                }
+                log_api = ActionLogAPI::open(api, &path).map_err(Errors::LibErrors)?;
+                &mut log_api
+            }
+            None => api,
+        };
+        let mut journal_api;
+        let api: &mut dyn TwitterAPI = match self.settings.journal_path.clone() {
+            Some(path) => {
+                journal_api = JournalAPI::open(api, &path, self.settings.timezone)
+                    .map_err(Errors::LibErrors)?;
+                &mut journal_api
+            }
+            None => api,
+        };
+        let mut backup_api;
+        let api: &mut dyn TwitterAPI = match self.settings.backup_path.clone() {
+            Some(path) => {
+                backup_api = BackupAPI::open(api, &path).map_err(Errors::LibErrors)?;
+                &mut backup_api
+            }
+            None => api,
+        };
+        let mut media_api;
+        let api: &mut dyn TwitterAPI = match self.settings.media_path.clone() {
+            Some(path) => {
+                let downloader = HttpDownloader::new().map_err(Errors::LibErrors)?;
+                media_api = MediaAPI::new(api, &path, Box::new(downloader));
+                &mut media_api
+            }
+            None => api,
+        };
+        // on top of the backup and the media, so tweets they fail are stored as failed
+        let mut store_api;
+        let api: &mut dyn TwitterAPI = match self.settings.store_path.clone() {
+            Some(path) => {
+                let store = JsonLinesStore::open(&path).map_err(Errors::LibErrors)?;
+                store_api = StoreAPI::new(api, Box::new(store));
+                &mut store_api
+            }
+            None => api,
+        };
+
+        let action = self.action.as_deref_mut();
+        // the retry queue and the export see every tweet before the observer given to the run
+        let retry_queue_path = self.settings.retry_queue_path.clone();
+        let mut export = match self.settings.export_path.clone() {
+            Some(path) if !self.settings.dry_run => {
+                Some(CsvExport::create(&path).map_err(Errors::LibErrors)?)
+            }
+            _ => None,
+        };
+        let mut recording_observer;
+        let observer: Option<&mut TweetObserver> = if retry_queue_path.is_some() || export.is_some()
+        {
+            let mut user_observer = self.observer.take();
+            recording_observer = move |tweet: &Tweet, outcome: Outcome| {
+                if let (Some(path), Outcome::Failed(reason)) = (&retry_queue_path, &outcome) {
+                    if let Err(e) = retry_queue::append(path, tweet.id, reason) {
+                        warn!("{}", e);
+                    }
+                }
+                if let Some(export) = export.as_mut() {
+                    export.record(tweet, &outcome);
+                }
+                if let Some(observer) = user_observer.as_mut() {
+                    observer(tweet, outcome);
+                }
+            };
+            Some(&mut recording_observer)
+        } else {
+            self.observer.as_deref_mut()
+        };
+        if self.settings.dry_run {
+            info!("Dry run, no tweet will be modified");
+            process(&mut DryRunAPI::new(api), &self.settings, action, observer)
+        } else {
+            process(api, &self.settings, action, observer)
+        }
+    }
+
+    /// Pages through the selected timelines without modifying any tweet, and returns the summary
+    /// along with the number of tweets retrieved. Every tweet is counted, regardless of its age or
+    /// the filters, so we can measure how fast we get through the timelines.
+    ///
+    /// # Impure
+    ///
+    /// - Multiple requests to Twitter API
+    ///
+    /// # Errors
+    ///
+    /// - Errors while retrieving the timelines
+    pub fn scan(mut self, api: &mut dyn TwitterAPI) -> Result<(RunSummary, usize), Errors> {
+        select_every_tweet(&mut self.settings);
+
+        let scanned = Cell::new(0);
+        let mut count = |_: &mut dyn TwitterAPI, _: &Tweet| {
+            scanned.set(scanned.get() + 1);
+            Ok(())
+        };
+        let summary = clear_user_timelines(api, &self.settings, Some(&mut count), None)?;
+        Ok((summary, scanned.get()))
+    }
+
+    /// Selects the tweets to erase as `execute` does, but returns them instead of modifying them,
+    /// along with the summary
+    ///
+    /// # Impure
+    ///
+    /// - Multiple requests to Twitter API
+    ///
+    /// # Errors
+    ///
+    /// - Errors while retrieving the timelines
+    pub fn review(self, api: &mut dyn TwitterAPI) -> Result<(RunSummary, Vec<Tweet>), Errors> {
+        let mut selected = Vec::new();
+        let mut collect = |_: &mut dyn TwitterAPI, tweet: &Tweet| {
+            selected.push(tweet.clone());
+            Ok(())
+        };
+        let summary = clear_user_timelines(api, &self.settings, Some(&mut collect), None)?;
+        Ok((summary, selected))
+    }
+
+    /// Pages through the user timeline without modifying any tweet, and compares the tweets it
+    /// returns with those posted by the user. The timeline only returns the latest tweets, so if
+    /// many are missing we warn that older tweets can only be erased from the archive
+    ///
+    /// # Impure
+    ///
+    /// - Multiple requests to Twitter API
+    ///
+    /// # Errors
+    ///
+    /// - Errors while retrieving the timeline or the details of the user
+    pub fn reconcile(
+        mut self,
+        api: &mut dyn TwitterAPI,
+    ) -> Result<(RunSummary, Reconciliation), Errors> {
+        select_every_tweet(&mut self.settings);
+        self.settings.timelines = vec![TimelineKind::User];
+
+        let reachable = Cell::new(0);
+        let mut count = |_: &mut dyn TwitterAPI, _: &Tweet| {
+            reachable.set(reachable.get() + 1);
+            Ok(())
+        };
+        let summary = clear_user_timelines(api, &self.settings, Some(&mut count), None)?;
+        let reconciliation = Reconciliation {
+            reachable: reachable.get(),
+            posted: api.get_statuses_count().map_err(Errors::APIErrors)?,
+        };
+
+        match reconciliation.warning() {
+            Some(warning) => warn!("{}", warning),
+            None => info!(
+                "Timeline API exposes {} of {} tweets",
+                reconciliation.reachable, reconciliation.posted
+            ),
+        }
+        Ok((summary, reconciliation))
+    }
+
+    /// Pages through the user timeline without modifying any tweet, and counts its tweets by age
+    /// to help choosing `preserve_days`. The histogram is also logged
+    ///
+    /// # Impure
+    ///
+    /// - Multiple requests to Twitter API
+    ///
+    /// # Errors
+    ///
+    /// - Errors while retrieving the timeline
+    pub fn age_histogram(mut self, api: &mut dyn TwitterAPI) -> Result<AgeHistogram, Errors> {
+        select_every_tweet(&mut self.settings);
+        self.settings.timelines = vec![TimelineKind::User];
+
+        let now = Utc::now();
+        let histogram = RefCell::new(AgeHistogram::default());
+        let mut bucket = |_: &mut dyn TwitterAPI, tweet: &Tweet| {
+            histogram.borrow_mut().record(tweet.created_at, now);
+            Ok(())
+        };
+        clear_user_timelines(api, &self.settings, Some(&mut bucket), None)?;
+
+        let histogram = histogram.into_inner();
+        info!("{}", histogram);
+        Ok(histogram)
+    }
+}
+
+// disables every rule that preserves tweets, so a read-only action sees all the tweets retrieved
+#[allow(deprecated)]
+fn select_every_tweet(settings: &mut Settings) {
+    settings.preserve_days = Duration::zero();
+    settings.minimum_age_days = 0;
+    settings.filters = Filters::default();
+    settings.timeline_rules.clear();
+    settings.date_window = None;
+    settings.delete_range = None;
+    settings.preserve_pinned = false;
+    settings.preserve_active_threads = false;
+    settings.max_empty_pages = None;
+    settings.max_deletions = None;
+    settings.oldest_first = false;
+    settings.min_replies = None;
+    settings.delete_dms = false;
+    settings.keep_ids_file = None;
+    settings.keep_latest = 0;
+    settings.keep_self_likes = false;
+    settings.preserve_self_threads = false;
+}
+
+impl<'a> Default for Run<'a> {
+    fn default() -> Self {
+        Run::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Outcome, Run};
+    use crate::action_log::{self, ActionLogEntry};
+    use crate::api::{APIError, EraseOutcome, TestAPI};
+    use crate::config::{Settings, TimelineKind};
+    use crate::histogram::AgeHistogram;
+    use crate::last_run;
+    use crate::tests::{sample_tweet, sample_user};
+    use crate::{read_ids, Errors, RunSummary};
+    use chrono::{Duration, Utc};
+    use std::collections::VecDeque;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn builds_settings() {
+        let run = Run::new()
+            .preserve_days(7)
+            .dry_run(true)
+            .concurrency(3)
+            .timelines(vec![TimelineKind::Likes]);
+
+        let settings = run.settings();
+        assert_eq!(settings.preserve_days, Duration::days(7));
+        assert!(settings.dry_run);
+        assert_eq!(settings.concurrency, 3);
+        assert_eq!(settings.timelines, vec![TimelineKind::Likes]);
+    }
+
+    #[test]
+    fn processes_selected_timelines() {
+        let mut api = TestAPI {
+            ..Default::default()
+        };
+
+        let summary = Run::new()
+            .timelines(vec![TimelineKind::Likes])
+            .execute(&mut api)
+            .unwrap();
+
+        let likes = RunSummary {
+            api_calls: 1,
+            ..Default::default()
+        };
+        let mut expected = RunSummary::default();
+        expected.add_timeline("Likes Timeline", &likes);
+        assert_eq!(summary, expected);
+        assert_eq!(
+            api.methods_called_in_order,
+            vec!["likes_timeline_next_page"]
+        );
+    }
+
+    #[test]
+    fn observes_outcome_of_each_tweet() {
+        let err = APIError::ErasureError(String::from("error"));
+        let cases = vec![
+            (
+                Ok(EraseOutcome::Deleted),
+                vec![(1, Outcome::Deleted), (2, Outcome::Deleted)],
+            ),
+            (
+                Ok(EraseOutcome::Skipped(String::from("not owned"))),
+                vec![
+                    (1, Outcome::Skipped(String::from("not owned"))),
+                    (2, Outcome::Skipped(String::from("not owned"))),
+                ],
+            ),
+            // the run stops at the first failure
+            (
+                Err(err.clone()),
+                vec![(
+                    1,
+                    Outcome::Failed(Errors::APIErrors(err.for_tweet(1)).to_string()),
+                )],
+            ),
+        ];
+
+        for (erase_tweet_answer, expected) in cases {
+            let tweets = (1..=2)
+                .map(|id| {
+                    let mut tweet = sample_tweet(40);
+                    tweet.id = id;
+                    tweet
+                })
+                .collect();
+            let mut api = TestAPI {
+                user_timeline_pages: VecDeque::from(vec![Ok(tweets)]),
+                erase_tweet_answer,
+                ..Default::default()
+            };
+
+            let mut observed = Vec::new();
+            let _ = Run::new()
+                .timelines(vec![TimelineKind::User])
+                .observer(|tweet, outcome| observed.push((tweet.id, outcome)))
+                .execute(&mut api);
+
+            assert_eq!(observed, expected);
+        }
+    }
+
+    #[test]
+    fn erases_listed_tweets_whatever_their_age() {
+        let mut liked = sample_tweet(0);
+        liked.id = 1;
+        liked.favorited = Some(true);
+        let mut old = sample_tweet(400);
+        old.id = 2;
+        let mut api = TestAPI {
+            tweets_by_id: vec![(1, liked), (2, old)].into_iter().collect(),
+            ..Default::default()
+        };
+        let ids = read_ids("1\nnot an id\n3\n2\n".as_bytes());
+
+        let summary = Run::new().erase_ids(&mut api, &ids).unwrap();
+
+        assert_eq!(summary.deleted, 2);
+        assert_eq!(summary.unliked, 1);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(api.erased_tweet_ids, vec![1, 2]);
+        assert_eq!(
+            api.methods_called_in_order,
+            vec![
+                "get_tweet",
+                "unlike_tweet",
+                "erase_tweet",
+                "get_tweet",
+                "get_tweet",
+                "erase_tweet"
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_counts_tweets_without_modifying_them() {
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![
+                Ok(vec![sample_tweet(40); 25]),
+                Ok(vec![sample_tweet(0); 3]),
+            ]),
+            likes_timeline_pages: VecDeque::from(vec![Ok(vec![sample_tweet(40); 2])]),
+            ..Default::default()
+        };
+
+        let (summary, scanned) = Run::new().preserve_days(90).scan(&mut api).unwrap();
+
+        assert_eq!(scanned, 30);
+        assert_eq!(summary.api_calls, 5);
+        assert!(api
+            .methods_called_in_order
+            .iter()
+            .all(|m| m.ends_with("_next_page")));
+    }
+
+    #[test]
+    fn scan_counts_the_latest_tweets_too() {
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok(vec![sample_tweet(40); 5])]),
+            ..Default::default()
+        };
+        let run = Run {
+            settings: Settings {
+                keep_latest: 3,
+                ..Default::default()
+            },
+            ..Run::new()
+        };
+
+        let (_, scanned) = run.scan(&mut api).unwrap();
+
+        assert_eq!(scanned, 5);
+    }
+
+    #[test]
+    fn scan_counts_self_likes_and_self_threads_too() {
+        // a thread of the user with a recent reply, and a like of one of their own tweets
+        let own_tweet = |id, days_ago, parent| {
+            let mut tweet = sample_tweet(days_ago);
+            tweet.id = id;
+            tweet.in_reply_to_status_id = parent;
+            tweet.user = Some(Box::new(sample_user(7)));
+            tweet
+        };
+        let mut liked = own_tweet(4, 40, None);
+        liked.favorited = Some(true);
+        let mut api = TestAPI {
+            user_id_answer: Some(7),
+            user_timeline_pages: VecDeque::from(vec![Ok(vec![
+                own_tweet(3, 0, Some(2)),
+                own_tweet(2, 40, Some(1)),
+                own_tweet(1, 40, None),
+            ])]),
+            likes_timeline_pages: VecDeque::from(vec![Ok(vec![liked])]),
+            ..Default::default()
+        };
+        let run = Run {
+            settings: Settings {
+                keep_self_likes: true,
+                preserve_self_threads: true,
+                ..Default::default()
+            },
+            ..Run::new()
+        };
+
+        let (_, scanned) = run.scan(&mut api).unwrap();
+
+        assert_eq!(scanned, 4);
+    }
+
+    #[test]
+    fn warns_when_the_timeline_misses_posted_tweets() {
+        for (posted, warns) in &[(18402, true), (3, false)] {
+            let mut api = TestAPI {
+                user_timeline_pages: VecDeque::from(vec![
+                    Ok(vec![sample_tweet(1), sample_tweet(400)]),
+                    Ok(vec![sample_tweet(2000)]),
+                ]),
+                get_statuses_count_answer: Ok(*posted),
+                ..Default::default()
+            };
+
+            let (summary, reconciliation) = Run::new().reconcile(&mut api).unwrap();
+
+            assert_eq!(reconciliation.reachable, 3);
+            assert_eq!(reconciliation.posted, *posted);
+            assert_eq!(reconciliation.warning().is_some(), *warns);
+            assert_eq!(summary.deleted, 0);
+            assert!(api.erased_tweet_ids.is_empty());
+        }
+    }
+
+    #[test]
+    fn counts_tweets_of_the_user_timeline_by_age() {
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![
+                Ok(vec![sample_tweet(1), sample_tweet(40), sample_tweet(100)]),
+                Ok(vec![sample_tweet(400), sample_tweet(800)]),
+            ]),
+            likes_timeline_pages: VecDeque::from(vec![Ok(vec![sample_tweet(1)])]),
+            ..Default::default()
+        };
+
+        let histogram = Run::new()
+            .preserve_days(90)
+            .age_histogram(&mut api)
+            .unwrap();
+
+        let expected = AgeHistogram {
+            up_to_30_days: 1,
+            up_to_90_days: 1,
+            up_to_365_days: 1,
+            older: 2,
+        };
+        assert_eq!(histogram, expected);
+        assert!(api
+            .methods_called_in_order
+            .iter()
+            .all(|m| m == "user_timeline_next_page"));
+    }
+
+    #[test]
+    fn skips_tweets_in_the_action_log() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_run_action_log_{}.jsonl",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let previous: Vec<String> = [1, 2]
+            .iter()
+            .map(|id| {
+                let entry = ActionLogEntry {
+                    id: *id,
+                    action: String::from("deleted"),
+                    at: Utc::now(),
+                };
+                serde_json::to_string(&entry).unwrap()
+            })
+            .collect();
+        fs::write(path, format!("{}\n", previous.join("\n"))).unwrap();
+        let tweets = (1..=3)
+            .map(|id| {
+                let mut tweet = sample_tweet(40);
+                tweet.id = id;
+                tweet
+            })
+            .collect();
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok(tweets)]),
+            ..Default::default()
+        };
+
+        let summary = Run::new()
+            .timelines(vec![TimelineKind::User])
+            .action_log(path, true)
+            .execute(&mut api);
+        let logged = action_log::handled_ids(path);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(summary.map(|s| (s.deleted, s.skipped)), Ok((1, 2)));
+        assert_eq!(api.erased_tweet_ids, vec![3]);
+        assert_eq!(logged.map(|ids| ids.len()), Ok(3));
+    }
+
+    #[test]
+    fn only_erases_tweets_that_became_old_since_the_last_run() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_run_last_run_{}.txt",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let last_run = Utc::now() - Duration::days(5);
+        last_run::write_to(path, last_run).unwrap();
+        let tweets = [(1, 10), (2, 33), (3, 40), (4, 50)]
+            .iter()
+            .map(|(id, days_ago)| {
+                let mut tweet = sample_tweet(*days_ago);
+                tweet.id = *id;
+                tweet
+            })
+            .collect();
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok(tweets)]),
+            ..Default::default()
+        };
+
+        let before = Utc::now();
+        let summary = Run::new()
+            .preserve_days(30)
+            .timelines(vec![TimelineKind::User])
+            .last_run_file(path)
+            .execute(&mut api);
+        let written = last_run::read_from(path);
+        fs::remove_file(path).unwrap();
+
+        // only tweets posted between 35 and 30 days ago weren't old enough for the last run
+        assert_eq!(summary.map(|s| s.deleted), Ok(1));
+        assert_eq!(api.erased_tweet_ids, vec![2]);
+        assert!(written.unwrap().unwrap() >= before);
+    }
+
+    #[test]
+    fn keeps_the_last_run_if_stopped_by_max_deletions() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_run_capped_last_run_{}.txt",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let tweets = (1..=3)
+            .map(|id| {
+                let mut tweet = sample_tweet(40);
+                tweet.id = id;
+                tweet
+            })
+            .collect();
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok(tweets)]),
+            ..Default::default()
+        };
+
+        let summary = Run::new()
+            .preserve_days(30)
+            .timelines(vec![TimelineKind::User])
+            .max_deletions(1)
+            .last_run_file(path)
+            .execute(&mut api)
+            .unwrap();
+        let written = last_run::read_from(path);
+
+        // the tweets left behind must be looked at again by the next run
+        assert_eq!(summary.deleted, 1);
+        assert!(summary.stopped_early);
+        assert_eq!(written, Ok(None));
+    }
+
+    #[test]
+    fn never_touches_tweets_listed_to_keep() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_run_keep_ids_{}.txt",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        fs::write(path, "2\n4\n").unwrap();
+        let tweet_with_id = |id| {
+            let mut tweet = sample_tweet(40);
+            tweet.id = id;
+            tweet
+        };
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok((1..=3).map(tweet_with_id).collect())]),
+            tweets_by_id: (4..=5).map(|id| (id, tweet_with_id(id))).collect(),
+            ..Default::default()
+        };
+
+        let summary = Run::new()
+            .timelines(vec![TimelineKind::User])
+            .keep_ids_file(path)
+            .execute(&mut api);
+        let listed = Run::new().keep_ids_file(path).erase_ids(&mut api, &[4, 5]);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(summary.map(|s| (s.deleted, s.skipped)), Ok((2, 1)));
+        assert_eq!(listed.map(|s| (s.deleted, s.skipped)), Ok((1, 1)));
+        assert_eq!(api.erased_tweet_ids, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn queues_tweets_that_failed() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_run_retry_queue_{}.txt",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let err = APIError::ErasureError(String::from("error"));
+        let mut tweet = sample_tweet(40);
+        tweet.id = 1;
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok(vec![tweet])]),
+            erase_tweet_answer: Err(err.clone()),
+            ..Default::default()
+        };
+
+        let _ = Run::new()
+            .timelines(vec![TimelineKind::User])
+            .retry_queue(path, false)
+            .execute(&mut api);
+        let queue = fs::read_to_string(path);
+        fs::remove_file(path).unwrap();
+
+        let reason = Errors::APIErrors(err.for_tweet(1)).to_string();
+        assert_eq!(queue.unwrap(), format!("1 # {}\n", reason));
+    }
+
+    #[test]
+    fn erases_queued_tweets_first() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_run_retry_first_{}.txt",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        fs::write(path, "7 # rate limited\n").unwrap();
+        let mut queued = sample_tweet(0);
+        queued.id = 7;
+        let mut old = sample_tweet(40);
+        old.id = 1;
+        let mut api = TestAPI {
+            tweets_by_id: vec![(7, queued)].into_iter().collect(),
+            user_timeline_pages: VecDeque::from(vec![Ok(vec![old])]),
+            ..Default::default()
+        };
+
+        let summary = Run::new()
+            .timelines(vec![TimelineKind::User])
+            .retry_queue(path, true)
+            .execute(&mut api);
+        let cleared = !std::path::Path::new(path).exists();
+        let _ = fs::remove_file(path);
+
+        assert_eq!(summary.map(|s| s.deleted), Ok(2));
+        assert_eq!(api.erased_tweet_ids, vec![7, 1]);
+        assert!(cleared);
+    }
+
+    #[test]
+    fn review_returns_tweets_to_erase_without_modifying_them() {
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok(vec![
+                sample_tweet(40),
+                sample_tweet(1),
+                sample_tweet(50),
+            ])]),
+            ..Default::default()
+        };
+
+        let (_, tweets) = Run::new()
+            .preserve_days(30)
+            .timelines(vec![TimelineKind::User])
+            .review(&mut api)
+            .unwrap();
+
+        assert_eq!(tweets.len(), 2);
+        assert_eq!(
+            api.methods_called_in_order,
+            vec!["user_timeline_next_page", "user_timeline_next_page"]
+        );
+    }
+
+    #[test]
+    fn dry_run_does_not_modify_tweets() {
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok(vec![sample_tweet(40); 2])]),
+            ..Default::default()
+        };
+
+        let summary = Run::new()
+            .timelines(vec![TimelineKind::User])
+            .dry_run(true)
+            .execute(&mut api)
+            .unwrap();
+
+        assert_eq!(summary.deleted, 2);
+        assert_eq!(
+            api.methods_called_in_order,
+            vec!["user_timeline_next_page", "user_timeline_next_page"]
+        );
+    }
+}
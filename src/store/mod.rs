@@ -0,0 +1,327 @@
+use crate::api::{APIError, AccountInfo, DirectMessage, EraseOutcome, TwitterAPI};
+use crate::pacing::RateLimitBudget;
+use chrono::prelude::*;
+use egg_mode::tweet::Tweet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+
+/// Entry of the store, recording a tweet we retrieved or a request we sent about it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredTweet {
+    pub id: u64,
+    pub created_at: DateTime<Utc>,
+    pub text: String,
+    pub favorite_count: i32,
+    pub retweet_count: i32,
+    /// One of `seen`, `unliked`, `unretweeted`, `deleted`, `skipped` or `failed`
+    pub action: String,
+    /// Why the tweet was skipped, or the error if it failed
+    pub reason: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl StoredTweet {
+    /// Builds the entry of the given tweet, with the action taken at `at`
+    pub fn of(
+        tweet: &Tweet,
+        action: &str,
+        reason: Option<String>,
+        at: DateTime<Utc>,
+    ) -> StoredTweet {
+        StoredTweet {
+            id: tweet.id,
+            created_at: tweet.created_at,
+            text: tweet.text.clone(),
+            favorite_count: tweet.favorite_count,
+            retweet_count: tweet.retweet_count,
+            action: String::from(action),
+            reason,
+            at,
+        }
+    }
+}
+
+/// Place where we keep a record of every tweet we see and what we do with it, to report on it
+/// without going back to Twitter
+pub trait TweetStore {
+    /// Adds an entry to the store
+    fn save(&mut self, entry: &StoredTweet) -> Result<(), String>;
+
+    /// Returns every entry in the store, in the order they were saved
+    fn entries(&self) -> Result<Vec<StoredTweet>, String>;
+}
+
+/// Returns the last entry of each tweet, so the action taken on it the last time we saw it
+pub fn latest_entries(entries: Vec<StoredTweet>) -> BTreeMap<u64, StoredTweet> {
+    entries.into_iter().map(|entry| (entry.id, entry)).collect()
+}
+
+/// `TweetStore` that appends the entries to a file, as lines of JSON, like the backup
+pub struct JsonLinesStore {
+    path: String,
+    file: File,
+}
+
+impl JsonLinesStore {
+    /// Opens the store at `path`, appending to it if it exists
+    pub fn open(path: &str) -> Result<JsonLinesStore, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Couldn't open store {}: {}", path, e))?;
+        Ok(JsonLinesStore {
+            path: String::from(path),
+            file,
+        })
+    }
+}
+
+impl TweetStore for JsonLinesStore {
+    fn save(&mut self, entry: &StoredTweet) -> Result<(), String> {
+        serde_json::to_string(entry)
+            .map_err(|e| e.to_string())
+            .and_then(|line| writeln!(self.file, "{}", line).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Couldn't write to store {}: {}", self.path, e))
+    }
+
+    fn entries(&self) -> Result<Vec<StoredTweet>, String> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Couldn't open store {}: {}", self.path, e)),
+        };
+
+        let mut entries = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("Couldn't read store {}: {}", self.path, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line).map_err(|e| {
+                format!("Invalid line {} in store {}: {}", number + 1, self.path, e)
+            })?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// Wrapper over a `TwitterAPI` that saves in a `TweetStore` every tweet of the timelines we page
+/// through, and the outcome of every unlike, unretweet and erase request. Failing to save an entry
+/// doesn't stop the run.
+pub struct StoreAPI<'r> {
+    api: &'r mut dyn TwitterAPI,
+    store: Box<dyn TweetStore>,
+}
+
+impl<'r> StoreAPI<'r> {
+    pub fn new(api: &'r mut dyn TwitterAPI, store: Box<dyn TweetStore>) -> StoreAPI<'r> {
+        StoreAPI { api, store }
+    }
+
+    fn record(&mut self, tweet: &Tweet, action: &str, reason: Option<String>) {
+        let entry = StoredTweet::of(tweet, action, reason, Utc::now());
+        if let Err(e) = self.store.save(&entry) {
+            warn!("{}", e);
+        }
+    }
+
+    fn record_page(&mut self, page: Result<Vec<Tweet>, APIError>) -> Result<Vec<Tweet>, APIError> {
+        if let Ok(tweets) = &page {
+            for tweet in tweets {
+                self.record(tweet, "seen", None);
+            }
+        }
+        page
+    }
+
+    fn record_request<T>(&mut self, tweet: &Tweet, action: &str, result: &Result<T, APIError>) {
+        match result {
+            Ok(_) => self.record(tweet, action, None),
+            Err(e) => self.record(tweet, "failed", Some(e.to_string())),
+        }
+    }
+
+    fn record_erasure(&mut self, tweet: &Tweet, result: &Result<EraseOutcome, APIError>) {
+        match result {
+            Ok(EraseOutcome::Skipped(reason)) => {
+                self.record(tweet, "skipped", Some(reason.clone()))
+            }
+            _ => self.record_request(tweet, "deleted", result),
+        }
+    }
+}
+
+impl<'r> TwitterAPI for StoreAPI<'r> {
+    fn validate_token(&mut self) -> Result<(), APIError> {
+        self.api.validate_token()
+    }
+
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError> {
+        self.api.get_user_id(screen_name)
+    }
+
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        self.api.get_account_info(screen_name)
+    }
+
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.api.get_statuses_count()
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        self.api.get_tweet(id)
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        self.api.rate_limit_status()
+    }
+
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        self.api.reply_count(tweet)
+    }
+
+    fn api_calls(&self) -> usize {
+        self.api.api_calls()
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.api.token_user_id()
+    }
+
+    fn tweet_count(&self) -> Option<u64> {
+        self.api.tweet_count()
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.api.pinned_tweet_id()
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        self.api.user_id()
+    }
+
+    fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        let page = self.api.user_timeline_next_page();
+        self.record_page(page)
+    }
+
+    fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        let page = self.api.likes_timeline_next_page();
+        self.record_page(page)
+    }
+
+    fn unlike_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        let result = self.api.unlike_tweet(tweet);
+        self.record_request(tweet, "unliked", &result);
+        result
+    }
+
+    fn unretweet_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        let result = self.api.unretweet_tweet(tweet);
+        self.record_request(tweet, "unretweeted", &result);
+        result
+    }
+
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
+        let result = self.api.erase_tweet(tweet);
+        self.record_erasure(tweet, &result);
+        result
+    }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        self.api.direct_messages_next_page()
+    }
+
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError> {
+        self.api.erase_dm(dm)
+    }
+
+    fn maintain_tweets(
+        &mut self,
+        tweets: &[&Tweet],
+        concurrency: usize,
+    ) -> Vec<Result<EraseOutcome, APIError>> {
+        let results = self.api.maintain_tweets(tweets, concurrency);
+        for (tweet, result) in tweets.iter().zip(&results) {
+            self.record_erasure(tweet, result);
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{latest_entries, JsonLinesStore, StoreAPI, TweetStore};
+    use crate::api::{APIError, TestAPI, TwitterAPI};
+    use crate::tests::sample_tweet;
+    use std::collections::VecDeque;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn records_tweets_seen_and_the_actions_taken() {
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_store_{}.jsonl",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let tweets: Vec<_> = (1..=3)
+            .map(|id| {
+                let mut tweet = sample_tweet(40);
+                tweet.id = id;
+                tweet
+            })
+            .collect();
+        let mut api = TestAPI {
+            user_timeline_pages: VecDeque::from(vec![Ok(tweets.clone())]),
+            ..Default::default()
+        };
+
+        {
+            let store = JsonLinesStore::open(path).unwrap();
+            let mut store_api = StoreAPI::new(&mut api, Box::new(store));
+            store_api.user_timeline_next_page().unwrap();
+            store_api.erase_tweet(&tweets[0]).unwrap();
+            store_api.unlike_tweet(&tweets[1]).unwrap();
+        }
+        api.erase_tweet_answer = Err(APIError::ErasureError(String::from("error")));
+        {
+            let store = JsonLinesStore::open(path).unwrap();
+            let mut store_api = StoreAPI::new(&mut api, Box::new(store));
+            store_api.maintain_tweets(&[&tweets[1]], 2);
+        }
+        let entries = JsonLinesStore::open(path).unwrap().entries().unwrap();
+        fs::remove_file(path).unwrap();
+
+        let actions: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.id, entry.action.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (1, "seen"),
+                (2, "seen"),
+                (3, "seen"),
+                (1, "deleted"),
+                (2, "unliked"),
+                (2, "failed"),
+            ]
+        );
+        assert_eq!(entries[0].text, tweets[0].text);
+        assert_eq!(
+            entries[5].reason,
+            Some(String::from(
+                "Tweet #2: Failure removing link between tweet and user: error"
+            ))
+        );
+
+        let latest = latest_entries(entries);
+        let latest_actions: Vec<_> = latest.values().map(|e| e.action.as_str()).collect();
+        assert_eq!(latest_actions, vec!["deleted", "failed", "seen"]);
+    }
+}
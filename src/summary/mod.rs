@@ -0,0 +1,602 @@
+use crate::api::{APIError, AccountInfo, DirectMessage, EraseOutcome, TwitterAPI};
+use crate::estimate::Progress;
+use crate::pacing::RateLimitBudget;
+use chrono::prelude::*;
+use egg_mode::tweet::Tweet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+
+/// Counts of what happened to the tweets processed during a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    /// Tweets retrieved from the timelines, whether they were selected for erasure or not
+    #[serde(default)]
+    pub scanned: usize,
+    pub deleted: usize,
+    pub skipped: usize,
+    pub unliked: usize,
+    pub unretweeted: usize,
+    pub failed: usize,
+    pub deleted_dms: usize,
+    /// Requests sent to Twitter API
+    pub api_calls: usize,
+    /// True if the run was stopped before processing all the timelines
+    pub cancelled: bool,
+    /// True if we stopped paging through a timeline before its end, due to `max_deletions` or
+    /// `max_empty_pages`, so older tweets may be left to erase
+    #[serde(default)]
+    pub stopped_early: bool,
+    /// When the run started and finished, only set for the run of a whole account
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Counts of each timeline processed, by name. Not kept when erasing from the oldest tweet,
+    /// as the tweets of all the timelines are erased together
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub timelines: BTreeMap<String, RunSummary>,
+    /// Error of the last failed request for each tweet that failed, by tweet id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub failures: BTreeMap<u64, String>,
+}
+
+impl RunSummary {
+    /// Adds the counts of `other` to this summary
+    pub fn merge(&mut self, other: &RunSummary) {
+        self.scanned += other.scanned;
+        self.deleted += other.deleted;
+        self.skipped += other.skipped;
+        self.unliked += other.unliked;
+        self.unretweeted += other.unretweeted;
+        self.failed += other.failed;
+        self.deleted_dms += other.deleted_dms;
+        self.api_calls += other.api_calls;
+        self.cancelled = self.cancelled || other.cancelled;
+        self.stopped_early = self.stopped_early || other.stopped_early;
+        self.started_at = earliest(self.started_at, other.started_at);
+        self.finished_at = latest(self.finished_at, other.finished_at);
+        for (name, timeline) in &other.timelines {
+            self.timelines
+                .entry(name.clone())
+                .or_default()
+                .merge(timeline);
+        }
+        self.failures
+            .extend(other.failures.iter().map(|(id, e)| (*id, e.clone())));
+    }
+
+    /// Adds the counts of the timeline `name` to this summary, keeping them apart as well
+    pub fn add_timeline(&mut self, name: &str, timeline: &RunSummary) {
+        self.merge(timeline);
+        // failures are listed once, with the totals
+        let counts = RunSummary {
+            failures: BTreeMap::new(),
+            ..timeline.clone()
+        };
+        self.timelines
+            .entry(String::from(name))
+            .or_default()
+            .merge(&counts);
+    }
+
+    /// Writes this summary to the given file, as CSV if the path ends in `.csv` or as JSON otherwise
+    pub fn write_to(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("Couldn't create {}: {}", path, e))?;
+        let result = if is_csv(path) {
+            // a CSV row can't nest the counts of each timeline or the failures, so only the totals
+            // are written
+            let totals = RunSummary {
+                timelines: BTreeMap::new(),
+                failures: BTreeMap::new(),
+                ..self.clone()
+            };
+            let mut writer = csv::Writer::from_writer(file);
+            writer
+                .serialize(totals)
+                .and_then(|_| writer.flush().map_err(csv::Error::from))
+                .map_err(|e| e.to_string())
+        } else {
+            serde_json::to_writer_pretty(file, self).map_err(|e| e.to_string())
+        };
+        result.map_err(|e| format!("Couldn't write summary to {}: {}", path, e))
+    }
+
+    /// Reads a summary written by `write_to` from the given file
+    pub fn read_from(path: &str) -> Result<RunSummary, String> {
+        let file = File::open(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+        let result = if is_csv(path) {
+            match csv::Reader::from_reader(file).deserialize().next() {
+                Some(row) => row.map_err(|e| e.to_string()),
+                None => Err(String::from("the file has no rows")),
+            }
+        } else {
+            serde_json::from_reader(file).map_err(|e| e.to_string())
+        };
+        result.map_err(|e| format!("Couldn't read summary from {}: {}", path, e))
+    }
+
+    /// Returns the counters of this summary in Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let last_run = self.finished_at.unwrap_or_else(Utc::now).timestamp();
+        let metrics = [
+            (
+                "tweets_deleted_total",
+                "counter",
+                "Tweets deleted",
+                self.deleted as i64,
+            ),
+            (
+                "tweets_skipped_total",
+                "counter",
+                "Tweets skipped",
+                self.skipped as i64,
+            ),
+            (
+                "likes_removed_total",
+                "counter",
+                "Tweets unliked",
+                self.unliked as i64,
+            ),
+            (
+                "retweets_removed_total",
+                "counter",
+                "Tweets unretweeted",
+                self.unretweeted as i64,
+            ),
+            (
+                "failures_total",
+                "counter",
+                "Requests that failed",
+                self.failed as i64,
+            ),
+            (
+                "direct_messages_deleted_total",
+                "counter",
+                "Direct messages deleted",
+                self.deleted_dms as i64,
+            ),
+            (
+                "api_calls_total",
+                "counter",
+                "Requests sent to Twitter API",
+                self.api_calls as i64,
+            ),
+            (
+                "cancelled",
+                "gauge",
+                "1 if the run was stopped before completion",
+                self.cancelled as i64,
+            ),
+            (
+                "last_run_timestamp_seconds",
+                "gauge",
+                "When the run finished, in seconds since the epoch",
+                last_run,
+            ),
+        ];
+        metrics
+            .iter()
+            .map(|(name, kind, help, value)| {
+                format!(
+                    "# HELP twitter_privacy_{name} {help}\n# TYPE twitter_privacy_{name} {kind}\ntwitter_privacy_{name} {value}\n",
+                    name = name,
+                    help = help,
+                    kind = kind,
+                    value = value
+                )
+            })
+            .collect()
+    }
+
+    /// Writes this summary to the given file in Prometheus text exposition format, so the textfile
+    /// collector of node_exporter can pick it up
+    pub fn write_metrics_to(&self, path: &str) -> Result<(), String> {
+        // the collector may read the file at any time, so we replace it at once
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, self.to_prometheus())
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| format!("Couldn't write metrics to {}: {}", path, e))
+    }
+}
+
+fn is_csv(path: &str) -> bool {
+    path.to_lowercase().ends_with(".csv")
+}
+
+fn earliest(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        _ => a.or(b),
+    }
+}
+
+fn latest(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        _ => a.or(b),
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} deleted, {} skipped, {} unliked, {} unretweeted, {} failed, {} API calls",
+            self.deleted, self.skipped, self.unliked, self.unretweeted, self.failed, self.api_calls
+        )?;
+        if self.deleted_dms > 0 {
+            write!(f, ", {} direct messages deleted", self.deleted_dms)?;
+        }
+        if self.scanned > 0 {
+            write!(f, ", {} tweets scanned", self.scanned)?;
+        }
+        if self.cancelled {
+            write!(f, " (cancelled before completion)")?;
+        }
+        if self.stopped_early {
+            write!(f, " (stopped before the end of the timelines)")?;
+        }
+        for (name, timeline) in &self.timelines {
+            write!(f, "\n  {}: {}", name, timeline)?;
+        }
+        for (id, error) in &self.failures {
+            write!(f, "\n  Failed tweet #{}: {}", id, error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Wrapper over a `TwitterAPI` that records the outcome of every unlike, unretweet and erase
+/// request in a `RunSummary`, so the processing functions don't need to keep count themselves.
+pub struct RecordingAPI<'r> {
+    api: &'r mut dyn TwitterAPI,
+    pub summary: RunSummary,
+    progress: Option<&'r Progress>,
+}
+
+impl<'r> RecordingAPI<'r> {
+    pub fn new(api: &'r mut dyn TwitterAPI) -> RecordingAPI<'r> {
+        RecordingAPI {
+            api,
+            summary: RunSummary::default(),
+            progress: None,
+        }
+    }
+
+    /// Same as `new`, also counting the deleted tweets in `progress`, which may be shared by
+    /// several timelines
+    pub fn with_progress(api: &'r mut dyn TwitterAPI, progress: &'r Progress) -> RecordingAPI<'r> {
+        RecordingAPI {
+            progress: Some(progress),
+            ..RecordingAPI::new(api)
+        }
+    }
+
+    fn record_erasure(&mut self, tweet: &Tweet, result: &Result<EraseOutcome, APIError>) {
+        match result {
+            Ok(EraseOutcome::Deleted) => {
+                self.summary.deleted += 1;
+                if let Some(progress) = self.progress {
+                    progress.record_deleted();
+                }
+            }
+            Ok(EraseOutcome::Skipped(_)) => self.summary.skipped += 1,
+            Err(e) => self.record_failure(tweet, e),
+        }
+    }
+
+    fn record_failure(&mut self, tweet: &Tweet, error: &APIError) {
+        self.summary.failed += 1;
+        self.summary.failures.insert(tweet.id, error.to_string());
+    }
+}
+
+impl<'r> TwitterAPI for RecordingAPI<'r> {
+    fn validate_token(&mut self) -> Result<(), APIError> {
+        self.api.validate_token()
+    }
+
+    fn get_user_id(&mut self, screen_name: &str) -> Result<u64, APIError> {
+        self.api.get_user_id(screen_name)
+    }
+
+    fn get_account_info(&mut self, screen_name: &str) -> Result<AccountInfo, APIError> {
+        self.api.get_account_info(screen_name)
+    }
+
+    fn get_statuses_count(&mut self) -> Result<u64, APIError> {
+        self.api.get_statuses_count()
+    }
+
+    fn get_tweet(&mut self, id: u64) -> Result<Tweet, APIError> {
+        self.api.get_tweet(id)
+    }
+
+    fn rate_limit_status(&mut self) -> Result<RateLimitBudget, APIError> {
+        self.api.rate_limit_status()
+    }
+
+    fn reply_count(&mut self, tweet: &Tweet) -> Result<u64, APIError> {
+        self.api.reply_count(tweet)
+    }
+
+    fn api_calls(&self) -> usize {
+        self.api.api_calls()
+    }
+
+    fn token_user_id(&self) -> Option<u64> {
+        self.api.token_user_id()
+    }
+
+    fn tweet_count(&self) -> Option<u64> {
+        self.api.tweet_count()
+    }
+
+    fn pinned_tweet_id(&self) -> Option<u64> {
+        self.api.pinned_tweet_id()
+    }
+
+    fn user_id(&self) -> Option<u64> {
+        self.api.user_id()
+    }
+
+    fn user_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.user_timeline_next_page()
+    }
+
+    fn likes_timeline_next_page(&mut self) -> Result<Vec<Tweet>, APIError> {
+        self.api.likes_timeline_next_page()
+    }
+
+    fn unlike_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        let result = self.api.unlike_tweet(tweet);
+        match &result {
+            Ok(_) => self.summary.unliked += 1,
+            Err(e) => self.record_failure(tweet, e),
+        }
+        result
+    }
+
+    fn unretweet_tweet(&mut self, tweet: &Tweet) -> Result<(), APIError> {
+        let result = self.api.unretweet_tweet(tweet);
+        match &result {
+            Ok(_) => self.summary.unretweeted += 1,
+            Err(e) => self.record_failure(tweet, e),
+        }
+        result
+    }
+
+    fn erase_tweet(&mut self, tweet: &Tweet) -> Result<EraseOutcome, APIError> {
+        let result = self.api.erase_tweet(tweet);
+        self.record_erasure(tweet, &result);
+        result
+    }
+
+    fn direct_messages_next_page(&mut self) -> Result<Vec<DirectMessage>, APIError> {
+        self.api.direct_messages_next_page()
+    }
+
+    fn erase_dm(&mut self, dm: &DirectMessage) -> Result<(), APIError> {
+        let result = self.api.erase_dm(dm);
+        match result {
+            Ok(_) => self.summary.deleted_dms += 1,
+            Err(_) => self.summary.failed += 1,
+        }
+        result
+    }
+
+    // the wrapped API may run the requests concurrently, so we count from the outcome of each tweet.
+    // A tweet that was successfully processed had been unliked and unretweeted, if needed
+    fn maintain_tweets(
+        &mut self,
+        tweets: &[&Tweet],
+        concurrency: usize,
+    ) -> Vec<Result<EraseOutcome, APIError>> {
+        let results = self.api.maintain_tweets(tweets, concurrency);
+        for (tweet, result) in tweets.iter().zip(results.iter()) {
+            if result.is_ok() {
+                if tweet.favorited.unwrap_or(false) {
+                    self.summary.unliked += 1;
+                }
+                if tweet.retweeted.unwrap_or(false) {
+                    self.summary.unretweeted += 1;
+                }
+            }
+            self.record_erasure(tweet, result);
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordingAPI, RunSummary};
+    use crate::api::{APIError, EraseOutcome, TestAPI, TwitterAPI};
+    use crate::tests::sample_tweet;
+    use chrono::prelude::*;
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn records_outcome_of_requests() {
+        let mut api = TestAPI {
+            unretweet_tweet_answer: Err(APIError::ErasureError(String::from("error"))),
+            ..Default::default()
+        };
+        let mut recording = RecordingAPI::new(&mut api);
+        let tweet = sample_tweet(1);
+
+        recording.unlike_tweet(&tweet).unwrap();
+        recording.unretweet_tweet(&tweet).unwrap_err();
+        recording.erase_tweet(&tweet).unwrap();
+
+        let mut failures = BTreeMap::new();
+        failures.insert(
+            tweet.id,
+            String::from("Failure removing link between tweet and user: error"),
+        );
+        let expected = RunSummary {
+            deleted: 1,
+            unliked: 1,
+            failed: 1,
+            failures,
+            ..Default::default()
+        };
+        assert_eq!(recording.summary, expected);
+    }
+
+    #[test]
+    fn records_outcome_of_batches() {
+        let mut api = TestAPI {
+            erase_tweet_answer: Ok(EraseOutcome::Skipped(String::from("not owned"))),
+            ..Default::default()
+        };
+        let mut recording = RecordingAPI::new(&mut api);
+        let mut tweet = sample_tweet(1);
+        tweet.favorited = Some(true);
+        tweet.retweeted = Some(true);
+
+        recording.maintain_tweets(&[&tweet, &tweet], 2);
+
+        let expected = RunSummary {
+            skipped: 2,
+            unliked: 2,
+            unretweeted: 2,
+            ..Default::default()
+        };
+        assert_eq!(recording.summary, expected);
+    }
+
+    #[test]
+    fn merges_summaries() {
+        let mut summary = RunSummary {
+            deleted: 1,
+            failed: 2,
+            api_calls: 5,
+            ..Default::default()
+        };
+        summary.merge(&RunSummary {
+            deleted: 3,
+            skipped: 1,
+            api_calls: 4,
+            cancelled: true,
+            ..Default::default()
+        });
+
+        let expected = RunSummary {
+            deleted: 4,
+            skipped: 1,
+            failed: 2,
+            api_calls: 9,
+            cancelled: true,
+            ..Default::default()
+        };
+        assert_eq!(summary, expected);
+    }
+
+    #[test]
+    fn keeps_counts_of_each_timeline() {
+        let user = RunSummary {
+            scanned: 5,
+            deleted: 2,
+            ..Default::default()
+        };
+        let likes = RunSummary {
+            scanned: 3,
+            unliked: 3,
+            ..Default::default()
+        };
+        let mut summary = RunSummary::default();
+        summary.add_timeline("User Timeline", &user);
+        summary.add_timeline("Likes Timeline", &likes);
+        let mut other = RunSummary::default();
+        other.add_timeline("User Timeline", &user);
+        summary.merge(&other);
+
+        assert_eq!(summary.scanned, 13);
+        assert_eq!(summary.timelines["User Timeline"].deleted, 4);
+        assert_eq!(summary.timelines["Likes Timeline"], likes);
+        assert_eq!(
+            summary.to_string(),
+            "4 deleted, 0 skipped, 3 unliked, 0 unretweeted, 0 failed, 0 API calls, 13 tweets scanned\n  \
+             Likes Timeline: 0 deleted, 0 skipped, 3 unliked, 0 unretweeted, 0 failed, 0 API calls, 3 tweets scanned\n  \
+             User Timeline: 4 deleted, 0 skipped, 0 unliked, 0 unretweeted, 0 failed, 0 API calls, 10 tweets scanned"
+        );
+    }
+
+    #[test]
+    fn writes_and_reads_summaries() {
+        let summary = RunSummary {
+            scanned: 20,
+            deleted: 3,
+            skipped: 1,
+            unliked: 2,
+            failed: 1,
+            api_calls: 12,
+            started_at: Some(Utc.timestamp(1_570_000_000, 0)),
+            finished_at: Some(Utc.timestamp(1_570_000_042, 0)),
+            ..Default::default()
+        };
+
+        for extension in &["json", "csv"] {
+            let path = env::temp_dir().join(format!(
+                "twitter_privacy_summary_{}.{}",
+                std::process::id(),
+                extension
+            ));
+            let path = path.to_str().unwrap();
+
+            summary.write_to(path).unwrap();
+            let read = RunSummary::read_from(path);
+            fs::remove_file(path).unwrap();
+
+            assert_eq!(read, Ok(summary.clone()));
+        }
+    }
+
+    #[test]
+    fn writes_metrics_in_prometheus_format() {
+        let summary = RunSummary {
+            deleted: 3,
+            unliked: 2,
+            unretweeted: 1,
+            failed: 1,
+            api_calls: 12,
+            finished_at: Some(Utc.timestamp(1_570_000_042, 0)),
+            ..Default::default()
+        };
+        let path = env::temp_dir().join(format!(
+            "twitter_privacy_metrics_{}.prom",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        summary.write_metrics_to(path).unwrap();
+        let text = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let samples: Vec<(&str, i64)> = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                let mut parts = line.split(' ');
+                let name = parts.next().unwrap();
+                (name, parts.next().unwrap().parse().unwrap())
+            })
+            .collect();
+        let expected = vec![
+            ("twitter_privacy_tweets_deleted_total", 3),
+            ("twitter_privacy_tweets_skipped_total", 0),
+            ("twitter_privacy_likes_removed_total", 2),
+            ("twitter_privacy_retweets_removed_total", 1),
+            ("twitter_privacy_failures_total", 1),
+            ("twitter_privacy_direct_messages_deleted_total", 0),
+            ("twitter_privacy_api_calls_total", 12),
+            ("twitter_privacy_cancelled", 0),
+            ("twitter_privacy_last_run_timestamp_seconds", 1_570_000_042),
+        ];
+        assert_eq!(samples, expected);
+        assert!(text.contains("# TYPE twitter_privacy_tweets_deleted_total counter\n"));
+    }
+}
@@ -0,0 +1,85 @@
+use egg_mode::tweet::Tweet;
+use std::collections::{HashMap, HashSet};
+
+/// Returns the ids of the tweets that belong to a thread where at least one tweet is not old
+/// enough to be erased, as decided by `is_old`.
+///
+/// A thread is a chain of tweets linked by `in_reply_to_status_id`. We only follow replies to
+/// tweets included in `tweets`, so a reply to a tweet we haven't fetched starts its own thread.
+pub fn active_thread_ids<'t, I, F>(tweets: I, is_old: F) -> HashSet<u64>
+where
+    I: IntoIterator<Item = &'t Tweet>,
+    F: Fn(&Tweet) -> bool,
+{
+    let tweets: Vec<&Tweet> = tweets.into_iter().collect();
+    let ids: HashSet<u64> = tweets.iter().map(|t| t.id).collect();
+    let parents: HashMap<u64, u64> = tweets
+        .iter()
+        .filter_map(|t| t.in_reply_to_status_id.map(|parent| (t.id, parent)))
+        .filter(|(_, parent)| ids.contains(parent))
+        .collect();
+
+    // replies are always newer than the tweet they reply to, but we bound the walk anyway
+    let root_of = |id: u64| {
+        let mut root = id;
+        for _ in 0..tweets.len() {
+            match parents.get(&root) {
+                Some(parent) => root = *parent,
+                None => break,
+            }
+        }
+        root
+    };
+
+    let active_roots: HashSet<u64> = tweets
+        .iter()
+        .filter(|t| !is_old(t))
+        .map(|t| root_of(t.id))
+        .collect();
+    tweets
+        .iter()
+        .map(|t| t.id)
+        .filter(|id| active_roots.contains(&root_of(*id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::active_thread_ids;
+    use crate::tests::sample_tweet;
+    use egg_mode::tweet::Tweet;
+    use std::collections::HashSet;
+
+    fn reply(id: u64, days_ago: i64, parent: Option<u64>) -> Tweet {
+        let mut tweet = sample_tweet(days_ago);
+        tweet.id = id;
+        tweet.in_reply_to_status_id = parent;
+        tweet
+    }
+
+    fn is_old(tweet: &Tweet) -> bool {
+        tweet.id != 3 && tweet.id != 7
+    }
+
+    #[test]
+    fn returns_whole_threads_with_a_recent_tweet() {
+        let tweets = vec![
+            reply(1, 60, None),
+            reply(2, 50, Some(1)),
+            reply(3, 1, Some(2)),
+            reply(4, 60, None),
+            reply(5, 50, Some(4)),
+            reply(6, 40, Some(99)),
+            reply(7, 1, None),
+        ];
+
+        let expected: HashSet<u64> = vec![1, 2, 3, 7].into_iter().collect();
+        assert_eq!(active_thread_ids(&tweets, is_old), expected);
+    }
+
+    #[test]
+    fn returns_nothing_if_all_tweets_are_old() {
+        let tweets = vec![reply(1, 60, None), reply(2, 50, Some(1))];
+        assert!(active_thread_ids(&tweets, |_| true).is_empty());
+    }
+}